@@ -0,0 +1,242 @@
+//! Axis aligned bounding boxes.
+
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+/// Axis-aligned bounding box, stored as one interval per axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    x: Interval,
+    y: Interval,
+    z: Interval,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+impl Aabb {
+
+    /// Box containing nothing.
+    pub const EMPTY: Aabb = Aabb {
+        x: Interval::EMPTY,
+        y: Interval::EMPTY,
+        z: Interval::EMPTY,
+    };
+
+    /// Box from its extent along each axis, padded so that it is never flat.
+    pub fn new(x:Interval,y:Interval,z:Interval) -> Self {
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    /// Treat the two points a and b as extrema for the bounding box.
+    pub fn from_points(a:Point3,b:Point3) -> Self {
+        let x = if a.x <= b.x { Interval::new(a.x, b.x) } else { Interval::new(b.x, a.x) };
+        let y = if a.y <= b.y { Interval::new(a.y, b.y) } else { Interval::new(b.y, a.y) };
+        let z = if a.z <= b.z { Interval::new(a.z, b.z) } else { Interval::new(b.z, a.z) };
+
+        Self::new(x, y, z)
+    }
+
+    /// Smallest box containing both boxes.
+    pub fn surrounding(box0:&Aabb,box1:&Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(&box0.x, &box1.x),
+            y: Interval::enclosing(&box0.y, &box1.y),
+            z: Interval::enclosing(&box0.z, &box1.z),
+        }
+    }
+
+    /// Extent along axis `n`, 0 for x, 1 for y and 2 for z.
+    pub fn axis_interval(&self,n:usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    /// Whether the ray passes through the box during `ray_t`.
+    pub fn hit(&self,r:&Ray,ray_t:Interval) -> bool {
+        self.clip(r, ray_t).is_some()
+    }
+
+    /// Part of ray_t during which the ray is inside the box.
+    pub fn clip(&self,r:&Ray,ray_t:Interval) -> Option<Interval> {
+        let ray_orig = r.origin();
+        let ray_dir = r.direction();
+
+        let mut t_min = ray_t.min();
+        let mut t_max = ray_t.max();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / axis_value(ray_dir, axis);
+            let orig = axis_value(ray_orig, axis);
+
+            let t0 = (ax.min() - orig) * adinv;
+            let t1 = (ax.max() - orig) * adinv;
+
+            let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            if t_near > t_min { t_min = t_near; }
+            if t_far < t_max { t_max = t_far; }
+
+            if t_max <= t_min {
+                return None;
+            }
+        }
+
+        Some(Interval::new(t_min, t_max))
+    }
+
+    /// Index of the axis along which the box is the longest.
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Center of the box.
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min() + self.x.max()),
+            0.5 * (self.y.min() + self.y.max()),
+            0.5 * (self.z.min() + self.z.max()),
+        )
+    }
+
+    /// Whether the box contains nothing.
+    pub fn is_empty(&self) -> bool {
+        self.x.size() < 0.0 || self.y.size() < 0.0 || self.z.size() < 0.0
+    }
+
+    /// Area of the six faces, zero for an empty box.
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let dx = self.x.size();
+        let dy = self.y.size();
+        let dz = self.z.size();
+
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    // Avoid boxes with zero thickness (e.g. for flat primitives)
+    fn pad_to_minimums(&mut self) {
+        let delta = 0.0001;
+        if self.x.size() < delta { self.x = self.x.expand(delta); }
+        if self.y.size() < delta { self.y = self.y.expand(delta); }
+        if self.z.size() < delta { self.z = self.z.expand(delta); }
+    }
+
+    //Getters
+    /// Extent along the x axis.
+    pub fn x(&self) -> Interval {
+        self.x
+    }
+
+    /// Extent along the y axis.
+    pub fn y(&self) -> Interval {
+        self.y
+    }
+
+    /// Extent along the z axis.
+    pub fn z(&self) -> Interval {
+        self.z
+    }
+}
+
+/// Component of `v` along `axis`, 0 for x, 1 for y and 2 for z.
+pub fn axis_value(v:Point3,axis:usize) -> f64 {
+    match axis {
+        1 => v.y,
+        2 => v.z,
+        _ => v.x,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::Vec3;
+
+    #[test]
+    fn test_aabb_from_points_orders_extrema() {
+        let bbox = Aabb::from_points(Point3::new(1.0, -1.0, 2.0), Point3::new(-1.0, 1.0, 0.0));
+        assert_eq!(bbox.x(), Interval::new(-1.0, 1.0));
+        assert_eq!(bbox.y(), Interval::new(-1.0, 1.0));
+        assert_eq!(bbox.z(), Interval::new(0.0, 2.0));
+    }
+
+    #[test]
+    fn test_aabb_pads_flat_boxes() {
+        let bbox = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0));
+        assert!(bbox.z().size() > 0.0);
+    }
+
+    #[test]
+    fn test_aabb_surrounding() {
+        let a = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let b = Aabb::from_points(Point3::new(-1.0, 2.0, 0.5), Point3::new(0.5, 3.0, 0.6));
+        let c = Aabb::surrounding(&a, &b);
+        assert_eq!(c.x(), Interval::new(-1.0, 1.0));
+        assert_eq!(c.y(), Interval::new(0.0, 3.0));
+        assert_eq!(c.z(), Interval::new(0.0, 1.0));
+
+        assert_eq!(Aabb::surrounding(&Aabb::EMPTY, &a), a);
+    }
+
+    #[test]
+    fn test_aabb_hit_and_miss() {
+        let bbox = Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let towards = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let away = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, -1.0));
+        let beside = Ray::new(Point3::new(2.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert!(bbox.hit(&towards, Interval::new(0.0, f64::INFINITY)));
+        assert!(!bbox.hit(&away, Interval::new(0.0, f64::INFINITY)));
+        assert!(!bbox.hit(&beside, Interval::new(0.0, f64::INFINITY)));
+        // Box lies beyond the allowed range of t
+        assert!(!bbox.hit(&towards, Interval::new(0.0, 3.0)));
+    }
+
+    #[test]
+    fn test_aabb_clip() {
+        let bbox = Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let towards = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        assert_eq!(bbox.clip(&towards, Interval::new(0.0, f64::INFINITY)), Some(Interval::new(4.0, 6.0)));
+        assert_eq!(bbox.clip(&towards, Interval::new(4.5, 5.0)), Some(Interval::new(4.5, 5.0)));
+        assert_eq!(bbox.clip(&towards, Interval::new(0.0, 3.0)), None);
+    }
+
+    #[test]
+    fn test_aabb_longest_axis() {
+        let bbox = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 3.0, 2.0));
+        assert_eq!(bbox.longest_axis(), 1);
+        assert_eq!(bbox.centroid(), Point3::new(0.5, 1.5, 1.0));
+
+        let bbox = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 3.0, 5.0));
+        assert_eq!(bbox.longest_axis(), 2);
+    }
+
+    #[test]
+    fn test_aabb_surface_area() {
+        let bbox = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 3.0, 2.0));
+        assert_eq!(bbox.surface_area(), 2.0 * (3.0 + 6.0 + 2.0));
+        assert!(Aabb::EMPTY.is_empty());
+        assert_eq!(Aabb::EMPTY.surface_area(), 0.0);
+    }
+}
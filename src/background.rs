@@ -0,0 +1,134 @@
+//! What rays that miss every object see.
+
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::commons::PI;
+use crate::environment::EnvironmentMap;
+use crate::vec3::Vec3;
+
+/// Radiance seen by rays that escape the scene.
+#[derive(Clone)]
+pub enum Background {
+    /// No light at all.
+    Black,
+    /// The same color in every direction.
+    Solid(Color),
+    /// Blend between two colors by the height of the ray direction.
+    Gradient {
+        /// Color straight down.
+        bottom: Color,
+        /// Color straight up.
+        top: Color,
+    },
+    /// Image wrapped around the scene, without being sampled as a light.
+    Image(Arc<BackgroundImage>),
+    /// HDR map that also acts as a light source and is sampled directly.
+    Environment(Arc<EnvironmentMap>),
+}
+
+impl Background {
+    /// The white to blue sky of the book scenes.
+    pub fn sky() -> Self {
+        Background::Gradient {
+            bottom: Color::new(1.0, 1.0, 1.0),
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    /// Radiance arriving along `direction`.
+    pub fn color(&self, direction: Vec3) -> Color {
+        match self {
+            Background::Black => Color::new(0.0, 0.0, 0.0),
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let unit_direction = direction.unit_vector();
+                let a = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - a) * *bottom + a * *top
+            }
+            Background::Image(image) => image.lookup(direction),
+            Background::Environment(map) => map.radiance(direction),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::sky()
+    }
+}
+
+/// Latitude-longitude image wrapped around the scene, rows run from the top
+/// (+y) down to the bottom (-y).
+pub struct BackgroundImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl BackgroundImage {
+    /// Image from its pixels, top row first. Panics if their number does not
+    /// match the size.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count does not match {}x{}", width, height);
+        Self { width, height, pixels }
+    }
+
+    /// Pixel seen along `direction`, black for an empty image.
+    pub fn lookup(&self, direction: Vec3) -> Color {
+        if self.pixels.is_empty() {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // Same mapping as the sphere UVs: u goes around the y axis starting
+        // at -x, v from the bottom to the top
+        let d = direction.unit_vector();
+        let theta = (-d.y).clamp(-1.0, 1.0).acos();
+        let phi = (-d.z).atan2(d.x) + PI;
+        let u = phi / (2.0 * PI);
+        let v = theta / PI;
+
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = (((1.0 - v) * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solid_and_black() {
+        let direction = Vec3::new(0.3, -0.2, 1.0);
+        assert_eq!(Background::Black.color(direction), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(
+            Background::Solid(Color::new(0.1, 0.2, 0.3)).color(direction),
+            Color::new(0.1, 0.2, 0.3)
+        );
+    }
+
+    #[test]
+    fn test_gradient() {
+        let background = Background::sky();
+        assert_eq!(background.color(Vec3::new(0.0, 5.0, 0.0)), Color::new(0.5, 0.7, 1.0));
+        assert_eq!(background.color(Vec3::new(0.0, -1.0, 0.0)), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(background.color(Vec3::new(1.0, 0.0, 0.0)), Color::new(0.75, 0.85, 1.0));
+    }
+
+    #[test]
+    fn test_image_lookup() {
+        // 2x2 map: top row red/green, bottom row blue/white
+        let red = Color::new(1.0, 0.0, 0.0);
+        let green = Color::new(0.0, 1.0, 0.0);
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let background = Background::Image(Arc::new(BackgroundImage::new(2, 2, vec![red, green, blue, white])));
+
+        // u = 0.25 looks towards +z, u = 0.75 towards -z
+        assert_eq!(background.color(Vec3::new(0.0, 0.5, 1.0)), red);
+        assert_eq!(background.color(Vec3::new(0.0, 0.5, -1.0)), green);
+        assert_eq!(background.color(Vec3::new(0.0, -0.5, 1.0)), blue);
+        assert_eq!(background.color(Vec3::new(0.0, -0.5, -1.0)), white);
+    }
+}
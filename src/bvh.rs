@@ -0,0 +1,112 @@
+//! The original median split BVH.
+
+use crate::aabb::Aabb;
+use crate::flat_bvh::{FlatBvh, SplitStrategy};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+/// Bounding volume hierarchy splitting each span of objects at the median
+/// along its longest axis. Hits are the same as for the plain list. Built on
+/// [`FlatBvh`] with [`SplitStrategy::Median`].
+pub struct BvhNode {
+    bvh: FlatBvh,
+}
+
+impl BvhNode {
+    /// Builds the hierarchy over the objects of `list`.
+    pub fn new(list: HittableList) -> Self {
+        Self { bvh: FlatBvh::new(list, SplitStrategy::Median) }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    fn transmittance(&self, r: &Ray, t: &Interval) -> f64 {
+        self.bvh.transmittance(r, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::commons::{random_double, random_double_range, seed_random, INFINITY};
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::{Point3, Vec3};
+
+    fn random_world(count: usize) -> HittableList {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for _ in 0..count {
+            let center = Vec3::random_range(-10.0, 10.0);
+            list.add(Box::new(Sphere::new(center, random_double_range(0.1, 1.0), material.clone())));
+        }
+        list
+    }
+
+    #[test]
+    fn test_bvh_bounding_box_matches_list() {
+        let list = random_world(50);
+        let expected = list.bounding_box();
+        let bvh = BvhNode::new(list);
+
+        assert_eq!(bvh.bounding_box(), expected);
+    }
+
+    #[test]
+    fn test_bvh_empty_world_never_hits() {
+        let bvh = BvhNode::new(HittableList::new());
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(!bvh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_bvh_matches_linear_list() {
+        seed_random(1);
+
+        // Build the same spheres twice: once for the list, once for the tree
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let spheres: Vec<(Point3, f64)> = (0..100)
+            .map(|_| (Vec3::random_range(-10.0, 10.0), random_double_range(0.1, 1.0)))
+            .collect();
+
+        let mut list = HittableList::new();
+        let mut bvh_list = HittableList::new();
+        for (center, radius) in &spheres {
+            list.add(Box::new(Sphere::new(*center, *radius, material.clone())));
+            bvh_list.add(Box::new(Sphere::new(*center, *radius, material.clone())));
+        }
+        let bvh = BvhNode::new(bvh_list);
+
+        for _ in 0..1000 {
+            let origin = Vec3::random_range(-15.0, 15.0);
+            let direction = Vec3::random_unit_vector() + Vec3::new(0.0, 0.0, random_double() * 0.1);
+            let ray = Ray::new(origin, direction);
+
+            let mut list_rec = HitRecord::default();
+            let mut bvh_rec = HitRecord::default();
+            let list_hit = list.hit(&ray, &Interval::new(0.001, INFINITY), &mut list_rec);
+            let bvh_hit = bvh.hit(&ray, &Interval::new(0.001, INFINITY), &mut bvh_rec);
+
+            assert_eq!(list_hit, bvh_hit);
+            if list_hit {
+                assert_eq!(list_rec.t(), bvh_rec.t());
+                assert_eq!(list_rec.p(), bvh_rec.p());
+                assert_eq!(list_rec.normal(), bvh_rec.normal());
+            }
+        }
+    }
+}
@@ -1,29 +1,59 @@
-use super::*;
+//! The camera and the renderer.
+
 use rayon::prelude::*;
+use crate::background::Background;
+use crate::environment::EnvironmentMap;
 use crate::color::Color;
-use crate::commons::{degrees_to_radians, random_double};
+use crate::commons::{degrees_to_radians, mix_seed, random_double, seed_random, INFINITY};
+use crate::hittable::{HitRecord, Hittable};
+use crate::image::Image;
 use crate::interval::Interval;
-use crate::vec3::Point3;
-use std::io::{self,BufWriter,Write};
-use std::fs::File;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
 use indicatif::ProgressBar;
 use std::sync::Arc;
 
-#[derive(Clone, Copy)]
+/// Pinhole or thin lens camera. Set the public fields, then call
+/// [`Camera::render`].
+#[derive(Clone)]
 pub struct Camera{
+    /// Image width over height.
     pub aspect_ratio : f64,
+    /// Width of the rendered image in pixels.
     pub image_width : i32,
+    /// Random samples averaged for each pixel.
     pub samples_per_pixel : i32,
+    /// Bounces followed before a path is cut off.
     pub max_depth: i32,
 
+    /// Vertical field of view in degrees.
     pub vfov : f64,
+    /// Position of the camera.
     pub lookfrom : Point3,
+    /// Point the camera looks at.
     pub lookat: Point3,
+    /// Direction that is up in the image.
     pub vup: Vec3,
 
+    /// Angle in degrees of the cone from each pixel to the lens, zero for a
+    /// pinhole camera.
     pub defocus_angle:f64,
+    /// Distance from the camera to the plane in perfect focus.
     pub focus_dist:f64,
 
+    /// What rays that miss every object see.
+    pub background: Background,
+
+    /// Rays are spread uniformly over [shutter_open, shutter_close]. Moving
+    /// spheres travel between times 0 and 1 and hold still outside them.
+    pub shutter_open: f64,
+    /// Time the shutter closes.
+    pub shutter_close: f64,
+
+    /// Each pixel reseeds the random generator from this, so a render only
+    /// depends on the seed and not on how rayon schedules the pixels.
+    pub seed: u64,
+
     image_height :i32,
     pixel_samples_scale: f64,
     center : Point3,
@@ -51,6 +81,10 @@ impl Default for Camera {
             vup: Vec3::new(0.0, 1.0, 0.0),
             defocus_angle:0.0,
             focus_dist:10.0,
+            background: Background::default(),
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            seed: 0,
             image_height: 100,
             pixel_samples_scale: 1.0,
             center: Point3::new(0.0, 0.0, 0.0),
@@ -67,6 +101,8 @@ impl Default for Camera {
 }
 
 impl Camera {
+    /// Camera with the given settings, the shutter open from 0 to 1 and the
+    /// default background.
     pub fn new(
         aspect_ratio:f64,
         image_width:i32,
@@ -89,6 +125,10 @@ impl Camera {
             vup,
             defocus_angle,
             focus_dist,
+            background: Background::default(),
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            seed: 0,
             image_height: 0, // Calculated based on aspect_ratio
             pixel_samples_scale:0.0,
             center: Point3::new(0.0, 0.0, 0.0),
@@ -136,32 +176,70 @@ impl Camera {
         self.defocus_disk_v = v * defocus_radius;
     }
 
-    fn ray_color(r: &Ray,depth:i32 ,world: &dyn Hittable) -> Color{
+    fn ray_color(&self, r: &Ray,depth:i32 ,world: &dyn Hittable) -> Color{
+        self.trace(r, depth, world, None)
+    }
 
+    // `scatter_pdf` is the pdf the material picked `r` with, None for camera
+    // rays and mirror-like bounces. It is needed to weight environment hits
+    // against the light samples taken at the previous bounce.
+    fn trace(&self, r: &Ray, depth: i32, world: &dyn Hittable, scatter_pdf: Option<f64>) -> Color {
         if depth <= 0 {
             return Color::new(0.0, 0.0, 0.0);
         }
 
         let mut rec = HitRecord::default();
-    
+
         if world.hit(r, &Interval::new(0.001, INFINITY), &mut rec) {
             let mut scattered = Ray::default();
             let mut attenuation = Color::default();
+            let color_from_emission = rec.mat.emitted(rec.u, rec.v, &rec.p());
+
+            if !rec.mat.scatter(r, &rec, &mut attenuation, &mut scattered) {
+                return color_from_emission;
+            }
+
+            let pdf = rec.mat.scattering_pdf(r, &rec, &scattered);
+            let pdf = if pdf > 0.0 { Some(pdf) } else { None };
+
+            let mut color = color_from_emission;
+            if let (Background::Environment(map), Some(_)) = (&self.background, pdf) {
+                color += self.sample_environment(map, r, &rec, attenuation, world);
+            }
 
-            if rec.mat.scatter(&r, &rec, &mut attenuation, &mut scattered) {
-                let recursive_color = Camera::ray_color(&scattered, depth - 1, world);
-                return attenuation * recursive_color;
+            return color + attenuation * self.trace(&scattered, depth - 1, world, pdf);
+        }
+
+        match (&self.background, scatter_pdf) {
+            (Background::Environment(map), Some(pdf)) => {
+                let direction = r.direction().unit_vector();
+                map.radiance(direction) * power_heuristic(pdf, map.pdf(direction))
             }
+            _ => self.background.color(r.direction()),
+        }
+    }
+
+    // Next event estimation: shoot a shadow ray towards a direction picked
+    // by the environment map's luminance
+    fn sample_environment(&self, map: &EnvironmentMap, r: &Ray, rec: &HitRecord, attenuation: Color, world: &dyn Hittable) -> Color {
+        let Some((direction, light_pdf)) = map.sample(random_double(), random_double()) else {
+            return Color::new(0.0, 0.0, 0.0);
+        };
+
+        let shadow_ray = Ray::with_time(rec.p(), direction, r.time());
+        let scatter_pdf = rec.mat.scattering_pdf(r, rec, &shadow_ray);
+        if scatter_pdf <= 0.0 {
             return Color::new(0.0, 0.0, 0.0);
         }
-    
-        let unit_direction = r.direction().unit_vector();
-        let a = 0.5 * (unit_direction.y + 1.0);
-    
-        let white =  Color::new(1.0, 1.0, 1.0);
-        let blue =  Color::new(0.5, 0.7, 1.0);
-    
-        (1.0 - a) * white + a * blue
+
+        // Surfaces block the light, media in the way only dim it
+        let transmittance = world.transmittance(&shadow_ray, &Interval::new(0.001, INFINITY));
+        if transmittance <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        let weight = power_heuristic(light_pdf, scatter_pdf);
+        attenuation * map.radiance(direction) * (transmittance * scatter_pdf * weight / light_pdf)
     }
 
     fn sample_square() -> Vec3 {
@@ -192,8 +270,9 @@ impl Camera {
         };
 
         let ray_direction = pixel_sample - ray_origin;
-        
-        Ray::new(ray_origin, ray_direction)
+        let ray_time = self.shutter_open + random_double() * (self.shutter_close - self.shutter_open);
+
+        Ray::with_time(ray_origin, ray_direction, ray_time)
     }
 
     fn pixel_sample_square(&self) -> Vec3 {
@@ -202,20 +281,17 @@ impl Camera {
         (px * self.pixel_du) + (py * self.pixel_dv)
     }
 
-    pub fn render(&mut self, world: &dyn Hittable) -> io::Result<()> {
+    /// Renders `world` on all rayon threads. The pixels of the returned
+    /// image hold linear radiance; pass it to an encoder such as
+    /// [`save_ppm`](crate::ppm::save_ppm) to write it out.
+    pub fn render(&mut self, world: &dyn Hittable) -> Image {
         self.initialize();
 
-        let image_output_path = std::env::var("IMAGE_OUTPUT")
-            .expect("IMAGE_OUTPUT must be set");
-        let file = File::create(image_output_path)?;
-        let mut writer = BufWriter::new(file);
-        
-        writeln!(writer, "P3\n{} {}\n255", self.image_width, self.image_height)?;
-
         let samples = self.samples_per_pixel;
         let max_depth = self.max_depth;
         let scale = self.pixel_samples_scale;
         let image_width = self.image_width;
+        let seed = self.seed;
 
         // Add progress bar
         let progress = std::sync::Arc::new(ProgressBar::new((self.image_height * self.image_width) as u64));
@@ -235,10 +311,11 @@ impl Camera {
             .flat_map(move |j| {
                 let progress = progress.clone();
                 (0..image_width).into_par_iter().map(move |i| {
+                    seed_random(mix_seed(seed, (j * image_width + i) as u64));
                     let mut pixel_color = Color::new(0.0, 0.0, 0.0);
                     for _ in 0..samples {
                         let r = camera.get_ray(i, j);
-                        pixel_color += Camera::ray_color(&r, max_depth, world);
+                        pixel_color += camera.ray_color(&r, max_depth, world);
                     }
                     progress.inc(1);
                     pixel_color * scale
@@ -247,15 +324,17 @@ impl Camera {
             .collect();
 
         progress_ref.finish_with_message("Render complete");
-        // Write pixels to file
-        for pixel in pixels {
-            write_color(&mut writer, pixel)?;
-        }
-
-        Ok(())
+        Image::from_pixels(self.image_width as usize, self.image_height as usize, pixels)
     }
 }
 
+// Multiple importance sampling weight for a sample taken with pdf `a`
+// while a second strategy could have produced it with pdf `b`
+fn power_heuristic(a: f64, b: f64) -> f64 {
+    let (a2, b2) = (a * a, b * b);
+    if a2 + b2 > 0.0 { a2 / (a2 + b2) } else { 0.0 }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -308,4 +387,105 @@ mod tests {
         assert!(camera.pixel_dv.length() > 0.0);
         assert_eq!(camera.center, Point3::new(0.0, 0.0, -1.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_ray_color_adds_emission() {
+        use crate::hittable_list::HittableList;
+        use crate::material::DiffuseLight;
+        use crate::sphere::Sphere;
+
+        // Lights end the path, so the color is exactly the emitted radiance
+        let mut world = HittableList::new();
+        let light = Arc::new(DiffuseLight::new(Color::new(4.0, 2.0, 1.0)));
+        world.add(Box::new(Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, light)));
+
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let camera = Camera::default();
+        assert_eq!(camera.ray_color(&r, 10, &world), Color::new(4.0, 2.0, 1.0));
+        assert_eq!(camera.ray_color(&r, 0, &world), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_ray_color_misses_use_background() {
+        let world = crate::hittable_list::HittableList::new();
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let mut camera = Camera::default();
+        assert_eq!(camera.ray_color(&r, 10, &world), Color::new(0.5, 0.7, 1.0));
+
+        camera.background = Background::Black;
+        assert_eq!(camera.ray_color(&r, 10, &world), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_environment_lighting_converges() {
+        use crate::material::Lambertian;
+        use crate::quad::Quad;
+
+        // A diffuse floor under a uniform sky only ever sees the sky, so its
+        // radiance is exactly albedo * sky whatever the MIS weights are
+        let map = EnvironmentMap::new(Image::from_pixels(8, 4, vec![Color::new(1.0, 1.0, 1.0); 32])).unwrap();
+
+        let mut world = crate::hittable_list::HittableList::new();
+        let floor = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        world.add(Box::new(Quad::new(
+            Point3::new(-1000.0, 0.0, -1000.0),
+            Vec3::new(0.0, 0.0, 2000.0),
+            Vec3::new(2000.0, 0.0, 0.0),
+            floor,
+        )));
+
+        let camera = Camera { background: Background::Environment(Arc::new(map)), ..Camera::default() };
+
+        let r = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.3, -1.0, 0.2));
+        let samples = 4000;
+        let mut total = Color::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            total += camera.ray_color(&r, 10, &world);
+        }
+        let mean = total / samples as f64;
+
+        assert!((mean.x - 0.5).abs() < 0.02, "mean radiance {:?}", mean);
+    }
+
+    #[test]
+    fn test_rays_are_spread_over_shutter() {
+        let mut camera = Camera { shutter_open: 0.25, shutter_close: 0.5, ..Camera::default() };
+        camera.initialize();
+
+        let times: Vec<f64> = (0..200).map(|_| camera.get_ray(10, 10).time()).collect();
+        assert!(times.iter().all(|t| (0.25..=0.5).contains(t)));
+        assert!(times.iter().any(|t| *t < 0.3) && times.iter().any(|t| *t > 0.45));
+
+        // A closed shutter freezes everything at one instant
+        camera.shutter_close = 0.25;
+        assert_eq!(camera.get_ray(10, 10).time(), 0.25);
+    }
+
+    #[test]
+    fn test_same_seed_renders_same_image() {
+        use crate::hittable_list::HittableList;
+        use crate::material::Lambertian;
+        use crate::sphere::Sphere;
+
+        let mut world = HittableList::new();
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        world.add(Box::new(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, material)));
+
+        let render = |seed: u64| {
+            let mut camera = Camera {
+                image_width: 8,
+                samples_per_pixel: 4,
+                lookat: Point3::new(0.0, 0.0, -1.0),
+                seed,
+                ..Camera::default()
+            };
+            camera.render(&world)
+        };
+
+        let image = render(7);
+        assert_eq!((image.width(), image.height()), (8, 8));
+        assert_eq!(image, render(7));
+        assert_ne!(image, render(8));
+    }
+}
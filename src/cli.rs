@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueEnum};
+
+use raytracer::color_space::ColorSpace;
+use raytracer::image::BitDepth;
+use raytracer::tonemap::Operator;
+use raytracer::SplitStrategy;
+
+// Scene files shipped with the renderer, usable by name without a path. They
+// must not reference textures or meshes, which an installed binary can't find.
+pub const BUNDLED_SCENES: [(&str, &str); 6] = [
+    ("toy", include_str!("../scenes/toy.toml")),
+    ("cornell_box", include_str!("../scenes/cornell_box.toml")),
+    ("cornell_smoke", include_str!("../scenes/cornell_smoke.toml")),
+    ("perlin_spheres", include_str!("../scenes/perlin_spheres.toml")),
+    ("cloud", include_str!("../scenes/cloud.toml")),
+    ("motion_blur", include_str!("../scenes/motion_blur.toml")),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Ppm,
+    Pgm,
+    Png,
+    Exr,
+    Hdr,
+    Pfm,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("ppm") => Some(OutputFormat::Ppm),
+            Some("pgm") => Some(OutputFormat::Pgm),
+            Some("png") => Some(OutputFormat::Png),
+            Some("exr") => Some(OutputFormat::Exr),
+            Some("hdr") => Some(OutputFormat::Hdr),
+            Some("pfm") => Some(OutputFormat::Pfm),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneSource {
+    // The book's final scene, built in code from random numbers
+    Book,
+    Bundled { name: &'static str, text: &'static str },
+    File(PathBuf),
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Path tracer following the Ray Tracing in One Weekend books")]
+pub struct Cli {
+    #[arg(
+        default_value = "book",
+        help = "Built-in scene (book, toy, cornell_box, cornell_smoke, perlin_spheres, cloud, motion_blur) or path to a .toml scene file"
+    )]
+    pub scene: String,
+
+    #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..), help = "Image width in pixels, the height follows the aspect ratio")]
+    pub width: Option<i32>,
+
+    #[arg(short, long, value_parser = clap::value_parser!(i32).range(1..), help = "Samples per pixel")]
+    pub samples: Option<i32>,
+
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..), help = "Maximum number of bounces per path")]
+    pub max_depth: Option<i32>,
+
+    #[arg(long, help = "Seed for the random numbers; the same seed renders the same image [default: 0]")]
+    pub seed: Option<u64>,
+
+    #[arg(short = 'j', long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), help = "Number of render threads [default: one per core]")]
+    pub threads: Option<usize>,
+
+    #[arg(short, long, default_value = "image.ppm", help = "Where to write the image")]
+    pub output: PathBuf,
+
+    #[arg(long, value_enum, help = "Image format, guessed from the output extension when left out")]
+    pub format: Option<OutputFormat>,
+
+    #[arg(long, help = "Bits per sample for PPM, PGM and PNG output: 8 or 16 [default: 8]")]
+    pub bit_depth: Option<BitDepth>,
+
+    #[arg(long, help = "Write PPM and PGM as plain text (P3/P2) instead of binary (P6/P5)")]
+    pub plain: bool,
+
+    #[arg(long, help = "Store EXR channels as 32 bit floats instead of half floats")]
+    pub float: bool,
+
+    #[arg(long, help = "Tone mapping for 8/16 bit output: none, reinhard, reinhard-extended, aces, hable or agx [default: none]")]
+    pub tonemap: Option<Operator>,
+
+    #[arg(long, allow_negative_numbers = true, help = "Exposure adjustment in stops before tone mapping [default: 0]")]
+    pub exposure: Option<f64>,
+
+    #[arg(long, help = "Linear value shown as full white after tone mapping")]
+    pub white_point: Option<f64>,
+
+    #[arg(long, default_value = "srgb", help = "Output primaries and curve: srgb, rec709, display-p3 or rec2020; tagged in PNG, EXR and HDR files")]
+    pub color_space: ColorSpace,
+
+    #[arg(long, default_value = "sah", help = "How the BVH splits nodes: sah or median")]
+    pub bvh_split: SplitStrategy,
+}
+
+impl Cli {
+    pub fn scene_source(&self) -> Result<SceneSource, String> {
+        if self.scene == "book" {
+            return Ok(SceneSource::Book);
+        }
+        if let Some((name, text)) = BUNDLED_SCENES.iter().find(|(name, _)| *name == self.scene) {
+            return Ok(SceneSource::Bundled { name, text });
+        }
+
+        let path = PathBuf::from(&self.scene);
+        if path.extension().is_some_and(|e| e == "toml") || path.exists() {
+            return Ok(SceneSource::File(path));
+        }
+
+        let names: Vec<&str> = BUNDLED_SCENES.iter().map(|(name, _)| *name).collect();
+        Err(format!(
+            "unknown scene '{}', expected a .toml file or one of: book, {}",
+            self.scene,
+            names.join(", ")
+        ))
+    }
+
+    pub fn output_format(&self) -> Result<OutputFormat, String> {
+        self.format.or_else(|| OutputFormat::from_path(&self.output)).ok_or_else(|| {
+            format!("cannot tell the image format of '{}', pass --format (supported: ppm, pgm, png, exr, hdr, pfm)", self.output.display())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from([&["raytracer"], args].concat()).unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let cli = parse(&[]);
+        assert_eq!(cli.scene_source(), Ok(SceneSource::Book));
+        assert_eq!(cli.output, PathBuf::from("image.ppm"));
+        assert_eq!(cli.output_format(), Ok(OutputFormat::Ppm));
+        assert_eq!(cli.bvh_split, SplitStrategy::Sah);
+        assert_eq!((cli.width, cli.samples, cli.max_depth, cli.seed, cli.threads), (None, None, None, None, None));
+        assert_eq!((cli.bit_depth, cli.plain, cli.float), (None, false, false));
+        assert_eq!((cli.tonemap, cli.exposure, cli.white_point), (None, None, None));
+        assert_eq!(cli.color_space, ColorSpace::Srgb);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let cli = parse(&["cornell_box", "-w", "300", "-s", "16", "--max-depth", "8", "--seed", "42", "-j", "2", "-o", "out.ppm", "--bvh-split", "median"]);
+        assert!(matches!(cli.scene_source(), Ok(SceneSource::Bundled { name: "cornell_box", .. })));
+        assert_eq!((cli.width, cli.samples, cli.max_depth, cli.seed, cli.threads), (Some(300), Some(16), Some(8), Some(42), Some(2)));
+        assert_eq!(cli.bvh_split, SplitStrategy::Median);
+
+        let cli = parse(&["--tonemap", "agx", "--exposure", "-1.5", "--white-point", "8"]);
+        assert_eq!((cli.tonemap, cli.exposure, cli.white_point), (Some(Operator::Agx), Some(-1.5), Some(8.0)));
+        assert_eq!(parse(&["--color-space", "display-p3"]).color_space, ColorSpace::DisplayP3);
+    }
+
+    #[test]
+    fn test_invalid_arguments_are_rejected() {
+        assert!(Cli::try_parse_from(["raytracer", "--width", "0"]).is_err());
+        assert!(Cli::try_parse_from(["raytracer", "--samples", "many"]).is_err());
+        assert!(Cli::try_parse_from(["raytracer", "--bvh-split", "octree"]).is_err());
+        assert!(Cli::try_parse_from(["raytracer", "--format", "gif"]).is_err());
+        assert!(Cli::try_parse_from(["raytracer", "--bit-depth", "12"]).is_err());
+        assert!(Cli::try_parse_from(["raytracer", "--tonemap", "filmic"]).is_err());
+        assert!(Cli::try_parse_from(["raytracer", "--color-space", "adobe-rgb"]).is_err());
+    }
+
+    #[test]
+    fn test_scene_source() {
+        assert_eq!(parse(&["my/scene.toml"]).scene_source(), Ok(SceneSource::File(PathBuf::from("my/scene.toml"))));
+
+        let err = parse(&["cornell"]).scene_source().unwrap_err();
+        assert!(err.contains("unknown scene 'cornell'") && err.contains("cornell_box"), "{}", err);
+    }
+
+    #[test]
+    fn test_bundled_scenes_are_self_contained() {
+        for (name, text) in BUNDLED_SCENES {
+            let result = raytracer::scene::parse_scene(text, Path::new("/nonexistent"));
+            assert!(result.is_ok(), "{}: {}", name, result.err().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_output_format() {
+        assert!(parse(&["-o", "render.tiff"]).output_format().is_err());
+        assert_eq!(parse(&["-o", "render.tiff", "--format", "ppm"]).output_format(), Ok(OutputFormat::Ppm));
+        assert_eq!(parse(&["-o", "render.exr"]).output_format(), Ok(OutputFormat::Exr));
+        assert_eq!(parse(&["-o", "render.hdr"]).output_format(), Ok(OutputFormat::Hdr));
+        assert_eq!(parse(&["-o", "render.pfm"]).output_format(), Ok(OutputFormat::Pfm));
+        assert_eq!(parse(&["-o", "render.pgm"]).output_format(), Ok(OutputFormat::Pgm));
+        assert_eq!(parse(&["-o", "RENDER.PPM"]).output_format(), Ok(OutputFormat::Ppm));
+        assert_eq!(parse(&["-o", "render.png"]).output_format(), Ok(OutputFormat::Png));
+        assert_eq!(parse(&["-o", "render.png", "--bit-depth", "16"]).bit_depth, Some(BitDepth::Sixteen));
+    }
+}
@@ -1,17 +1,23 @@
+//! Colors and the sRGB transfer curve.
+
 use crate::{interval::Interval, vec3::Vec3};
 use std::io::{self, Write};
 
+/// Linear RGB, stored in a [`Vec3`] as x, y and z.
 pub type Color = Vec3;
 
+/// Black.
 pub fn default() -> Vec3 {
     Vec3::new(0.0, 0.0, 0.0)
 }
 
+/// Writes one pixel as a line of plain PPM text: sRGB encoded, each channel
+/// scaled to 0-255.
 pub fn write_color<W: Write>(writer: &mut W, pixel_color: Color) -> io::Result<()> {
 
-    let r: f64 = linear_to_gamma(pixel_color.x);
-    let g: f64 = linear_to_gamma(pixel_color.y);
-    let b: f64 = linear_to_gamma(pixel_color.z);
+    let r: f64 = linear_to_srgb(pixel_color.x);
+    let g: f64 = linear_to_srgb(pixel_color.y);
+    let b: f64 = linear_to_srgb(pixel_color.z);
 
     // Convert from [0,1] to [0,255]
     let intensity = Interval::new(0.000, 0.999);
@@ -23,11 +29,51 @@ pub fn write_color<W: Write>(writer: &mut W, pixel_color: Color) -> io::Result<(
     writeln!(writer, "{} {} {}", r_byte, g_byte, b_byte)
 }
 
-pub fn linear_to_gamma(linear_component:f64) -> f64{
-
-    if linear_component > 0.0 {
-        linear_component.sqrt()
+/// Piecewise sRGB curve: linear near black, then a 1/2.4 power.
+pub fn linear_to_srgb(linear_component: f64) -> f64 {
+    if linear_component <= 0.0 {
+        0.0
+    } else if linear_component <= 0.0031308 {
+        12.92 * linear_component
     } else {
+        1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of [`linear_to_srgb`], for colors read from 8/16 bit images.
+pub fn srgb_to_linear(srgb_component: f64) -> f64 {
+    if srgb_component <= 0.0 {
         0.0
+    } else if srgb_component <= 0.04045 {
+        srgb_component / 12.92
+    } else {
+        ((srgb_component + 0.055) / 1.055).powf(2.4)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_curve() {
+        assert_eq!(linear_to_srgb(0.0), 0.0);
+        assert_eq!(linear_to_srgb(-0.5), 0.0);
+        assert!((linear_to_srgb(1.0) - 1.0).abs() < 1e-12);
+        // Mid grey is encoded at about 46%, not the 42% of a sqrt
+        assert!((linear_to_srgb(0.18) - 0.4614).abs() < 1e-4);
+        // Both pieces meet at the break point
+        assert!((linear_to_srgb(0.0031308) - 0.04045).abs() < 1e-5);
+
+        for x in [0.001, 0.003, 0.01, 0.2, 0.5, 0.99] {
+            assert!((srgb_to_linear(linear_to_srgb(x)) - x).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_write_color_uses_srgb() {
+        let mut bytes = Vec::new();
+        write_color(&mut bytes, Color::new(1.0, 0.18, 0.0)).unwrap();
+        assert_eq!(bytes, b"255 118 0\n");
+    }
+}
@@ -0,0 +1,260 @@
+//! Output color spaces, their primaries and transfer curves.
+
+use std::str::FromStr;
+
+use crate::color::{linear_to_srgb, srgb_to_linear, Color};
+
+type Mat3 = [[f64; 3]; 3];
+
+/// CIE xy chromaticities of the red, green and blue primaries and the white
+/// point, in the order EXR and Radiance files list them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chromaticities {
+    /// Red primary.
+    pub red: (f64, f64),
+    /// Green primary.
+    pub green: (f64, f64),
+    /// Blue primary.
+    pub blue: (f64, f64),
+    /// White point.
+    pub white: (f64, f64),
+}
+
+const D65: (f64, f64) = (0.3127, 0.3290);
+
+/// Output color spaces. Rendering always happens in linear Rec. 709, the
+/// primaries of sRGB, so converting only matters for the wider gamuts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Rec. 709 primaries with the piecewise sRGB curve, what viewers assume
+    /// for untagged images.
+    #[default]
+    Srgb,
+    /// Rec. 709 primaries with the BT.709 camera curve used for video.
+    Rec709,
+    /// Wide gamut of recent Apple and Android displays, sRGB curve.
+    DisplayP3,
+    /// UHD television gamut with the BT.2020 curve.
+    Rec2020,
+}
+
+impl ColorSpace {
+    /// Primaries and white point of the space.
+    pub fn chromaticities(self) -> Chromaticities {
+        match self {
+            ColorSpace::Srgb | ColorSpace::Rec709 => {
+                Chromaticities { red: (0.640, 0.330), green: (0.300, 0.600), blue: (0.150, 0.060), white: D65 }
+            }
+            ColorSpace::DisplayP3 => {
+                Chromaticities { red: (0.680, 0.320), green: (0.265, 0.690), blue: (0.150, 0.060), white: D65 }
+            }
+            ColorSpace::Rec2020 => {
+                Chromaticities { red: (0.708, 0.292), green: (0.170, 0.797), blue: (0.131, 0.046), white: D65 }
+            }
+        }
+    }
+
+    /// ITU-T H.273 colour primaries and transfer characteristics, as stored in
+    /// the PNG cICP chunk.
+    pub fn code_points(self) -> (u8, u8) {
+        match self {
+            ColorSpace::Srgb => (1, 13),
+            ColorSpace::Rec709 => (1, 1),
+            ColorSpace::DisplayP3 => (12, 13),
+            ColorSpace::Rec2020 => (9, 14),
+        }
+    }
+
+    /// Transfer function from linear light to the [0, 1] signal stored in
+    /// 8 and 16 bit files.
+    pub fn encode(self, linear: f64) -> f64 {
+        match self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => linear_to_srgb(linear),
+            ColorSpace::Rec709 | ColorSpace::Rec2020 => linear_to_bt709(linear),
+        }
+    }
+
+    /// Inverse of [`ColorSpace::encode`].
+    pub fn decode(self, encoded: f64) -> f64 {
+        match self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => srgb_to_linear(encoded),
+            ColorSpace::Rec709 | ColorSpace::Rec2020 => bt709_to_linear(encoded),
+        }
+    }
+
+    /// Relative luminance Y of a linear color in this space.
+    pub fn luminance(self, c: Color) -> f64 {
+        let y = rgb_to_xyz(self.chromaticities())[1];
+        y[0] * c.x + y[1] * c.y + y[2] * c.z
+    }
+
+    /// Matrix taking linear colors in this space to linear colors in `target`.
+    pub fn conversion_to(self, target: ColorSpace) -> impl Fn(Color) -> Color {
+        let m = if self.chromaticities() == target.chromaticities() {
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        } else {
+            multiply(&inverse(&rgb_to_xyz(target.chromaticities())), &rgb_to_xyz(self.chromaticities()))
+        };
+        move |c: Color| {
+            Color::new(
+                m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+                m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+                m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z,
+            )
+        }
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "srgb" => Ok(ColorSpace::Srgb),
+            "rec709" => Ok(ColorSpace::Rec709),
+            "display-p3" => Ok(ColorSpace::DisplayP3),
+            "rec2020" => Ok(ColorSpace::Rec2020),
+            other => Err(format!(
+                "unknown color space '{}' (expected srgb, rec709, display-p3 or rec2020)",
+                other
+            )),
+        }
+    }
+}
+
+// BT.709 and BT.2020 share this curve, the constants are the exact ones that
+// make both pieces meet
+const BT709_ALPHA: f64 = 1.09929682680944;
+const BT709_BETA: f64 = 0.018053968510807;
+
+fn linear_to_bt709(linear: f64) -> f64 {
+    if linear <= 0.0 {
+        0.0
+    } else if linear < BT709_BETA {
+        4.5 * linear
+    } else {
+        BT709_ALPHA * linear.powf(0.45) - (BT709_ALPHA - 1.0)
+    }
+}
+
+fn bt709_to_linear(encoded: f64) -> f64 {
+    if encoded <= 0.0 {
+        0.0
+    } else if encoded < 4.5 * BT709_BETA {
+        encoded / 4.5
+    } else {
+        ((encoded + BT709_ALPHA - 1.0) / BT709_ALPHA).powf(1.0 / 0.45)
+    }
+}
+
+// Columns are the XYZ of the three primaries, scaled so that RGB (1, 1, 1)
+// lands on the white point with Y = 1
+fn rgb_to_xyz(c: Chromaticities) -> Mat3 {
+    let xyz = |(x, y): (f64, f64)| [x / y, 1.0, (1.0 - x - y) / y];
+    let (r, g, b) = (xyz(c.red), xyz(c.green), xyz(c.blue));
+    let primaries = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+
+    let white = xyz(c.white);
+    let inv = inverse(&primaries);
+    let s: Vec<f64> = (0..3).map(|i| (0..3).map(|j| inv[i][j] * white[j]).sum()).collect();
+
+    let mut m = primaries;
+    for row in m.iter_mut() {
+        for (value, scale) in row.iter_mut().zip(&s) {
+            *value *= scale;
+        }
+    }
+    m
+}
+
+fn multiply(a: &Mat3, b: &Mat3) -> Mat3 {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+fn inverse(m: &Mat3) -> Mat3 {
+    let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+        [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+        [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+    ];
+    let determinant = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    adjugate.map(|row| row.map(|value| value / determinant))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACES: [ColorSpace; 4] = [ColorSpace::Srgb, ColorSpace::Rec709, ColorSpace::DisplayP3, ColorSpace::Rec2020];
+
+    fn assert_color(a: Color, b: Color, tolerance: f64) {
+        assert!((a - b).length() < tolerance, "{:?} vs {:?}", a, b);
+    }
+
+    #[test]
+    fn test_known_conversion_matrices() {
+        // Published Rec. 709 to Rec. 2020 and Display P3 matrices, checked a
+        // column at a time
+        let to_2020 = ColorSpace::Srgb.conversion_to(ColorSpace::Rec2020);
+        assert_color(to_2020(Color::new(1.0, 0.0, 0.0)), Color::new(0.6274, 0.0691, 0.0164), 1e-4);
+        assert_color(to_2020(Color::new(0.0, 1.0, 0.0)), Color::new(0.3293, 0.9195, 0.0880), 1e-4);
+
+        let to_p3 = ColorSpace::Srgb.conversion_to(ColorSpace::DisplayP3);
+        assert_color(to_p3(Color::new(1.0, 0.0, 0.0)), Color::new(0.8225, 0.0332, 0.0171), 1e-4);
+        assert_color(to_p3(Color::new(0.0, 0.0, 1.0)), Color::new(0.0, 0.0, 0.9105), 1e-4);
+    }
+
+    #[test]
+    fn test_conversions_keep_white_and_round_trip() {
+        let color = Color::new(0.2, 0.7, 0.4);
+        for from in SPACES {
+            for to in SPACES {
+                let white = from.conversion_to(to)(Color::new(1.0, 1.0, 1.0));
+                assert_color(white, Color::new(1.0, 1.0, 1.0), 1e-9);
+
+                let back = to.conversion_to(from)(from.conversion_to(to)(color));
+                assert_color(back, color, 1e-9);
+            }
+        }
+        assert_eq!(ColorSpace::Srgb.conversion_to(ColorSpace::Rec709)(color), color);
+    }
+
+    #[test]
+    fn test_luminance() {
+        assert!((ColorSpace::Srgb.luminance(Color::new(1.0, 0.0, 0.0)) - 0.2126).abs() < 1e-4);
+        assert!((ColorSpace::Srgb.luminance(Color::new(0.0, 1.0, 0.0)) - 0.7152).abs() < 1e-4);
+        assert!((ColorSpace::Rec2020.luminance(Color::new(1.0, 0.0, 0.0)) - 0.2627).abs() < 1e-4);
+        for space in SPACES {
+            assert!((space.luminance(Color::new(1.0, 1.0, 1.0)) - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_transfer_functions() {
+        for space in SPACES {
+            assert_eq!(space.encode(0.0), 0.0);
+            assert!((space.encode(1.0) - 1.0).abs() < 1e-9);
+            assert_eq!(space.encode(-1.0), 0.0);
+            for x in [0.001, 0.01, 0.018, 0.02, 0.18, 0.5, 0.9] {
+                assert!((space.decode(space.encode(x)) - x).abs() < 1e-12, "{:?} {}", space, x);
+            }
+        }
+        // BT.709 is steeper than sRGB in the dark
+        assert!((ColorSpace::Rec709.encode(0.01) - 0.045).abs() < 1e-12);
+        assert!((ColorSpace::Rec709.encode(0.18) - 0.409).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("Display-P3".parse(), Ok(ColorSpace::DisplayP3));
+        assert_eq!("rec2020".parse(), Ok(ColorSpace::Rec2020));
+        assert!("adobe-rgb".parse::<ColorSpace>().is_err());
+    }
+}
@@ -1,5 +1,9 @@
+use std::cell::RefCell;
 use std::f64::consts::PI as STD_PI;
 
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
 pub const INFINITY: f64 = f64::INFINITY;
 pub const PI: f64 = STD_PI;
 
@@ -7,12 +11,33 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+// Every random number of the renderer comes from this per-thread generator,
+// so reseeding it makes the following draws reproducible
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_rng(&mut rand::rng()));
+}
+
+// Reseeds the calling thread's generator. Rendering does this per pixel from
+// Camera::seed, so renders don't depend on thread scheduling
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
 pub fn random_double() -> f64 {
-    rand::random_range(0.0..1.0)
+    RNG.with(|rng| rng.borrow_mut().random_range(0.0..1.0))
 }
 
 pub fn random_double_range(min:f64,max:f64) -> f64{
-    rand::random_range(min..max)
+    RNG.with(|rng| rng.borrow_mut().random_range(min..max))
+}
+
+// Spreads neighbouring inputs (pixel indices) over unrelated seeds
+// (SplitMix64 finalizer)
+pub fn mix_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
 
 
@@ -40,4 +65,23 @@ mod tests {
         let r = random_double_range(min, max);
         assert!(r >= min && r < max);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_seed_random_repeats_draws() {
+        seed_random(17);
+        let first: Vec<f64> = (0..8).map(|_| random_double()).collect();
+        seed_random(17);
+        let second: Vec<f64> = (0..8).map(|_| random_double()).collect();
+        assert_eq!(first, second);
+
+        seed_random(18);
+        assert_ne!(random_double(), first[0]);
+    }
+
+    #[test]
+    fn test_mix_seed() {
+        assert_eq!(mix_seed(1, 2), mix_seed(1, 2));
+        assert_ne!(mix_seed(1, 2), mix_seed(1, 3));
+        assert_ne!(mix_seed(1, 2), mix_seed(2, 2));
+    }
+}
@@ -0,0 +1,175 @@
+//! Volumes of constant density, like smoke or fog.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::color::Color;
+use crate::commons::{random_double, INFINITY};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::Vec3;
+
+/// Fog of uniform density filling a boundary. Rays scatter at a random
+/// distance inside with probability 1 - exp(-density * distance), otherwise
+/// they pass straight through. The boundary must be convex: only the first
+/// entry and exit are looked at.
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    /// Fog scattering equally in every direction, tinted by `albedo`.
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, albedo: Color) -> Self {
+        Self::with_phase_function(boundary, density, Arc::new(Isotropic::new(albedo)))
+    }
+
+    /// Like [`ConstantMedium::new`] with the tint taken from a texture.
+    pub fn from_texture(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+        Self::with_phase_function(boundary, density, Arc::new(Isotropic::from_texture(tex)))
+    }
+
+    /// Fog scattering with any phase function, e.g. a
+    /// [`HenyeyGreenstein`](crate::material::HenyeyGreenstein).
+    pub fn with_phase_function(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+        Self { boundary, neg_inv_density: -1.0 / density, phase_function }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        // Find the boundary along the whole line, so rays starting inside
+        // still see where they entered
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+
+        if !self.boundary.hit(r, &Interval::UNIVERSE, &mut rec1) {
+            return false;
+        }
+        if !self.boundary.hit(r, &Interval::new(rec1.t() + 0.0001, INFINITY), &mut rec2) {
+            return false;
+        }
+
+        // Only the part of the segment inside the query interval counts
+        let t_enter = rec1.t().max(t.min()).max(0.0);
+        let t_exit = rec2.t().min(t.max());
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double().ln();
+
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.set_t(t_enter + hit_distance / ray_length);
+        rec.set_p(r.at(rec.t()));
+
+        // The normal and side mean nothing inside a volume
+        rec.set_normal(Vec3::new(1.0, 0.0, 0.0));
+        rec.front_face = true;
+        rec.mat = self.phase_function.clone();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::Point3;
+
+    // Unit sphere at the origin
+    fn fog(density: f64) -> ConstantMedium {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let boundary = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material));
+        ConstantMedium::new(boundary, density, Color::new(0.9, 0.9, 0.9))
+    }
+
+    fn hit_fraction(medium: &ConstantMedium, r: &Ray, t: &Interval) -> f64 {
+        let samples = 20000;
+        let mut hits = 0;
+        for _ in 0..samples {
+            let mut rec = HitRecord::default();
+            if medium.hit(r, t, &mut rec) {
+                assert!(t.surrounds(rec.t()) || rec.t() == t.min());
+                hits += 1;
+            }
+        }
+        hits as f64 / samples as f64
+    }
+
+    #[test]
+    fn test_scattering_follows_beer_lambert() {
+        let medium = fog(0.5);
+        // Two units of fog along the diameter; the unnormalized direction
+        // must not change the result
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 2.0));
+        let expected = 1.0 - (-0.5 * 2.0f64).exp();
+
+        let fraction = hit_fraction(&medium, &r, &Interval::new(0.001, INFINITY));
+        assert!((fraction - expected).abs() < 0.02, "{} vs {}", fraction, expected);
+    }
+
+    #[test]
+    fn test_ray_starting_inside() {
+        let medium = fog(0.5);
+        // Only the unit of fog in front of the origin counts
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let expected = 1.0 - (-0.5f64).exp();
+
+        let fraction = hit_fraction(&medium, &r, &Interval::new(0.001, INFINITY));
+        assert!((fraction - expected).abs() < 0.02, "{} vs {}", fraction, expected);
+
+        let mut rec = HitRecord::default();
+        while !medium.hit(&r, &Interval::new(0.001, INFINITY), &mut rec) {}
+        assert!(rec.t() > 0.0 && rec.t() <= 1.0);
+        assert!((rec.p() - Point3::new(0.0, 0.0, rec.t())).length() < 1e-12);
+    }
+
+    #[test]
+    fn test_interval_clips_the_segment() {
+        let medium = fog(0.5);
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+
+        // Stopping halfway through the sphere leaves one unit of fog
+        let expected = 1.0 - (-0.5f64).exp();
+        let fraction = hit_fraction(&medium, &r, &Interval::new(0.001, 5.0));
+        assert!((fraction - expected).abs() < 0.02, "{} vs {}", fraction, expected);
+
+        // Intervals ending before or starting after the fog never scatter
+        assert_eq!(hit_fraction(&medium, &r, &Interval::new(0.001, 3.5)), 0.0);
+        assert_eq!(hit_fraction(&medium, &r, &Interval::new(6.5, INFINITY)), 0.0);
+    }
+
+    #[test]
+    fn test_missing_and_behind() {
+        let medium = fog(100.0);
+        let mut rec = HitRecord::default();
+
+        let miss = Ray::new(Point3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!medium.hit(&miss, &Interval::new(0.001, INFINITY), &mut rec));
+
+        let behind = Ray::new(Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!medium.hit(&behind, &Interval::new(0.001, INFINITY), &mut rec));
+
+        // Dense fog scatters almost at the boundary
+        let r = Ray::new(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(medium.hit(&r, &Interval::new(0.001, INFINITY), &mut rec));
+        assert!(rec.t() >= 4.0 && rec.t() < 4.2);
+        assert_eq!(medium.bounding_box().x(), Interval::new(-1.0, 1.0));
+    }
+}
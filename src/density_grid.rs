@@ -0,0 +1,220 @@
+//! Voxel grids of densities for heterogeneous volumes.
+
+use std::fs;
+use std::path::Path;
+
+use crate::parse_error::ParseError;
+use crate::perlin::Perlin;
+use crate::vec3::Point3;
+
+/// Voxel densities covering the unit cube, stored x fastest, then y, then z.
+/// Values sit at the voxel centers and are blended trilinearly in between.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    data: Vec<f64>,
+    max: f64,
+}
+
+impl DensityGrid {
+    /// Grid of `nx` by `ny` by `nz` voxels from their densities, x fastest. Fails
+    /// when `data` does not hold exactly that many values.
+    pub fn new(nx: usize, ny: usize, nz: usize, data: Vec<f64>) -> Result<Self, ParseError> {
+        let count = voxel_count(nx, ny, nz)?;
+        if data.len() != count {
+            return Err(ParseError::record(
+                data.len().min(count),
+                format!("{} voxels do not fill a {}x{}x{} grid", data.len(), nx, ny, nz),
+            ));
+        }
+        let max = data.iter().copied().fold(0.0, f64::max);
+        Ok(Self { nx, ny, nz, data, max })
+    }
+
+    /// Evaluates `density` at every voxel center, given in [0, 1]^3.
+    pub fn from_fn(nx: usize, ny: usize, nz: usize, density: impl Fn(Point3) -> f64) -> Result<Self, ParseError> {
+        let count = voxel_count(nx, ny, nz)?;
+        let mut data = Vec::new();
+        data.try_reserve_exact(count)
+            .map_err(|_| ParseError::record(0, format!("not enough memory for a {}x{}x{} grid", nx, ny, nz)))?;
+        for k in 0..nz {
+            for j in 0..ny {
+                for i in 0..nx {
+                    let p = Point3::new(
+                        (i as f64 + 0.5) / nx as f64,
+                        (j as f64 + 0.5) / ny as f64,
+                        (k as f64 + 0.5) / nz as f64,
+                    );
+                    data.push(density(p).max(0.0));
+                }
+            }
+        }
+        Self::new(nx, ny, nz, data)
+    }
+
+    /// Puffy ball of turbulence that fades out towards the faces of the cube.
+    pub fn perlin_cloud(seed: u64, resolution: usize) -> Result<Self, ParseError> {
+        let noise = Perlin::new(seed);
+        Self::from_fn(resolution, resolution, resolution, |p| {
+            let centered = 2.0 * p - Point3::new(1.0, 1.0, 1.0);
+            let falloff = 1.0 - centered.length_squared();
+            falloff * (0.5 + 2.0 * noise.turb(&(4.0 * p), 6))
+        })
+    }
+
+    /// Reads a raw voxel file, see [`DensityGrid::parse_raw`].
+    pub fn load_raw<P: AsRef<Path>>(path: P, nx: usize, ny: usize, nz: usize) -> Result<Self, ParseError> {
+        let bytes = fs::read(path)?;
+        DensityGrid::parse_raw(&bytes, nx, ny, nz)
+    }
+
+    /// Headerless voxel dump. The size of the data tells the sample type:
+    /// one byte per voxel is read as u8 scaled to [0, 1], four bytes as
+    /// little endian f32.
+    pub fn parse_raw(bytes: &[u8], nx: usize, ny: usize, nz: usize) -> Result<Self, ParseError> {
+        let count = voxel_count(nx, ny, nz)?;
+        let data = if bytes.len() == count {
+            bytes.iter().map(|&b| b as f64 / 255.0).collect()
+        } else if count.checked_mul(4) == Some(bytes.len()) {
+            bytes
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f64)
+                .collect()
+        } else {
+            return Err(ParseError::record(
+                0,
+                format!(
+                    "{} bytes do not hold {}x{}x{} u8 ({} bytes) or f32 ({} bytes) voxels",
+                    bytes.len(),
+                    nx,
+                    ny,
+                    nz,
+                    count,
+                    count.saturating_mul(4)
+                ),
+            ));
+        };
+
+        let grid = Self::new(nx, ny, nz, data)?;
+        if let Some(index) = grid.data.iter().position(|d| !d.is_finite() || *d < 0.0) {
+            return Err(ParseError::record(index, format!("invalid density {}", grid.data[index])));
+        }
+        Ok(grid)
+    }
+
+    /// Number of voxels along x, y and z.
+    pub fn dimensions(&self) -> (usize, usize, usize) {
+        (self.nx, self.ny, self.nz)
+    }
+
+    /// Upper bound of sample() anywhere in the grid.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn voxel(&self, i: i64, j: i64, k: i64) -> f64 {
+        let i = i.clamp(0, self.nx as i64 - 1) as usize;
+        let j = j.clamp(0, self.ny as i64 - 1) as usize;
+        let k = k.clamp(0, self.nz as i64 - 1) as usize;
+        self.data[(k * self.ny + j) * self.nx + i]
+    }
+
+    /// Density at a point of the unit cube, zero outside of it.
+    pub fn sample(&self, p: Point3) -> f64 {
+        if self.data.is_empty() || !(0.0..=1.0).contains(&p.x) || !(0.0..=1.0).contains(&p.y) || !(0.0..=1.0).contains(&p.z) {
+            return 0.0;
+        }
+
+        let x = p.x * self.nx as f64 - 0.5;
+        let y = p.y * self.ny as f64 - 0.5;
+        let z = p.z * self.nz as f64 - 0.5;
+        let (i, j, k) = (x.floor(), y.floor(), z.floor());
+        let (tx, ty, tz) = (x - i, y - j, z - k);
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+
+        let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+        let c00 = lerp(self.voxel(i, j, k), self.voxel(i + 1, j, k), tx);
+        let c10 = lerp(self.voxel(i, j + 1, k), self.voxel(i + 1, j + 1, k), tx);
+        let c01 = lerp(self.voxel(i, j, k + 1), self.voxel(i + 1, j, k + 1), tx);
+        let c11 = lerp(self.voxel(i, j + 1, k + 1), self.voxel(i + 1, j + 1, k + 1), tx);
+
+        lerp(lerp(c00, c10, ty), lerp(c01, c11, ty), tz)
+    }
+}
+
+fn voxel_count(nx: usize, ny: usize, nz: usize) -> Result<usize, ParseError> {
+    nx.checked_mul(ny)
+        .and_then(|count| count.checked_mul(nz))
+        .ok_or_else(|| ParseError::record(0, format!("grid size {}x{}x{} is too large", nx, ny, nz)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn test_trilinear_sampling() {
+        // Density grows along x only
+        let grid = DensityGrid::new(2, 1, 1, vec![0.0, 1.0]).unwrap();
+
+        assert_eq!(grid.max(), 1.0);
+        assert!((grid.sample(Point3::new(0.25, 0.5, 0.5)) - 0.0).abs() < EPSILON);
+        assert!((grid.sample(Point3::new(0.5, 0.5, 0.5)) - 0.5).abs() < EPSILON);
+        assert!((grid.sample(Point3::new(0.75, 0.1, 0.9)) - 1.0).abs() < EPSILON);
+        // Clamped to the outer voxel near the faces, empty outside
+        assert!((grid.sample(Point3::new(1.0, 0.5, 0.5)) - 1.0).abs() < EPSILON);
+        assert_eq!(grid.sample(Point3::new(1.1, 0.5, 0.5)), 0.0);
+    }
+
+    #[test]
+    fn test_voxel_order_is_x_fastest() {
+        let grid = DensityGrid::from_fn(2, 3, 4, |p| p.x + 10.0 * p.y + 100.0 * p.z).unwrap();
+        assert_eq!(grid.dimensions(), (2, 3, 4));
+        assert!((grid.voxel(1, 2, 3) - (0.75 + 10.0 * 5.0 / 6.0 + 100.0 * 0.875)).abs() < EPSILON);
+        assert!((grid.voxel(0, 0, 0) - (0.25 + 10.0 / 6.0 + 12.5)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_parse_raw() {
+        let grid = DensityGrid::parse_raw(&[0, 255, 51, 102], 2, 2, 1).unwrap();
+        assert_eq!(grid.voxel(1, 0, 0), 1.0);
+        assert!((grid.voxel(0, 1, 0) - 0.2).abs() < EPSILON);
+
+        let floats: Vec<u8> = [0.5f32, 2.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        let grid = DensityGrid::parse_raw(&floats, 1, 1, 2).unwrap();
+        assert_eq!(grid.voxel(0, 0, 1), 2.0);
+        assert_eq!(grid.max(), 2.0);
+
+        let err = DensityGrid::parse_raw(&[0, 1, 2], 2, 2, 1).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 0, .. }));
+
+        let negative: Vec<u8> = [0.5f32, -1.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        let err = DensityGrid::parse_raw(&negative, 2, 1, 1).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_huge_sizes_fail_cleanly() {
+        let huge = usize::MAX / 2;
+        assert!(DensityGrid::parse_raw(&[0, 1], huge, huge, 1).is_err());
+        assert!(DensityGrid::parse_raw(&[0, 1], usize::MAX / 4 + 1, 1, 1).is_err());
+        assert!(DensityGrid::new(huge, 3, 1, vec![0.0; 2]).is_err());
+        assert!(DensityGrid::new(2, 2, 1, vec![0.0; 3]).is_err());
+        assert!(DensityGrid::from_fn(huge, huge, huge, |_| 1.0).is_err());
+        assert!(DensityGrid::from_fn(1 << 20, 1 << 20, 1 << 20, |_| 1.0).is_err());
+    }
+
+    #[test]
+    fn test_perlin_cloud() {
+        let a = DensityGrid::perlin_cloud(3, 16).unwrap();
+        assert_eq!(a, DensityGrid::perlin_cloud(3, 16).unwrap());
+        assert!(a.max() > 0.0);
+        // Empty in the corners
+        assert_eq!(a.voxel(0, 0, 0), 0.0);
+        assert!(a.sample(Point3::new(0.5, 0.5, 0.5)) > 0.0);
+    }
+}
@@ -0,0 +1,148 @@
+// Piecewise constant distribution over [0, 1) used for importance sampling
+#[derive(Debug, Clone)]
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].max(0.0) / n as f64;
+        }
+
+        let integral = cdf[n];
+        for (i, value) in cdf.iter_mut().enumerate().skip(1) {
+            // Fall back to uniform sampling when everything is zero
+            *value = if integral > 0.0 { *value / integral } else { i as f64 / n as f64 };
+        }
+
+        Self { func, cdf, integral }
+    }
+
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // Maps a uniform u to (x, pdf of x, index of the segment x fell into)
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        let n = self.len();
+        let offset = (self.cdf.partition_point(|&c| c <= u).max(1) - 1).min(n - 1);
+
+        let mut du = u - self.cdf[offset];
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        if width > 0.0 {
+            du /= width;
+        }
+
+        let x = (offset as f64 + du.clamp(0.0, 1.0)) / n as f64;
+        (x.min(1.0 - f64::EPSILON), self.pdf_at(offset), offset)
+    }
+
+    pub fn pdf(&self, x: f64) -> f64 {
+        let index = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        self.pdf_at(index)
+    }
+
+    fn pdf_at(&self, index: usize) -> f64 {
+        if self.integral > 0.0 { self.func[index].max(0.0) / self.integral } else { 1.0 }
+    }
+}
+
+// 2D distribution over [0, 1)^2 sampled as a marginal over rows (v) followed
+// by the conditional distribution of the chosen row (u)
+#[derive(Debug, Clone)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // `func` holds `width * height` values, row by row
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+
+        let conditional: Vec<Distribution1D> = func.chunks_exact(width).map(|row| Distribution1D::new(row.to_vec())).collect();
+        let marginal = Distribution1D::new(conditional.iter().map(Distribution1D::integral).collect());
+
+        Self { conditional, marginal }
+    }
+
+    // Returns (u, v, pdf)
+    pub fn sample(&self, u1: f64, u2: f64) -> (f64, f64, f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.conditional[row].sample(u1);
+        (u, v, pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let row = ((v * self.marginal.len() as f64) as usize).min(self.marginal.len() - 1);
+        self.conditional[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn test_sample_follows_weights() {
+        let distribution = Distribution1D::new(vec![1.0, 3.0]);
+        assert_eq!(distribution.integral(), 2.0);
+
+        // The first quarter of u lands in the first segment
+        let (x, pdf, index) = distribution.sample(0.125);
+        assert!((x - 0.25).abs() < EPSILON);
+        assert!((pdf - 0.5).abs() < EPSILON);
+        assert_eq!(index, 0);
+
+        let (x, pdf, index) = distribution.sample(0.625);
+        assert!((x - 0.75).abs() < EPSILON);
+        assert!((pdf - 1.5).abs() < EPSILON);
+        assert_eq!(index, 1);
+
+        assert_eq!(distribution.pdf(0.9), 1.5);
+    }
+
+    #[test]
+    fn test_zero_function_is_uniform() {
+        let distribution = Distribution1D::new(vec![0.0; 4]);
+        let (x, pdf, index) = distribution.sample(0.6);
+        assert!((x - 0.6).abs() < EPSILON);
+        assert_eq!(pdf, 1.0);
+        assert_eq!(index, 2);
+    }
+
+    #[test]
+    fn test_zero_segments_are_never_sampled() {
+        let distribution = Distribution1D::new(vec![0.0, 1.0, 0.0]);
+        for u in [0.0, 0.3, 0.7, 0.999999] {
+            let (x, _, index) = distribution.sample(u);
+            assert_eq!(index, 1);
+            assert!((1.0 / 3.0..2.0 / 3.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_2d_sample_and_pdf_agree() {
+        // One bright texel in a 4x2 grid
+        let mut func = vec![1.0; 8];
+        func[6] = 25.0;
+        let distribution = Distribution2D::new(&func, 4, 2);
+
+        let (u, v, pdf) = distribution.sample(0.9, 0.9);
+        assert_eq!(((u * 4.0) as usize, (v * 2.0) as usize), (2, 1));
+        assert!((pdf - distribution.pdf(u, v)).abs() < EPSILON);
+        // 25 over a mean of 32/8
+        assert!((pdf - 25.0 / 4.0).abs() < EPSILON);
+    }
+}
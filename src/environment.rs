@@ -0,0 +1,195 @@
+//! Environment maps lighting the scene from every direction.
+
+use std::path::Path;
+
+use crate::color::Color;
+use crate::commons::{degrees_to_radians, PI};
+use crate::distribution::Distribution2D;
+use crate::image::{load_image, Image};
+use crate::parse_error::ParseError;
+use crate::vec3::Vec3;
+
+/// Latitude-longitude environment map lighting the scene from infinitely far
+/// away. Directions are importance sampled by luminance so small bright
+/// regions such as the sun are found without relying on luck.
+pub struct EnvironmentMap {
+    image: Image,
+    distribution: Distribution2D,
+    // Rotation around the y axis, in radians
+    rotation: f64,
+    intensity: f64,
+}
+
+impl EnvironmentMap {
+    /// Environment map over `image`, which must not be empty.
+    pub fn new(image: Image) -> Result<Self, ParseError> {
+        let (width, height) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return Err(ParseError::record(0, format!("environment map is empty ({}x{})", width, height)));
+        }
+
+        // Rows near the poles cover less solid angle, weight them by sin(theta)
+        let space = image.color_space();
+        let mut weights = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let sin_theta = (PI * (y as f64 + 0.5) / height as f64).sin();
+            weights.extend((0..width).map(|x| space.luminance(image.pixel(x, y)) * sin_theta));
+        }
+
+        Ok(Self {
+            distribution: Distribution2D::new(&weights, width, height),
+            image,
+            rotation: 0.0,
+            intensity: 1.0,
+        })
+    }
+
+    /// Loads a .hdr or .pfm file, LDR images work too but make poor lights.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Self::new(load_image(path)?)
+    }
+
+    /// Turns the map by `degrees` around the y axis.
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees_to_radians(degrees);
+        self
+    }
+
+    /// Scales the radiance of the map, 1 by default.
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Radiance arriving along `direction`.
+    pub fn radiance(&self, direction: Vec3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        let x = ((u * self.image.width() as f64) as usize).min(self.image.width() - 1);
+        let y = ((v * self.image.height() as f64) as usize).min(self.image.height() - 1);
+        self.intensity * self.image.pixel(x, y)
+    }
+
+    /// Picks a direction proportional to the map's luminance. Returns the unit
+    /// direction and its solid angle pdf, or None for degenerate samples.
+    pub fn sample(&self, u1: f64, u2: f64) -> Option<(Vec3, f64)> {
+        let (u, v, map_pdf) = self.distribution.sample(u1, u2);
+        let sin_theta = (v * PI).sin();
+        if map_pdf <= 0.0 || sin_theta <= 0.0 {
+            return None;
+        }
+
+        Some((self.uv_to_direction(u, v), map_pdf / (2.0 * PI * PI * sin_theta)))
+    }
+
+    /// Solid angle pdf of sampling `direction` through `sample`.
+    pub fn pdf(&self, direction: Vec3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (v * PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+
+    // u goes around the y axis starting at -x, v from the top (+y) down
+    fn direction_to_uv(&self, direction: Vec3) -> (f64, f64) {
+        let d = rotate_y(direction.unit_vector(), -self.rotation);
+        let theta = d.y.clamp(-1.0, 1.0).acos();
+        let phi = (-d.z).atan2(d.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn uv_to_direction(&self, u: f64, v: f64) -> Vec3 {
+        let theta = v * PI;
+        let phi = u * 2.0 * PI - PI;
+        let d = Vec3::new(theta.sin() * phi.cos(), theta.cos(), -theta.sin() * phi.sin());
+        rotate_y(d, self.rotation)
+    }
+}
+
+fn rotate_y(v: Vec3, angle: f64) -> Vec3 {
+    let (sin, cos) = angle.sin_cos();
+    Vec3::new(cos * v.x + sin * v.z, v.y, -sin * v.x + cos * v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn constant_map(color: Color) -> EnvironmentMap {
+        EnvironmentMap::new(Image::from_pixels(8, 4, vec![color; 32])).unwrap()
+    }
+
+    // Black map with a single bright texel
+    fn sun_map() -> EnvironmentMap {
+        let mut image = Image::new(16, 8);
+        image.set_pixel(5, 2, Color::new(1000.0, 900.0, 800.0));
+        EnvironmentMap::new(image).unwrap()
+    }
+
+    #[test]
+    fn test_uv_round_trip() {
+        let map = constant_map(Color::new(1.0, 1.0, 1.0)).with_rotation(30.0);
+        let direction = map.uv_to_direction(0.3, 0.6);
+        let (u, v) = map.direction_to_uv(direction);
+
+        assert!((u - 0.3).abs() < EPSILON);
+        assert!((v - 0.6).abs() < EPSILON);
+        assert!((direction.length() - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_radiance_and_intensity() {
+        let map = constant_map(Color::new(0.5, 1.0, 2.0)).with_intensity(2.0);
+        assert_eq!(map.radiance(Vec3::new(0.2, 0.7, -0.1)), Color::new(1.0, 2.0, 4.0));
+    }
+
+    #[test]
+    fn test_rotation_moves_the_map() {
+        let map = sun_map();
+        let (sun, _) = map.sample(0.5, 0.5).unwrap();
+        assert!(map.radiance(sun).x > 0.0);
+
+        // Rotated by 90 degrees the sun sits a quarter turn around y
+        let rotated = sun_map().with_rotation(90.0);
+        assert_eq!(rotated.radiance(sun), Color::new(0.0, 0.0, 0.0));
+        assert!(rotated.radiance(rotate_y(sun, degrees_to_radians(90.0))).x > 0.0);
+    }
+
+    #[test]
+    fn test_sampling_finds_the_sun() {
+        let map = sun_map();
+        for (u1, u2) in [(0.01, 0.02), (0.5, 0.5), (0.99, 0.97)] {
+            let (direction, pdf) = map.sample(u1, u2).unwrap();
+            assert!(map.radiance(direction).x > 0.0);
+            assert!((pdf - map.pdf(direction)).abs() < 1e-6 * pdf);
+        }
+    }
+
+    #[test]
+    fn test_constant_map_pdf_is_uniform_over_the_sphere() {
+        // Integrating the pdf over the sphere gives 1
+        let map = constant_map(Color::new(1.0, 1.0, 1.0));
+        let n = 200;
+        let mut total = 0.0;
+        for j in 0..n {
+            let theta = PI * (j as f64 + 0.5) / n as f64;
+            for i in 0..n {
+                let phi = 2.0 * PI * (i as f64 + 0.5) / n as f64;
+                let d = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+                total += map.pdf(d) * theta.sin() * (PI / n as f64) * (2.0 * PI / n as f64);
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_empty_map_is_rejected() {
+        for (width, height) in [(0, 0), (0, 4), (8, 0)] {
+            assert!(matches!(EnvironmentMap::new(Image::new(width, height)), Err(ParseError::Record { .. })));
+        }
+    }
+}
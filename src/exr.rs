@@ -0,0 +1,291 @@
+//! OpenEXR output.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::Color;
+use crate::image::Image;
+
+const MAGIC: u32 = 20000630;
+// Single part scanline file, no long names
+const VERSION: u32 = 2;
+
+/// How the channels are stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleType {
+    /// 16 bit float, plenty for color and half the size.
+    #[default]
+    Half,
+    /// 32 bit float.
+    Float,
+}
+
+impl SampleType {
+    fn code(self) -> i32 {
+        match self {
+            SampleType::Half => 1,
+            SampleType::Float => 2,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            SampleType::Half => 2,
+            SampleType::Float => 4,
+        }
+    }
+}
+
+/// Writes `image` to an `.exr` file, see [`write_exr`].
+pub fn save_exr<P: AsRef<Path>>(image: &Image, path: P, sample_type: SampleType) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_exr(image, &mut writer, sample_type)?;
+    writer.flush()
+}
+
+/// Uncompressed scanline OpenEXR with linear R, G and B channels. Values are
+/// written as they are, without clamping, and the primaries of the image's
+/// color space go in the chromaticities attribute.
+pub fn write_exr<W: Write>(image: &Image, writer: &mut W, sample_type: SampleType) -> io::Result<()> {
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 || width > i32::MAX as usize || height > i32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot write a {}x{} image as EXR", width, height),
+        ));
+    }
+
+    let mut header = Vec::new();
+    header.extend(MAGIC.to_le_bytes());
+    header.extend(VERSION.to_le_bytes());
+
+    // Channels have to be listed in alphabetical order
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend(name.as_bytes());
+        channels.push(0);
+        channels.extend(sample_type.code().to_le_bytes());
+        // Not perceptually linear, three reserved bytes, no subsampling
+        channels.extend([0, 0, 0, 0]);
+        channels.extend(1i32.to_le_bytes());
+        channels.extend(1i32.to_le_bytes());
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+
+    let c = image.color_space().chromaticities();
+    let chromaticities: Vec<u8> = [c.red, c.green, c.blue, c.white]
+        .iter()
+        .flat_map(|&(x, y)| [x as f32, y as f32])
+        .flat_map(f32::to_le_bytes)
+        .collect();
+    attribute(&mut header, "chromaticities", "chromaticities", &chromaticities);
+    attribute(&mut header, "compression", "compression", &[0]);
+    let window = [0, 0, width as i32 - 1, height as i32 - 1];
+    let window: Vec<u8> = window.iter().flat_map(|v| v.to_le_bytes()).collect();
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    // Increasing y, top row first like Image
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    // Without compression every block holds one scanline of the same size,
+    // so the offset table can be written up front
+    let line_size = 3 * width * sample_type.size();
+    let block_size = 8 + line_size;
+    let first_block = header.len() + 8 * height;
+    writer.write_all(&header)?;
+    for y in 0..height {
+        writer.write_all(&((first_block + y * block_size) as u64).to_le_bytes())?;
+    }
+
+    let mut block = Vec::with_capacity(block_size);
+    for y in 0..height {
+        block.clear();
+        block.extend((y as i32).to_le_bytes());
+        block.extend((line_size as i32).to_le_bytes());
+
+        let row = &image.pixels()[y * width..(y + 1) * width];
+        let channels: [fn(&Color) -> f64; 3] = [|c| c.z, |c| c.y, |c| c.x];
+        for channel in channels {
+            for pixel in row {
+                let value = channel(pixel) as f32;
+                match sample_type {
+                    SampleType::Half => block.extend(f32_to_half(value).to_le_bytes()),
+                    SampleType::Float => block.extend(value.to_le_bytes()),
+                }
+            }
+        }
+        writer.write_all(&block)?;
+    }
+
+    Ok(())
+}
+
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+// IEEE binary16 bits of `value`, rounded to nearest even. Too large values
+// become infinity, too small ones subnormals or zero.
+fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity, NaN keeps a mantissa bit set
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    let round = |half: u32, rest: u32, halfway: u32| {
+        if rest > halfway || (rest == halfway && half & 1 == 1) {
+            half + 1
+        } else {
+            half
+        }
+    };
+
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal: shift the mantissa, implicit bit included, into place
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = round(mantissa >> shift, mantissa & ((1 << shift) - 1), 1 << (shift - 1));
+        return sign | half as u16;
+    }
+
+    // A carry out of the mantissa correctly bumps the exponent, up to infinity
+    let half = round(((exponent as u32) << 10) | (mantissa >> 13), mantissa & 0x1fff, 0x1000);
+    sign | half as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_space::ColorSpace;
+
+    fn half_to_f32(half: u16) -> f32 {
+        let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+        let exponent = ((half >> 10) & 0x1f) as i32;
+        let mantissa = (half & 0x3ff) as f32;
+        match exponent {
+            0 => sign * mantissa * 2f32.powi(-24),
+            0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+            0x1f => f32::NAN,
+            _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+        }
+    }
+
+    #[test]
+    fn test_f32_to_half() {
+        assert_eq!(f32_to_half(0.0), 0x0000);
+        assert_eq!(f32_to_half(-0.0), 0x8000);
+        assert_eq!(f32_to_half(1.0), 0x3c00);
+        assert_eq!(f32_to_half(-2.0), 0xc000);
+        assert_eq!(f32_to_half(0.1), 0x2e66);
+        assert_eq!(f32_to_half(65504.0), 0x7bff);
+        assert_eq!(f32_to_half(1e6), 0x7c00);
+        assert_eq!(f32_to_half(f32::NEG_INFINITY), 0xfc00);
+        assert!(half_to_f32(f32_to_half(f32::NAN)).is_nan());
+        // Smallest subnormal, and half of it rounding to even zero
+        assert_eq!(f32_to_half(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_half(2f32.powi(-25)), 0x0000);
+        // 1 + 1/2048 is halfway between two halves and rounds to even
+        assert_eq!(f32_to_half(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_half(1.0 + 3.0 / 2048.0), 0x3c02);
+
+        for value in [0.001f32, 0.5, 3.75, 100.0, 12345.0] {
+            let back = half_to_f32(f32_to_half(value));
+            assert!((back - value).abs() <= value * 1e-3, "{} vs {}", back, value);
+        }
+    }
+
+    // Finds the value of a header attribute
+    fn find_attribute<'a>(bytes: &'a [u8], name: &str, kind: &str) -> &'a [u8] {
+        let key = [name.as_bytes(), &[0], kind.as_bytes(), &[0]].concat();
+        let start = bytes.windows(key.len()).position(|w| w == key).expect(name) + key.len();
+        let size = i32::from_le_bytes(bytes[start..start + 4].try_into().unwrap()) as usize;
+        &bytes[start + 4..start + 4 + size]
+    }
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_write_float_exr() {
+        let image = Image::from_pixels(2, 2, vec![
+            Color::new(1.0, 2.0, 3.0),
+            Color::new(40.0, 0.5, 0.0),
+            Color::new(-1.0, 0.0, 0.25),
+            Color::new(1e5, 7.0, 8.0),
+        ]);
+        let mut bytes = Vec::new();
+        write_exr(&image, &mut bytes, SampleType::Float).unwrap();
+
+        assert_eq!(bytes[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        let channels = find_attribute(&bytes, "channels", "chlist");
+        assert_eq!(channels.len(), 3 * 18 + 1);
+        assert_eq!(&channels[..2], b"B\0");
+        assert_eq!(&channels[36..38], b"R\0");
+        let window = find_attribute(&bytes, "dataWindow", "box2i");
+        assert_eq!(window[8..], [1, 0, 0, 0, 1, 0, 0, 0]);
+
+        // Second scanline, found through the offset table that follows the
+        // header. Channels are stored B, G, R, unclamped.
+        let table = bytes.len() - 2 * (8 + 3 * 2 * 4) - 2 * 8;
+        let offset = u64::from_le_bytes(bytes[table + 8..table + 16].try_into().unwrap()) as usize;
+        assert_eq!(i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()), 1);
+        assert_eq!(i32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()), 24);
+        let data = offset + 8;
+        assert_eq!([read_f32(&bytes, data), read_f32(&bytes, data + 4)], [0.25, 8.0]);
+        assert_eq!([read_f32(&bytes, data + 16), read_f32(&bytes, data + 20)], [-1.0, 1e5]);
+    }
+
+    #[test]
+    fn test_write_half_exr() {
+        let image = Image::from_pixels(1, 1, vec![Color::new(1.0, 0.5, 2.0)]);
+        let mut bytes = Vec::new();
+        write_exr(&image, &mut bytes, SampleType::Half).unwrap();
+
+        assert_eq!(i32::from_le_bytes(find_attribute(&bytes, "channels", "chlist")[2..6].try_into().unwrap()), 1);
+        // B, G, R as halves at the very end
+        let data = &bytes[bytes.len() - 6..];
+        assert_eq!(data, [0x00, 0x40, 0x00, 0x38, 0x00, 0x3c]);
+    }
+
+    #[test]
+    fn test_chromaticities() {
+        let image = Image::from_pixels(1, 1, vec![Color::new(1.0, 0.5, 2.0)]).convert_to(ColorSpace::Rec2020);
+        let mut bytes = Vec::new();
+        write_exr(&image, &mut bytes, SampleType::Float).unwrap();
+
+        let value = find_attribute(&bytes, "chromaticities", "chromaticities");
+        let floats: Vec<f32> = value.chunks_exact(4).map(|c| f32::from_le_bytes(c.try_into().unwrap())).collect();
+        assert_eq!(floats, [0.708, 0.292, 0.170, 0.797, 0.131, 0.046, 0.3127, 0.3290]);
+    }
+
+    #[test]
+    fn test_empty_image_is_rejected() {
+        let mut bytes = Vec::new();
+        assert!(write_exr(&Image::new(0, 3), &mut bytes, SampleType::Half).is_err());
+    }
+}
@@ -0,0 +1,540 @@
+//! Bounding volume hierarchy stored in a flat array.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+// Relative costs used by the surface area heuristic
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+
+const SAH_BINS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+
+// Past this depth the builder falls back to median splits so the traversal
+// stack can never overflow, whatever the SAH decides
+const MAX_SAH_DEPTH: usize = 48;
+const STACK_SIZE: usize = 64;
+
+/// How the objects of a node are divided between its two children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// Sort on the longest centroid axis and cut the span in half.
+    Median,
+    /// Binned surface area heuristic.
+    Sah,
+}
+
+impl FromStr for SplitStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "median" => Ok(SplitStrategy::Median),
+            "sah" => Ok(SplitStrategy::Sah),
+            other => Err(format!("unknown BVH split strategy '{}' (expected 'median' or 'sah')", other)),
+        }
+    }
+}
+
+/// Shape of a built hierarchy, for diagnostics.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BvhStats {
+    /// Nodes in the tree, leaves included.
+    pub node_count: usize,
+    /// Leaves in the tree.
+    pub leaf_count: usize,
+    /// Depth of the deepest leaf, the root being at depth 1.
+    pub max_depth: usize,
+    /// `leaf_size_histogram[n]` is the number of leaves holding n primitives.
+    pub leaf_size_histogram: Vec<usize>,
+    /// Expected cost of a random ray, relative to the root's surface area.
+    pub sah_cost: f64,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "nodes: {}, leaves: {}, depth: {}, SAH cost: {:.3}, leaf sizes: {:?}",
+            self.node_count, self.leaf_count, self.max_depth, self.sah_cost, self.leaf_size_histogram
+        )
+    }
+}
+
+// Interior nodes store the index of their second child, the first child always
+// directly follows its parent in the array. Leaves store a primitive range.
+#[derive(Debug, Clone, Copy)]
+struct FlatNode {
+    bbox: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+impl FlatNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// Bounding volume hierarchy over a list of objects, stored in a flat array
+/// and traversed without recursion.
+pub struct FlatBvh {
+    nodes: Vec<FlatNode>,
+    primitives: Vec<Arc<dyn Hittable>>,
+    stats: BvhStats,
+}
+
+// Per-primitive data needed while building, so bounds are only queried once
+#[derive(Clone, Copy)]
+struct BuildPrimitive {
+    index: usize,
+    bbox: Aabb,
+    centroid: [f64; 3],
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bbox: Aabb,
+    count: usize,
+}
+
+impl FlatBvh {
+    /// Builds the hierarchy over the objects of `list`.
+    pub fn new(list: HittableList, strategy: SplitStrategy) -> Self {
+        let objects: Vec<Arc<dyn Hittable>> = list
+            .into_objects()
+            .into_iter()
+            .map(Arc::from)
+            .collect();
+
+        Self::from_objects(objects, strategy)
+    }
+
+    /// Builds the hierarchy over `objects`.
+    pub fn from_objects(objects: Vec<Arc<dyn Hittable>>, strategy: SplitStrategy) -> Self {
+        let mut build: Vec<BuildPrimitive> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object.bounding_box();
+                let c = bbox.centroid();
+                BuildPrimitive { index, bbox, centroid: [c.x, c.y, c.z] }
+            })
+            .collect();
+
+        let mut bvh = FlatBvh {
+            nodes: Vec::with_capacity(2 * objects.len().max(1)),
+            primitives: Vec::with_capacity(objects.len()),
+            stats: BvhStats::default(),
+        };
+
+        if !build.is_empty() {
+            bvh.build_recursive(&objects, &mut build, strategy, 1);
+        }
+        bvh.compute_stats();
+
+        bvh
+    }
+
+    /// Statistics gathered while building.
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+
+    // Appends the subtree for `span` to the node array, returning its index
+    fn build_recursive(
+        &mut self,
+        objects: &[Arc<dyn Hittable>],
+        span: &mut [BuildPrimitive],
+        strategy: SplitStrategy,
+        depth: usize,
+    ) -> usize {
+        let node_index = self.nodes.len();
+        let bbox = span
+            .iter()
+            .fold(Aabb::EMPTY, |acc, prim| Aabb::surrounding(&acc, &prim.bbox));
+
+        self.nodes.push(FlatNode { bbox, offset: 0, count: 0, axis: 0 });
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+
+        let split = if span.len() <= 1 {
+            None
+        } else if strategy == SplitStrategy::Median || depth > MAX_SAH_DEPTH {
+            median_split(span)
+        } else {
+            sah_split(span, &bbox)
+        };
+
+        match split {
+            Some((mid, axis)) => {
+                let (lower, upper) = span.split_at_mut(mid);
+                self.build_recursive(objects, lower, strategy, depth + 1);
+                let second = self.build_recursive(objects, upper, strategy, depth + 1);
+
+                let node = &mut self.nodes[node_index];
+                node.offset = second;
+                node.axis = axis;
+            }
+            None => {
+                let node = &mut self.nodes[node_index];
+                node.offset = self.primitives.len();
+                node.count = span.len();
+                self.primitives
+                    .extend(span.iter().map(|prim| objects[prim.index].clone()));
+            }
+        }
+
+        node_index
+    }
+
+    fn compute_stats(&mut self) {
+        self.stats.node_count = self.nodes.len();
+
+        let Some(root) = self.nodes.first() else {
+            return;
+        };
+        let root_area = root.bbox.surface_area();
+
+        let mut histogram = Vec::new();
+        let mut cost = 0.0;
+        for node in &self.nodes {
+            let relative_area = if root_area > 0.0 {
+                node.bbox.surface_area() / root_area
+            } else {
+                1.0
+            };
+
+            if node.is_leaf() {
+                if histogram.len() <= node.count {
+                    histogram.resize(node.count + 1, 0);
+                }
+                histogram[node.count] += 1;
+                cost += INTERSECTION_COST * node.count as f64 * relative_area;
+            } else {
+                cost += TRAVERSAL_COST * relative_area;
+            }
+        }
+
+        self.stats.leaf_count = histogram.iter().sum();
+        self.stats.leaf_size_histogram = histogram;
+        self.stats.sah_cost = cost;
+    }
+}
+
+fn centroid_bounds(span: &[BuildPrimitive]) -> Aabb {
+    span.iter().fold(Aabb::EMPTY, |acc, prim| {
+        let c = Point3::new(prim.centroid[0], prim.centroid[1], prim.centroid[2]);
+        let point = Aabb::from_points(c, c);
+        Aabb::surrounding(&acc, &point)
+    })
+}
+
+fn median_split(span: &mut [BuildPrimitive]) -> Option<(usize, usize)> {
+    if span.len() <= MAX_LEAF_SIZE {
+        return None;
+    }
+
+    let axis = centroid_bounds(span).longest_axis();
+    let mid = span.len() / 2;
+    span.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+
+    Some((mid, axis))
+}
+
+fn sah_split(span: &mut [BuildPrimitive], bbox: &Aabb) -> Option<(usize, usize)> {
+    let bounds = centroid_bounds(span);
+    let leaf_cost = INTERSECTION_COST * span.len() as f64;
+    let parent_area = bbox.surface_area();
+
+    let mut best: Option<(f64, usize, usize)> = None;
+
+    for axis in 0..3 {
+        let extent = bounds.axis_interval(axis);
+        if extent.size() <= 0.0 {
+            continue;
+        }
+
+        let mut bins = [Bin { bbox: Aabb::EMPTY, count: 0 }; SAH_BINS];
+        for prim in span.iter() {
+            let b = bin_index(prim.centroid[axis], extent);
+            bins[b].count += 1;
+            bins[b].bbox = Aabb::surrounding(&bins[b].bbox, &prim.bbox);
+        }
+
+        // Sweep from the right to get the area and count of every suffix
+        let mut right_area = [0.0; SAH_BINS];
+        let mut right_count = [0; SAH_BINS];
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for i in (1..SAH_BINS).rev() {
+            acc_box = Aabb::surrounding(&acc_box, &bins[i].bbox);
+            acc_count += bins[i].count;
+            right_area[i] = acc_box.surface_area();
+            right_count[i] = acc_count;
+        }
+
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for split in 1..SAH_BINS {
+            acc_box = Aabb::surrounding(&acc_box, &bins[split - 1].bbox);
+            acc_count += bins[split - 1].count;
+            if acc_count == 0 || right_count[split] == 0 {
+                continue;
+            }
+
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (acc_box.surface_area() * acc_count as f64
+                        + right_area[split] * right_count[split] as f64)
+                    / parent_area;
+
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    match best {
+        Some((cost, axis, split)) if span.len() > MAX_LEAF_SIZE || cost < leaf_cost => {
+            let extent = *bounds.axis_interval(axis);
+            let mid = partition(span, |prim| bin_index(prim.centroid[axis], &extent) < split);
+            Some((mid, axis))
+        }
+        // Every centroid coincides, so binning cannot separate the span
+        None if span.len() > MAX_LEAF_SIZE => Some((span.len() / 2, bounds.longest_axis())),
+        _ => None,
+    }
+}
+
+fn bin_index(value: f64, extent: &Interval) -> usize {
+    let relative = (value - extent.min()) / extent.size();
+    ((relative * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+}
+
+// In-place partition, returning the number of elements satisfying `pred`
+fn partition<F: Fn(&BuildPrimitive) -> bool>(span: &mut [BuildPrimitive], pred: F) -> usize {
+    let mut first = 0;
+    for i in 0..span.len() {
+        if pred(&span[i]) {
+            span.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+impl Hittable for FlatBvh {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let dir = r.direction();
+        let dir_is_neg = [dir.x < 0.0, dir.y < 0.0, dir.z < 0.0];
+
+        let mut hit_anything = false;
+        let mut closest = t.max();
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bbox.hit(r, Interval::new(t.min(), closest)) {
+                if node.is_leaf() {
+                    for object in &self.primitives[node.offset..node.offset + node.count] {
+                        if object.hit(r, &Interval::new(t.min(), closest), rec) {
+                            hit_anything = true;
+                            closest = rec.t();
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first
+                    let (near, far) = if dir_is_neg[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        hit_anything
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |root| root.bbox)
+    }
+
+    // Every primitive along the ray counts, not only the closest, so the
+    // whole interval is searched in any order
+    fn transmittance(&self, r: &Ray, t: &Interval) -> f64 {
+        if self.nodes.is_empty() {
+            return 1.0;
+        }
+
+        let mut transmittance = 1.0;
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node.bbox.hit(r, *t) {
+                if node.is_leaf() {
+                    for object in &self.primitives[node.offset..node.offset + node.count] {
+                        transmittance *= object.transmittance(r, t);
+                        if transmittance <= 0.0 {
+                            return 0.0;
+                        }
+                    }
+                } else {
+                    stack[stack_len] = node.offset;
+                    stack_len += 1;
+                    current += 1;
+                    continue;
+                }
+            }
+
+            if stack_len == 0 {
+                return transmittance;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::{random_double_range, INFINITY};
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::Vec3;
+
+    fn random_spheres(count: usize) -> Vec<(Point3, f64)> {
+        (0..count)
+            .map(|_| (Vec3::random_range(-10.0, 10.0), random_double_range(0.1, 1.0)))
+            .collect()
+    }
+
+    fn list_from(spheres: &[(Point3, f64)]) -> HittableList {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mut list = HittableList::new();
+        for (center, radius) in spheres {
+            list.add(Box::new(Sphere::new(*center, *radius, material.clone())));
+        }
+        list
+    }
+
+    #[test]
+    fn test_split_strategy_from_str() {
+        assert_eq!("SAH".parse::<SplitStrategy>(), Ok(SplitStrategy::Sah));
+        assert_eq!("median".parse::<SplitStrategy>(), Ok(SplitStrategy::Median));
+        assert!("octree".parse::<SplitStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_empty_bvh() {
+        let bvh = FlatBvh::new(HittableList::new(), SplitStrategy::Sah);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(!bvh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(bvh.stats().node_count, 0);
+        assert_eq!(bvh.bounding_box(), Aabb::EMPTY);
+    }
+
+    #[test]
+    fn test_stats_account_for_every_primitive() {
+        let spheres = random_spheres(200);
+
+        for strategy in [SplitStrategy::Median, SplitStrategy::Sah] {
+            let bvh = FlatBvh::new(list_from(&spheres), strategy);
+            let stats = bvh.stats();
+
+            let primitives: usize = stats
+                .leaf_size_histogram
+                .iter()
+                .enumerate()
+                .map(|(size, leaves)| size * leaves)
+                .sum();
+            assert_eq!(primitives, 200);
+            assert_eq!(stats.node_count, 2 * stats.leaf_count - 1);
+            assert!(stats.leaf_size_histogram.len() <= MAX_LEAF_SIZE + 1);
+            assert!(stats.max_depth < STACK_SIZE);
+            assert!(stats.sah_cost > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_sah_is_no_worse_than_median() {
+        let spheres = random_spheres(500);
+        let median = FlatBvh::new(list_from(&spheres), SplitStrategy::Median);
+        let sah = FlatBvh::new(list_from(&spheres), SplitStrategy::Sah);
+
+        assert!(sah.stats().sah_cost <= median.stats().sah_cost * 1.05);
+    }
+
+    #[test]
+    fn test_identical_positions_still_build() {
+        let spheres = vec![(Point3::new(1.0, 1.0, 1.0), 0.5); 20];
+        let bvh = FlatBvh::new(list_from(&spheres), SplitStrategy::Sah);
+        let ray = Ray::new(Point3::new(1.0, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(bvh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 5.5);
+    }
+
+    #[test]
+    fn test_flat_bvh_matches_linear_list() {
+        let spheres = random_spheres(300);
+        let list = list_from(&spheres);
+        let bvhs = [
+            FlatBvh::new(list_from(&spheres), SplitStrategy::Median),
+            FlatBvh::new(list_from(&spheres), SplitStrategy::Sah),
+        ];
+
+        for _ in 0..1000 {
+            let ray = Ray::new(Vec3::random_range(-15.0, 15.0), Vec3::random_unit_vector());
+
+            let mut list_rec = HitRecord::default();
+            let list_hit = list.hit(&ray, &Interval::new(0.001, INFINITY), &mut list_rec);
+
+            for bvh in &bvhs {
+                let mut bvh_rec = HitRecord::default();
+                let bvh_hit = bvh.hit(&ray, &Interval::new(0.001, INFINITY), &mut bvh_rec);
+
+                assert_eq!(list_hit, bvh_hit);
+                if list_hit {
+                    assert_eq!(list_rec.t(), bvh_rec.t());
+                    assert_eq!(list_rec.p(), bvh_rec.p());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Volumes whose density comes from a voxel grid.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::commons::random_double;
+use crate::density_grid::DensityGrid;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+/// Volume whose density is read from a grid stretched over an axis aligned
+/// box. Use an Instance to place it any other way. Free flights are sampled
+/// with delta tracking against the grid maximum, so the density can vary
+/// arbitrarily inside the box. Shadow rays use ratio tracking instead.
+pub struct GridMedium {
+    grid: Arc<DensityGrid>,
+    bbox: Aabb,
+    density: f64,
+    majorant: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl GridMedium {
+    /// `density` scales the grid values to extinction per unit length.
+    pub fn new(grid: Arc<DensityGrid>, corner_a: Point3, corner_b: Point3, density: f64, phase_function: Arc<dyn Material>) -> Self {
+        let majorant = density * grid.max();
+        Self { grid, bbox: Aabb::from_points(corner_a, corner_b), density, majorant, phase_function }
+    }
+
+    fn density_at(&self, p: Point3) -> f64 {
+        let local = Point3::new(
+            (p.x - self.bbox.x().min()) / self.bbox.x().size(),
+            (p.y - self.bbox.y().min()) / self.bbox.y().size(),
+            (p.z - self.bbox.z().min()) / self.bbox.z().size(),
+        );
+        self.density * self.grid.sample(local)
+    }
+
+    // Tentative collisions with the majorant medium, spaced in ray
+    // parameter units
+    fn step(&self, ray_length: f64) -> f64 {
+        -(1.0 - random_double()).ln() / (self.majorant * ray_length)
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        let Some(segment) = self.bbox.clip(r, *t) else {
+            return false;
+        };
+        if self.majorant <= 0.0 {
+            return false;
+        }
+
+        // Delta tracking: accept a tentative collision with probability
+        // density / majorant, otherwise it was a null collision and the ray
+        // keeps going
+        let ray_length = r.direction().length();
+        let mut t = segment.min();
+        loop {
+            t += self.step(ray_length);
+            if t >= segment.max() {
+                return false;
+            }
+            if random_double() * self.majorant < self.density_at(r.at(t)) {
+                break;
+            }
+        }
+
+        rec.set_t(t);
+        rec.set_p(r.at(t));
+
+        // The normal and side mean nothing inside a volume
+        rec.set_normal(Vec3::new(1.0, 0.0, 0.0));
+        rec.front_face = true;
+        rec.mat = self.phase_function.clone();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    // Fraction of light surviving along `t`, estimated with ratio tracking.
+    // Unlike the 0 or 1 visibility delta tracking gives, every tentative
+    // collision only scales the estimate down, which keeps shadows smooth.
+    fn transmittance(&self, r: &Ray, t: &Interval) -> f64 {
+        let Some(segment) = self.bbox.clip(r, *t) else {
+            return 1.0;
+        };
+        if self.majorant <= 0.0 {
+            return 1.0;
+        }
+
+        let ray_length = r.direction().length();
+        let mut transmittance = 1.0;
+        let mut t = segment.min();
+        loop {
+            t += self.step(ray_length);
+            if t >= segment.max() {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density_at(r.at(t)) / self.majorant;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::commons::INFINITY;
+    use crate::material::HenyeyGreenstein;
+
+    fn phase() -> Arc<dyn Material> {
+        Arc::new(HenyeyGreenstein::new(Color::new(0.9, 0.9, 0.9), 0.5))
+    }
+
+    // Unit cube whose density rises linearly from 0 at x = 0 to 1 at x = 1
+    fn ramp(density: f64) -> GridMedium {
+        let grid = DensityGrid::from_fn(64, 1, 1, |p| p.x).unwrap();
+        GridMedium::new(Arc::new(grid), Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0), density, phase())
+    }
+
+    #[test]
+    fn test_delta_tracking_matches_optical_depth() {
+        crate::commons::seed_random(1);
+        let medium = ramp(2.0);
+        // Through the whole ramp the optical depth is 2 * 1/2 = 1
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(2.0, 0.0, 0.0));
+        let expected = 1.0 - (-1.0f64).exp();
+
+        let samples = 20000;
+        let mut hits = 0;
+        for _ in 0..samples {
+            let mut rec = HitRecord::default();
+            if medium.hit(&r, &Interval::new(0.001, INFINITY), &mut rec) {
+                assert!(rec.p().x >= 0.0 && rec.p().x <= 1.0);
+                hits += 1;
+            }
+        }
+        let fraction = hits as f64 / samples as f64;
+        assert!((fraction - expected).abs() < 0.02, "{} vs {}", fraction, expected);
+    }
+
+    #[test]
+    fn test_ratio_tracking_transmittance() {
+        crate::commons::seed_random(2);
+        let medium = ramp(2.0);
+        let r = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+
+        let samples = 5000;
+        let mean: f64 = (0..samples).map(|_| medium.transmittance(&r, &Interval::new(0.001, INFINITY))).sum::<f64>() / samples as f64;
+        assert!((mean - (-1.0f64).exp()).abs() < 0.01, "{}", mean);
+
+        // Stopping halfway sees only the thin end of the ramp: depth 2 * 1/8
+        let mean: f64 = (0..samples).map(|_| medium.transmittance(&r, &Interval::new(0.001, 1.5))).sum::<f64>() / samples as f64;
+        assert!((mean - (-0.25f64).exp()).abs() < 0.01, "{}", mean);
+
+        let miss = Ray::new(Point3::new(-1.0, 2.0, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(medium.transmittance(&miss, &Interval::new(0.001, INFINITY)), 1.0);
+    }
+
+    #[test]
+    fn test_shadow_rays_see_through_the_medium() {
+        use crate::hittable_list::HittableList;
+        use crate::instance::Instance;
+        use crate::sphere::Sphere;
+        use crate::transform::Transform;
+
+        crate::commons::seed_random(3);
+        let mut world = HittableList::new();
+        let medium: Arc<dyn Hittable> = Arc::new(ramp(2.0));
+        world.add(Box::new(Instance::new(medium, Transform::translate(Vec3::new(0.0, 0.0, -5.0)))));
+        world.add(Box::new(Sphere::new(Point3::new(0.0, 5.0, 0.0), 1.0, phase())));
+        let world = crate::flat_bvh::FlatBvh::new(world, crate::flat_bvh::SplitStrategy::Sah);
+
+        // Through the moved ramp only, and through the sphere as well
+        let r = Ray::new(Point3::new(-1.0, 0.5, -4.5), Vec3::new(1.0, 0.0, 0.0));
+        let samples = 5000;
+        let mean: f64 = (0..samples).map(|_| world.transmittance(&r, &Interval::new(0.001, INFINITY))).sum::<f64>() / samples as f64;
+        assert!((mean - (-1.0f64).exp()).abs() < 0.01, "{}", mean);
+
+        let blocked = Ray::new(Point3::new(0.0, 0.5, -4.5), Vec3::new(0.0, 1.0, 1.0));
+        assert_eq!(world.transmittance(&blocked, &Interval::new(0.001, INFINITY)), 0.0);
+    }
+
+    #[test]
+    fn test_empty_grid_never_scatters() {
+        let medium = ramp(0.0);
+        let r = Ray::new(Point3::new(0.5, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(!medium.hit(&r, &Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(medium.transmittance(&r, &Interval::new(0.001, INFINITY)), 1.0);
+    }
+}
@@ -0,0 +1,388 @@
+//! Radiance RGBE (.hdr) input and output.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::parse_error::ParseError;
+
+// Scanlines outside this range can't use the adaptive run length encoding
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7fff;
+
+/// Reads a Radiance `.hdr` file, see [`parse_hdr`].
+pub fn load_hdr<P: AsRef<Path>>(path: P) -> Result<Image, ParseError> {
+    let bytes = fs::read(path)?;
+    parse_hdr(&bytes)
+}
+
+/// Radiance RGBE (.hdr) image.
+pub fn parse_hdr(bytes: &[u8]) -> Result<Image, ParseError> {
+    let mut pos = 0;
+    let mut line_number = 0;
+    let mut next_line = |pos: &mut usize| -> Result<(usize, String), ParseError> {
+        line_number += 1;
+        let rest = &bytes[*pos..];
+        let Some(end) = rest.iter().position(|&b| b == b'\n') else {
+            return Err(ParseError::syntax(line_number, "file ends inside the header"));
+        };
+        *pos += end + 1;
+        Ok((line_number, String::from_utf8_lossy(&rest[..end]).trim_end().to_string()))
+    };
+
+    let (_, magic) = next_line(&mut pos)?;
+    if !magic.starts_with("#?") {
+        return Err(ParseError::syntax(1, "missing #?RADIANCE signature"));
+    }
+
+    // Variables until the first empty line
+    let mut exposure = 1.0;
+    loop {
+        let (line, text) = next_line(&mut pos)?;
+        if text.is_empty() {
+            break;
+        }
+        if let Some(format) = text.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(ParseError::syntax(line, format!("unsupported format '{}'", format)));
+            }
+        } else if let Some(value) = text.strip_prefix("EXPOSURE=") {
+            exposure *= value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| ParseError::syntax(line, format!("invalid exposure '{}'", value)))?;
+        }
+    }
+
+    // Only the standard orientations are supported, top down or bottom up
+    let (line, resolution) = next_line(&mut pos)?;
+    let tokens: Vec<&str> = resolution.split_whitespace().collect();
+    let (flip, height, width) = match tokens.as_slice() {
+        [y @ ("-Y" | "+Y"), height, "+X", width] => {
+            let parse = |token: &str| {
+                token
+                    .parse::<usize>()
+                    .map_err(|_| ParseError::syntax(line, format!("invalid image size '{}'", token)))
+            };
+            (*y == "+Y", parse(height)?, parse(width)?)
+        }
+        _ => return Err(ParseError::syntax(line, format!("unsupported resolution line '{}'", resolution))),
+    };
+
+    if width == 0 || height == 0 {
+        return Ok(Image::new(width, height));
+    }
+
+    // Even fully run length encoded scanlines take some bytes, so a header
+    // asking for more than the file can hold is caught before allocating
+    let needed = min_scanline_size(width).and_then(|size| size.checked_mul(height));
+    if needed.is_none_or(|needed| needed > bytes.len() - pos) {
+        return Err(ParseError::syntax(
+            line,
+            format!("image size {}x{} does not fit in the remaining {} bytes", width, height, bytes.len() - pos),
+        ));
+    }
+
+    let mut image = Image::new(width, height);
+    for row in 0..height {
+        let scanline = read_scanline(bytes, &mut pos, width, row)?;
+        let y = if flip { height - 1 - row } else { row };
+        for (x, rgbe) in scanline.iter().enumerate() {
+            image.set_pixel(x, y, rgbe_to_color(*rgbe) / exposure);
+        }
+    }
+
+    Ok(image)
+}
+
+// Smallest possible encoding of a scanline: runs of 127 in all four channels
+// when it can be run length encoded, raw pixels otherwise
+fn min_scanline_size(width: usize) -> Option<usize> {
+    if (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width) {
+        Some(4 + 4 * 2 * width.div_ceil(127))
+    } else {
+        width.checked_mul(4)
+    }
+}
+
+fn read_scanline(bytes: &[u8], pos: &mut usize, width: usize, row: usize) -> Result<Vec<[u8; 4]>, ParseError> {
+    let truncated = || ParseError::record(row, "file ends inside the scanline");
+    let header = bytes.get(*pos..*pos + 4).ok_or_else(truncated)?;
+
+    let is_rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width)
+        && header[0] == 2
+        && header[1] == 2
+        && header[2] & 0x80 == 0;
+    if !is_rle {
+        let raw = bytes.get(*pos..*pos + 4 * width).ok_or_else(truncated)?;
+        *pos += 4 * width;
+        return Ok(raw.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]).collect());
+    }
+
+    let encoded_width = ((header[2] as usize) << 8) | header[3] as usize;
+    if encoded_width != width {
+        return Err(ParseError::record(row, format!("scanline width {} does not match {}", encoded_width, width)));
+    }
+    *pos += 4;
+
+    // Each channel is stored separately as a mix of runs and literal spans
+    let mut scanline = vec![[0u8; 4]; width];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = *bytes.get(*pos).ok_or_else(truncated)? as usize;
+            *pos += 1;
+
+            if count > 128 {
+                let run = count - 128;
+                let value = *bytes.get(*pos).ok_or_else(truncated)?;
+                *pos += 1;
+                if x + run > width {
+                    return Err(ParseError::record(row, "run overruns the scanline"));
+                }
+                scanline[x..x + run].iter_mut().for_each(|pixel| pixel[channel] = value);
+                x += run;
+            } else {
+                if count == 0 || x + count > width {
+                    return Err(ParseError::record(row, "invalid literal span"));
+                }
+                let values = bytes.get(*pos..*pos + count).ok_or_else(truncated)?;
+                *pos += count;
+                for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                    pixel[channel] = *value;
+                }
+                x += count;
+            }
+        }
+    }
+
+    Ok(scanline)
+}
+
+// The shared exponent scales all three mantissas
+fn rgbe_to_color([r, g, b, e]: [u8; 4]) -> Color {
+    if e == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(e as i32 - (128 + 8));
+    Color::new(r as f64 * f, g as f64 * f, b as f64 * f)
+}
+
+/// Writes `image` to a Radiance `.hdr` file, see [`write_hdr`].
+pub fn save_hdr<P: AsRef<Path>>(image: &Image, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_hdr(image, &mut writer)?;
+    writer.flush()
+}
+
+/// Radiance RGBE image, top row first, with the primaries of the image's
+/// color space. Scanlines are run length encoded whenever the format allows it.
+pub fn write_hdr<W: Write>(image: &Image, writer: &mut W) -> io::Result<()> {
+    let (width, height) = (image.width(), image.height());
+    let c = image.color_space().chromaticities();
+    let primaries: Vec<String> = [c.red, c.green, c.blue, c.white]
+        .iter()
+        .flat_map(|&(x, y)| [x.to_string(), y.to_string()])
+        .collect();
+    write!(
+        writer,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nPRIMARIES={}\n\n-Y {} +X {}\n",
+        primaries.join(" "),
+        height,
+        width
+    )?;
+
+    let rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width);
+    let mut encoded = Vec::new();
+    for y in 0..height {
+        let scanline: Vec<[u8; 4]> = (0..width).map(|x| color_to_rgbe(image.pixel(x, y))).collect();
+        if !rle {
+            writer.write_all(scanline.as_flattened())?;
+            continue;
+        }
+
+        encoded.clear();
+        encoded.extend([2, 2, (width >> 8) as u8, width as u8]);
+        for channel in 0..4 {
+            let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[channel]).collect();
+            encode_channel(&values, &mut encoded);
+        }
+        writer.write_all(&encoded)?;
+    }
+    Ok(())
+}
+
+// Runs of at least four equal values become a run, everything in between
+// literal spans
+fn encode_channel(values: &[u8], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4;
+    let mut x = 0;
+    while x < values.len() {
+        let mut start = x;
+        let mut run = 0;
+        while start < values.len() {
+            run = values[start..].iter().take(127).take_while(|&&v| v == values[start]).count();
+            if run >= MIN_RUN {
+                break;
+            }
+            start += run;
+        }
+
+        while x < start {
+            let count = (start - x).min(128);
+            out.push(count as u8);
+            out.extend(&values[x..x + count]);
+            x += count;
+        }
+        if run >= MIN_RUN {
+            out.extend([128 + run as u8, values[start]]);
+            x += run;
+        }
+    }
+}
+
+// Shared exponent of the largest component. Negative and non finite
+// components can't be represented and become zero.
+fn color_to_rgbe(color: Color) -> [u8; 4] {
+    let component = |c: f64| if c.is_finite() { c.max(0.0) } else { 0.0 };
+    let (r, g, b) = (component(color.x), component(color.y), component(color.z));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // max = m * 2^e with m in [0.5, 1)
+    let mut e = max.log2().floor() as i32 + 1;
+    if max / 2f64.powi(e) >= 1.0 {
+        e += 1;
+    }
+    if e > 127 {
+        return [255, 255, 255, 255];
+    }
+    let scale = 256.0 / 2f64.powi(e);
+    let byte = |c: f64| (c * scale).min(255.0) as u8;
+    [byte(r), byte(g), byte(b), (e + 128) as u8]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(resolution: &str) -> Vec<u8> {
+        format!("#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\n\n{}\n", resolution).into_bytes()
+    }
+
+    #[test]
+    fn test_parse_flat_scanlines() {
+        // 2x2 image with 1.0, 2.0, 0.5 and black
+        let mut bytes = header("-Y 2 +X 2");
+        bytes.extend([128, 128, 128, 129, 128, 128, 128, 130]);
+        bytes.extend([128, 128, 128, 128, 0, 0, 0, 0]);
+
+        let image = parse_hdr(&bytes).unwrap();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(image.pixel(1, 0), Color::new(2.0, 2.0, 2.0));
+        assert_eq!(image.pixel(0, 1), Color::new(0.5, 0.5, 0.5));
+        assert_eq!(image.pixel(1, 1), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_rle_scanline() {
+        // Width 8: red is a run, green literal values, blue and exponent runs
+        let mut bytes = header("-Y 1 +X 8");
+        bytes.extend([2, 2, 0, 8]);
+        bytes.extend([128 + 8, 64]);
+        bytes.extend([8, 0, 16, 32, 48, 64, 80, 96, 112]);
+        bytes.extend([128 + 8, 0]);
+        bytes.extend([128 + 8, 129]);
+
+        let image = parse_hdr(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(0.5, 0.0, 0.0));
+        assert_eq!(image.pixel(7, 0), Color::new(0.5, 0.875, 0.0));
+    }
+
+    #[test]
+    fn test_bottom_up_and_exposure() {
+        let mut bytes = b"#?RGBE\nEXPOSURE=2\n\n+Y 2 +X 1\n".to_vec();
+        bytes.extend([128, 128, 128, 129, 128, 128, 128, 128]);
+
+        let image = parse_hdr(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(image.pixel(0, 1), Color::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn test_color_to_rgbe() {
+        assert_eq!(color_to_rgbe(Color::new(1.0, 1.0, 1.0)), [128, 128, 128, 129]);
+        assert_eq!(color_to_rgbe(Color::new(0.5, 0.25, 0.0)), [128, 64, 0, 128]);
+        assert_eq!(color_to_rgbe(Color::new(-1.0, f64::NAN, 0.0)), [0, 0, 0, 0]);
+        for color in [Color::new(3.0, 0.2, 1e3), Color::new(0.01, 0.02, 0.03)] {
+            let back = rgbe_to_color(color_to_rgbe(color));
+            assert!((back - color).length() < color.length() / 100.0, "{:?} vs {:?}", back, color);
+        }
+    }
+
+    #[test]
+    fn test_write_hdr_round_trip() {
+        // Wide enough to be run length encoded, with runs and literal spans
+        let pixels = (0..40)
+            .map(|i| if i < 20 { Color::new(2.0, 0.5, 0.0) } else { Color::new(i as f64, 1.0, 0.25 * i as f64) })
+            .collect();
+        let image = Image::from_pixels(20, 2, pixels);
+        let mut bytes = Vec::new();
+        write_hdr(&image, &mut bytes).unwrap();
+
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\nPRIMARIES=0.64 0.33 0.3 0.6 0.15 0.06 0.3127 0.329\n\n-Y 2 +X 20\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes[header.len()..header.len() + 4], [2, 2, 0, 20]);
+        let read = parse_hdr(&bytes).unwrap();
+        for (a, b) in read.pixels().iter().zip(image.pixels()) {
+            assert!((*a - *b).length() <= b.length() / 100.0, "{:?} vs {:?}", a, b);
+        }
+
+        // Too narrow for run length encoding
+        let image = Image::from_pixels(2, 1, vec![Color::new(1.0, 1.0, 1.0), Color::new(2.0, 2.0, 2.0)]);
+        let mut bytes = Vec::new();
+        write_hdr(&image, &mut bytes).unwrap();
+        assert!(bytes.ends_with(&[128, 128, 128, 129, 128, 128, 128, 130]));
+        assert_eq!(parse_hdr(&bytes).unwrap(), image);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse_hdr(b"P6\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = parse_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+
+        let err = parse_hdr(b"#?RADIANCE\n\n-X 1 +Y 1\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 3, .. }));
+
+        let mut bytes = header("-Y 2 +X 1");
+        bytes.extend([128, 128, 128, 129]);
+        let err = parse_hdr(&bytes).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 5, .. }));
+
+        // Big enough for two run length encoded rows, but the literal span
+        // of the second one is cut short
+        let mut bytes = header("-Y 2 +X 8");
+        bytes.extend([2, 2, 0, 8, 136, 1, 136, 1, 136, 1, 136, 129]);
+        bytes.extend([2, 2, 0, 8, 8, 1, 2, 3, 4, 5, 6, 7]);
+        let err = parse_hdr(&bytes).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_huge_size_is_rejected() {
+        for resolution in ["-Y 4000000000 +X 4000000000", "-Y 100000 +X 100000", "-Y 1 +X 18446744073709551615"] {
+            let mut bytes = header(resolution);
+            bytes.extend([2, 2, 0, 8]);
+            let err = parse_hdr(&bytes).unwrap_err();
+            assert!(matches!(err, ParseError::Syntax { line: 5, .. }), "{}", resolution);
+        }
+    }
+}
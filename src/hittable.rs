@@ -1,61 +1,109 @@
+//! The trait every object in a scene implements.
+
 use std::sync::Arc;
 
+use crate::aabb::Aabb;
 use crate::interval::Interval;
 use crate::material::{Material, Metal};
 use crate::vec3::{Point3,Vec3};
 use crate::ray::Ray;
 
+/// Where and how a ray hit an object.
 #[derive(Clone)]
 pub struct HitRecord {
+    /// The hit point.
     pub p: Point3,
+    /// Unit surface normal, facing against the ray.
     pub normal : Vec3,
+    /// Material of the surface that was hit.
     pub mat: Arc<dyn Material>,
+    /// Ray parameter of the hit point.
     pub t: f64,
-    pub front_face:bool
+    /// Whether the ray hit the outside of the surface.
+    pub front_face:bool,
+    /// First surface texture coordinate.
+    pub u: f64,
+    /// Second surface texture coordinate.
+    pub v: f64,
+    /// Barycentric weights of the hit point for the three triangle vertices.
+    pub barycentric: Vec3
 }
 
+/// Anything a ray can hit. Implementations must be shareable between the
+/// render threads.
 pub trait Hittable:Send + Sync {
+    /// Fills `rec` with the closest hit inside `t` and returns whether there
+    /// was one.
     fn hit(&self,r: &Ray,t: &Interval,rec: &mut HitRecord) ->bool;
+
+    /// Box containing the object at any time a ray can carry.
+    fn bounding_box(&self) -> Aabb;
+
+    /// Fraction of light passing straight through along `t`, as seen by
+    /// shadow rays. Surfaces block it entirely, participating media can
+    /// override this to let part of it through.
+    fn transmittance(&self, r: &Ray, t: &Interval) -> f64 {
+        let mut rec = HitRecord::default();
+        if self.hit(r, t, &mut rec) { 0.0 } else { 1.0 }
+    }
 }
 
 impl HitRecord {
+    /// Record of a hit at `p` and `t`, facing the given normal, without texture
+    /// coordinates.
     pub fn new(p:Point3,normal:Vec3,mat:Arc<dyn Material>,t:f64)-> Self {
-        Self{p,normal,mat,t,front_face:false}
+        Self{
+            p,
+            normal,
+            mat,
+            t,
+            front_face:false,
+            u: 0.0,
+            v: 0.0,
+            barycentric: Vec3::default()
+        }
     }
 
     //Getter
+    /// The hit point.
     pub fn p(&self) -> Point3 {
         self.p
     }
 
+    /// The surface normal.
     pub fn normal(&self) -> Vec3 {
         self.normal
     }
 
+    /// Ray parameter of the hit point.
     pub fn t(&self) -> f64 {
         self.t
     }
 
+    /// Whether the ray hit the outside of the surface.
     pub fn front_face(&self) -> bool {
         self.front_face
     }
 
 
     //Setter
+    /// Sets the ray parameter of the hit.
     pub fn set_t(&mut self,t:f64) {
         self.t = t;
     }
 
+    /// Sets the hit point.
     pub fn set_p(&mut self, p: Point3) {
         self.p = p;
     }
 
+    /// Sets the normal as it is, see [`HitRecord::set_face_normal`].
     pub fn set_normal(&mut self, normal:Vec3) {
         self.normal = normal;
     }
 
 
-    // Determine if the normal is pointing in the same direction as the ray
+    /// Determine if the normal is pointing in the same direction as the ray.
     pub fn set_face_normal(&mut self,r:&Ray,outward_normal:Vec3){ 
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -78,6 +126,9 @@ impl Default for HitRecord {
             ),
             t: 0.0,
             front_face: false,
+            u: 0.0,
+            v: 0.0,
+            barycentric: Vec3::default(),
         }
     }
 }
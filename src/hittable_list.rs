@@ -1,28 +1,46 @@
+//! Plain lists of objects.
+
+use crate::aabb::Aabb;
 use crate::interval::Interval;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord,Hittable};
 
+/// Objects tested one after the other. Fine for a handful of objects,
+/// larger worlds should go into a [`FlatBvh`](crate::flat_bvh::FlatBvh).
 #[derive(Default)]
 pub struct HittableList {
-    objects: Vec<Box<dyn Hittable>>
+    objects: Vec<Box<dyn Hittable>>,
+    bbox: Aabb
 }
 
 impl HittableList {
+    /// Empty list.
     pub fn new() -> HittableList {
-        HittableList { objects: Vec::new() }
+        HittableList { objects: Vec::new(), bbox: Aabb::EMPTY }
     }
 
+    /// Appends an object, growing the bounding box around it.
     pub fn add(&mut self,object: Box<dyn Hittable>) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(object);
     }
 
+    /// Removes every object.
     pub fn clear(&mut self) {
         self.objects.clear();
-
+        self.bbox = Aabb::EMPTY;
     }
 
+    /// Appends all of `objects`.
     pub fn add_objects(&mut self,objects: Vec<Box<dyn Hittable>>){
-        self.objects.extend(objects);
+        for object in objects {
+            self.add(object);
+        }
+    }
+
+    /// Hands the objects over, e.g. to build an acceleration structure.
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
     }
 }
 
@@ -35,7 +53,7 @@ impl Hittable for HittableList {
         let mut closest = t.max();
 
         for object in &self.objects {
-            if object.hit(ray,&Interval::new(0.0, closest),&mut temp_rec) {
+            if object.hit(ray,&Interval::new(t.min(), closest),&mut temp_rec) {
                 hit_anything = true;
                 closest = temp_rec.t();
                 *rec = temp_rec.clone();
@@ -44,11 +62,28 @@ impl Hittable for HittableList {
 
         return hit_anything
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, ray: &Ray, t: &Interval) -> f64 {
+        let mut transmittance = 1.0;
+        for object in &self.objects {
+            transmittance *= object.transmittance(ray, t);
+            if transmittance <= 0.0 {
+                return 0.0;
+            }
+        }
+        transmittance
+    }
 }
 
 impl From<Vec<Box<dyn Hittable>>> for HittableList{
     fn from(objects: Vec<Box<dyn Hittable>>) -> Self {
-        HittableList{objects}
+        let mut list = HittableList::new();
+        list.add_objects(objects);
+        list
     }
 }
 
@@ -178,4 +213,33 @@ mod tests{
         assert!(approx_eq((rec.p() - Vec3::new(0.0, 0.0, -0.5)).length(), 0.0));
         assert!(approx_eq((rec.normal() - Vec3::new(0.0, 0.0, 1.0)).length(), 0.0));
     }
+
+    #[test]
+    fn test_bounding_box_grows_with_objects() {
+        let mut list = HittableList::new();
+        assert_eq!(list.bounding_box(), Aabb::EMPTY);
+
+        list.add(create_test_sphere(Vec3::new(0.0, 0.0, -1.0), 0.5));
+        list.add(create_test_sphere(Vec3::new(2.0, 0.0, -1.0), 0.5));
+
+        let bbox = list.bounding_box();
+        assert_eq!(bbox.x(), Interval::new(-0.5, 2.5));
+        assert_eq!(bbox.y(), Interval::new(-0.5, 0.5));
+        assert_eq!(bbox.z(), Interval::new(-1.5, -0.5));
+
+        list.clear();
+        assert_eq!(list.bounding_box(), Aabb::EMPTY);
+    }
+
+    #[test]
+    fn test_hit_respects_interval_min() {
+        let mut list = HittableList::new();
+        list.add(create_test_sphere(Vec3::new(0.0, 0.0, -1.0), 0.5));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        // The near intersection at t = 0.5 is excluded, leaving the far one
+        assert!(list.hit(&ray, &Interval::new(0.6, INFINITY), &mut rec));
+        assert!(approx_eq(rec.t(), 1.5));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+//! Images of linear radiance and the bit depths they are written with.
+
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::color::Color;
+use crate::color_space::ColorSpace;
+use crate::hdr::load_hdr;
+use crate::parse_error::ParseError;
+use crate::pfm::load_pfm;
+use crate::png_io::load_png;
+use crate::ppm::load_ppm;
+
+/// Floating point RGB image stored row by row, starting with the top row.
+/// Pixels are linear; the color space gives their primaries and the curve
+/// encoders apply for 8 and 16 bit formats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    color_space: ColorSpace,
+}
+
+impl Image {
+    /// Black image of the given size.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::from_pixels(width, height, vec![Color::new(0.0, 0.0, 0.0); width * height])
+    }
+
+    /// Image from its pixels, top row first. Panics if their number does not
+    /// match the size.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height, "pixel count does not match {}x{}", width, height);
+        Self { width, height, pixels, color_space: ColorSpace::default() }
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// All pixels, top row first.
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    /// Pixel in column `x` of row `y`, counted from the top.
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Replaces the pixel in column `x` of row `y`.
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Color space the pixels are in.
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Applies `f` to every pixel, keeping the size and color space.
+    pub fn map_pixels(&self, f: impl Fn(Color) -> Color) -> Image {
+        let pixels = self.pixels.iter().map(|&pixel| f(pixel)).collect();
+        Image { width: self.width, height: self.height, pixels, color_space: self.color_space }
+    }
+
+    /// Same colors expressed with the primaries of `target`.
+    pub fn convert_to(&self, target: ColorSpace) -> Image {
+        let convert = self.color_space.conversion_to(target);
+        Image { color_space: target, ..self.map_pixels(convert) }
+    }
+}
+
+/// Sample size of the integer formats we write.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BitDepth {
+    /// 8 bits per sample.
+    #[default]
+    Eight,
+    /// 16 bits per sample.
+    Sixteen,
+}
+
+impl BitDepth {
+    /// Largest sample value.
+    pub fn max_value(self) -> u16 {
+        match self {
+            BitDepth::Eight => 255,
+            BitDepth::Sixteen => 65535,
+        }
+    }
+
+    /// Integer sample for a signal already encoded to [0, 1], rounded to the
+    /// nearest step so that readers dividing by max_value get it back.
+    pub fn quantize(self, encoded: f64) -> u16 {
+        (encoded.clamp(0.0, 1.0) * self.max_value() as f64).round() as u16
+    }
+}
+
+impl FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(BitDepth::Eight),
+            "16" => Ok(BitDepth::Sixteen),
+            other => Err(format!("unsupported bit depth '{}' (expected 8 or 16)", other)),
+        }
+    }
+}
+
+/// Picks the reader from the file extension.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<Image, ParseError> {
+    let path = path.as_ref();
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("png") => load_png(path),
+        Some("ppm" | "pgm") => load_ppm(path),
+        Some("hdr") => load_hdr(path),
+        Some("pfm") => load_pfm(path),
+        _ => Err(ParseError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: unsupported image format", path.display()),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixels_are_row_major() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(2, 1, Color::new(1.0, 0.5, 0.25));
+
+        assert_eq!(image.pixel(2, 1), Color::new(1.0, 0.5, 0.25));
+        assert_eq!(image.pixels()[5], Color::new(1.0, 0.5, 0.25));
+        assert_eq!(image.pixel(0, 0), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_convert_to() {
+        let image = Image::from_pixels(2, 1, vec![Color::new(1.0, 0.0, 0.0), Color::new(0.5, 0.5, 0.5)]);
+        assert_eq!(image.color_space(), ColorSpace::Srgb);
+
+        let wide = image.convert_to(ColorSpace::Rec2020);
+        assert_eq!(wide.color_space(), ColorSpace::Rec2020);
+        // Pure sRGB red is inside the wider gamut, greys stay grey
+        assert!(wide.pixel(0, 0).x < 1.0 && wide.pixel(0, 0).y > 0.0);
+        assert!((wide.pixel(1, 0) - Color::new(0.5, 0.5, 0.5)).length() < 1e-9);
+
+        let back = wide.convert_to(ColorSpace::Srgb);
+        assert!((back.pixel(0, 0) - Color::new(1.0, 0.0, 0.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_bit_depth_from_str() {
+        assert_eq!("8".parse(), Ok(BitDepth::Eight));
+        assert_eq!("16".parse::<BitDepth>().map(BitDepth::max_value), Ok(65535));
+        assert!("12".parse::<BitDepth>().is_err());
+    }
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(BitDepth::Eight.quantize(0.5), 128);
+        assert_eq!(BitDepth::Eight.quantize(0.499 / 255.0), 0);
+        assert_eq!(BitDepth::Eight.quantize(1.0), 255);
+        assert_eq!(BitDepth::Sixteen.quantize(1.0), 65535);
+        assert_eq!(BitDepth::Sixteen.quantize(-0.5), 0);
+        assert_eq!(BitDepth::Eight.quantize(f64::NAN), 0);
+        for sample in 0..=255u16 {
+            assert_eq!(BitDepth::Eight.quantize(sample as f64 / 255.0), sample);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_pixel_count_must_match() {
+        Image::from_pixels(2, 2, vec![Color::new(0.0, 0.0, 0.0); 3]);
+    }
+}
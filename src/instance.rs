@@ -0,0 +1,374 @@
+//! Objects placed in the scene by a transform, still or animated.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::vec3::{Point3, Vec3};
+
+/// Places a shared object in the world through a transform. Rays are moved
+/// into object space instead of moving the geometry, so a single mesh can be
+/// instanced many times.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl Instance {
+    /// Places `object` in the world with `transform`.
+    pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+        let bbox = transform.bounding_box(&object.bounding_box());
+        Self { object, transform, bbox }
+    }
+
+    /// Transform from object to world space.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        hit_transformed(self.object.as_ref(), &self.transform, r, t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, t: &Interval) -> f64 {
+        self.object.transmittance(&object_ray(&self.transform, r), t)
+    }
+}
+
+// The direction is left unnormalized so t means the same in both spaces
+fn object_ray(transform: &Transform, r: &Ray) -> Ray {
+    let inverse = transform.inverse();
+    Ray::with_time(inverse.point(r.origin()), inverse.vector(r.direction()), r.time())
+}
+
+fn hit_transformed(object: &dyn Hittable, transform: &Transform, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+    if !object.hit(&object_ray(transform, r), t, rec) {
+        return false;
+    }
+
+    // Linear maps keep the sign of normal·direction, so front_face holds
+    rec.set_p(transform.point(rec.p()));
+    rec.set_normal(transform.normal(rec.normal()).unit_vector());
+
+    true
+}
+
+/// Pose of an animated instance at one point in time. Rotation holds degrees
+/// around x, y and z, applied in that order after scaling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    time: f64,
+    translation: Vec3,
+    rotation: Vec3,
+    scale: Vec3,
+}
+
+impl Keyframe {
+    /// Keyframe at `time` with the object in its original pose.
+    pub fn new(time: f64) -> Self {
+        Self {
+            time,
+            translation: Vec3::default(),
+            rotation: Vec3::default(),
+            scale: Vec3::from_scalar(1.0),
+        }
+    }
+
+    /// Sets the offset applied last.
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    /// Sets the rotation in degrees around x, y and z.
+    pub fn with_rotation(mut self, degrees: Vec3) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    /// Sets the scale factors along each axis.
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Time of the keyframe.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Transform from object to world space in this pose, None if the scale
+    /// has a zero or non-finite component.
+    pub fn transform(&self) -> Option<Transform> {
+        let transform = Transform::scale(self.scale)?
+            .then(&Transform::rotate_x(self.rotation.x))
+            .then(&Transform::rotate_y(self.rotation.y))
+            .then(&Transform::rotate_z(self.rotation.z))
+            .then(&Transform::translate(self.translation));
+        Some(transform)
+    }
+
+    // Components are blended one by one, which keeps rotations turning the
+    // short way between close keyframes without any quaternion machinery
+    fn lerp(&self, other: &Keyframe, t: f64) -> Keyframe {
+        Keyframe {
+            time: (1.0 - t) * self.time + t * other.time,
+            translation: (1.0 - t) * self.translation + t * other.translation,
+            rotation: (1.0 - t) * self.rotation + t * other.rotation,
+            scale: (1.0 - t) * self.scale + t * other.scale,
+        }
+    }
+}
+
+/// Instance whose transform is interpolated between keyframes at the ray's
+/// time. Before the first and after the last keyframe the object holds still,
+/// so its bounding box covers every time, not only the keyframed span.
+#[derive(Clone)]
+pub struct AnimatedInstance {
+    object: Arc<dyn Hittable>,
+    keyframes: Vec<Keyframe>,
+    bbox: Aabb,
+}
+
+impl AnimatedInstance {
+    /// Animates `object` through `keyframes`, which may come in any order. Panics
+    /// when there are none or when one of them has no transform.
+    pub fn new(object: Arc<dyn Hittable>, mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty(), "an animated instance needs at least one keyframe");
+        assert!(keyframes.iter().all(|k| k.transform().is_some()), "keyframe scales must be non-zero and finite");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let bbox = AnimatedInstance::motion_bounds(&object.bounding_box(), &keyframes);
+        Self { object, keyframes, bbox }
+    }
+
+    /// Pose at `time`, interpolated between the surrounding keyframes.
+    pub fn keyframe_at(&self, time: f64) -> Keyframe {
+        let next = self.keyframes.partition_point(|k| k.time <= time);
+        if next == 0 {
+            return self.keyframes[0];
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1];
+        }
+
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        a.lerp(b, (time - a.time) / (b.time - a.time))
+    }
+
+    // Box around everything the object sweeps through. While the rotation is
+    // fixed every corner moves in a straight line, so the keyframe poses are
+    // enough. A rotating segment is bounded by the sphere the object can turn
+    // in around its moving origin.
+    fn motion_bounds(bbox: &Aabb, keyframes: &[Keyframe]) -> Aabb {
+        if bbox.is_empty() {
+            return *bbox;
+        }
+
+        let posed = |k: &Keyframe| k.transform().map_or(Aabb::EMPTY, |transform| transform.bounding_box(bbox));
+        let mut bounds = posed(&keyframes[0]);
+        for pair in keyframes.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            if a.rotation == b.rotation {
+                bounds = Aabb::surrounding(&bounds, &posed(b));
+                continue;
+            }
+
+            let radius = f64::max(AnimatedInstance::reach(bbox, a.scale), AnimatedInstance::reach(bbox, b.scale));
+            let r = Vec3::from_scalar(radius);
+            for k in [a, b] {
+                let sphere_box = Aabb::from_points(k.translation - r, k.translation + r);
+                bounds = Aabb::surrounding(&bounds, &sphere_box);
+            }
+        }
+        bounds
+    }
+
+    // Farthest a corner of the scaled box gets from the origin
+    fn reach(bbox: &Aabb, scale: Vec3) -> f64 {
+        let farthest = |i: Interval, s: f64| f64::max((i.min() * s).abs(), (i.max() * s).abs());
+        Point3::new(farthest(bbox.x(), scale.x), farthest(bbox.y(), scale.y), farthest(bbox.z(), scale.z)).length()
+    }
+}
+
+impl Hittable for AnimatedInstance {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        // Keyframes whose scales change sign flatten the object for an instant
+        let Some(transform) = self.keyframe_at(r.time()).transform() else {
+            return false;
+        };
+        hit_transformed(self.object.as_ref(), &transform, r, t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn transmittance(&self, r: &Ray, t: &Interval) -> f64 {
+        match self.keyframe_at(r.time()).transform() {
+            Some(transform) => self.object.transmittance(&object_ray(&transform, r), t),
+            None => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::material::Lambertian;
+    use crate::quad::make_box;
+    use crate::sphere::Sphere;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material))
+    }
+
+    #[test]
+    fn test_translated_instance() {
+        let instance = Instance::new(unit_sphere(), Transform::translate(Vec3::new(0.0, 0.0, -5.0)));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(instance.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert!((rec.t() - 4.0).abs() < EPSILON);
+        assert!((rec.p() - Point3::new(0.0, 0.0, -4.0)).length() < EPSILON);
+        assert!((rec.normal() - Vec3::new(0.0, 0.0, 1.0)).length() < EPSILON);
+        assert!(rec.front_face());
+    }
+
+    #[test]
+    fn test_scaled_instance_keeps_t_and_normals() {
+        // Ellipsoid stretched to 3 along x
+        let transform = Transform::scale(Vec3::new(3.0, 1.0, 1.0)).unwrap().then(&Transform::translate(Vec3::new(0.0, 0.0, -5.0)));
+        let instance = Instance::new(unit_sphere(), transform);
+
+        let ray = Ray::new(Point3::new(10.0, 0.0, -5.0), Vec3::new(-2.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(instance.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert!((rec.t() - 3.5).abs() < EPSILON);
+        assert!((rec.p() - Point3::new(3.0, 0.0, -5.0)).length() < EPSILON);
+        assert!((rec.normal() - Vec3::new(1.0, 0.0, 0.0)).length() < EPSILON);
+
+        let bbox = instance.bounding_box();
+        assert!((bbox.x().max() - 3.0).abs() < EPSILON);
+        assert!((bbox.z().min() + 6.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_rotated_box() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let cube: Arc<dyn Hittable> = Arc::new(make_box(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0), material));
+        let instance = Instance::new(cube, Transform::rotate_y(45.0));
+
+        // The rotated cube reaches out to sqrt(2) along x, with an edge facing +x
+        let ray = Ray::new(Point3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(instance.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert!((rec.p().x - 2f64.sqrt()).abs() < 1e-6);
+        assert!(rec.front_face());
+        assert!((rec.normal().length() - 1.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_shared_object_many_instances() {
+        let sphere = unit_sphere();
+        let instances: Vec<Instance> = (0..100)
+            .map(|i| Instance::new(sphere.clone(), Transform::translate(Vec3::new(3.0 * i as f64, 0.0, 0.0))))
+            .collect();
+
+        // One copy of the geometry plus a handle per instance
+        assert_eq!(Arc::strong_count(&sphere), 101);
+
+        let ray = Ray::new(Point3::new(30.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(instances[10].hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert!(!instances[11].hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_keyframes_interpolate() {
+        let instance = AnimatedInstance::new(
+            unit_sphere(),
+            vec![
+                Keyframe::new(1.0).with_translation(Vec3::new(4.0, 0.0, 0.0)).with_rotation(Vec3::new(0.0, 90.0, 0.0)),
+                Keyframe::new(0.0),
+            ],
+        );
+
+        assert_eq!(instance.keyframe_at(-1.0), Keyframe::new(0.0));
+        assert_eq!(instance.keyframe_at(0.25).translation, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(instance.keyframe_at(0.5).rotation, Vec3::new(0.0, 45.0, 0.0));
+        assert_eq!(instance.keyframe_at(2.0).translation, Vec3::new(4.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_animated_instance_uses_ray_time() {
+        let instance = AnimatedInstance::new(
+            unit_sphere(),
+            vec![Keyframe::new(0.0), Keyframe::new(1.0).with_translation(Vec3::new(0.0, 4.0, 0.0))],
+        );
+        let mut rec = HitRecord::default();
+
+        let at = |time| Ray::with_time(Point3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0), time);
+        assert!(!instance.hit(&at(0.0), &Interval::new(0.001, INFINITY), &mut rec));
+        assert!(instance.hit(&at(0.5), &Interval::new(0.001, INFINITY), &mut rec));
+        assert!((rec.p() - Point3::new(0.0, 2.0, -1.0)).length() < EPSILON);
+        assert!(!instance.hit(&at(1.0), &Interval::new(0.001, INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_flattened_pose_is_missed() {
+        // Mirroring through the yz plane passes through a zero scale halfway
+        let instance = AnimatedInstance::new(
+            unit_sphere(),
+            vec![Keyframe::new(0.0), Keyframe::new(1.0).with_scale(Vec3::new(-1.0, 1.0, 1.0))],
+        );
+        let mut rec = HitRecord::default();
+
+        let at = |time| Ray::with_time(Point3::new(0.0, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0), time);
+        assert!(!instance.hit(&at(0.5), &Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(instance.transmittance(&at(0.5), &Interval::new(0.001, INFINITY)), 1.0);
+        assert!(instance.hit(&at(0.25), &Interval::new(0.001, INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_animated_bounds_cover_the_sweep() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let plank: Arc<dyn Hittable> = Arc::new(make_box(Point3::new(0.0, 0.0, -0.1), Point3::new(2.0, 0.1, 0.1), material));
+        let instance = AnimatedInstance::new(
+            plank.clone(),
+            vec![Keyframe::new(0.0), Keyframe::new(1.0).with_rotation(Vec3::new(0.0, 90.0, 0.0))],
+        );
+
+        let bounds = instance.bounding_box();
+        for i in -10..=30 {
+            let pose = instance.keyframe_at(i as f64 / 20.0).transform().unwrap().bounding_box(&plank.bounding_box());
+            assert_eq!(Aabb::surrounding(&bounds, &pose), bounds);
+        }
+
+        // Without rotation the box is just the two end poses
+        let sliding = AnimatedInstance::new(
+            plank,
+            vec![Keyframe::new(0.0), Keyframe::new(1.0).with_translation(Vec3::new(0.0, 0.0, 3.0))],
+        );
+        assert!((sliding.bounding_box().z().max() - 3.1).abs() < 1e-3);
+        assert!((sliding.bounding_box().x().max() - 2.0).abs() < 1e-3);
+    }
+}
@@ -1,5 +1,9 @@
+//! Closed intervals of real numbers.
+
 use crate::commons::INFINITY;
 
+/// Interval from `min` to `max`, empty when `min` is larger.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Interval {
     min:f64,
     max:f64
@@ -16,22 +20,40 @@ impl Default for Interval{
 
 impl Interval {
 
+    /// Interval containing nothing.
+    pub const EMPTY: Interval = Interval { min: INFINITY, max: -INFINITY };
+    /// Interval containing every number.
+    pub const UNIVERSE: Interval = Interval { min: -INFINITY, max: INFINITY };
+
+    /// Interval from `min` to `max`.
     pub fn new(min:f64,max:f64) -> Self {
         Self {min,max}
     }
 
+    /// Smallest interval that encloses both a and b.
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        Self {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+        }
+    }
+
+    /// Length of the interval, negative when it is empty.
     pub fn size(&self) -> f64{
         self.max - self.min
     }
 
+    /// Whether `x` lies in the interval, bounds included.
     pub fn contains(&self,x:f64) -> bool {
         self.min <= x && x <= self.max
     }
 
+    /// Whether `x` lies strictly inside the interval.
     pub fn surrounds(&self,x:f64) -> bool {
         self.min < x && x < self.max
     }
 
+    /// Closest point of the interval to `x`.
     pub fn clamp(&self, x:f64) -> f64{
         if x < self.min { return self.min; }
         if x > self.max { return  self.max; }
@@ -39,11 +61,19 @@ impl Interval {
         x
     }
 
+    /// Interval grown by `delta` in total, half on each side.
+    pub fn expand(&self, delta:f64) -> Interval {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+
     //Getters
+    /// Upper bound.
     pub fn max(&self) -> f64 {
         self.max
     }
 
+    /// Lower bound.
     pub fn min(&self) -> f64 {
         self.min
     }
@@ -86,4 +116,29 @@ mod tests {
         assert_eq!(interval.clamp(0.5), 0.5);
         assert_eq!(interval.clamp(1.5), 1.0);
     }
+
+    #[test]
+    fn test_interval_empty() {
+        assert!(!Interval::EMPTY.contains(0.0));
+        assert!(Interval::EMPTY.size() < 0.0);
+    }
+
+    #[test]
+    fn test_interval_enclosing() {
+        let a = Interval::new(0.0, 1.0);
+        let b = Interval::new(-2.0, 0.5);
+        let c = Interval::enclosing(&a, &b);
+        assert_eq!(c.min(), -2.0);
+        assert_eq!(c.max(), 1.0);
+
+        // Enclosing with the empty interval leaves the other untouched
+        assert_eq!(Interval::enclosing(&Interval::EMPTY, &a), a);
+    }
+
+    #[test]
+    fn test_interval_expand() {
+        let interval = Interval::new(0.0, 1.0).expand(0.5);
+        assert_eq!(interval.min(), -0.25);
+        assert_eq!(interval.max(), 1.25);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+//! Path tracer following Peter Shirley's *Ray Tracing in One Weekend* books.
+//!
+//! A render is a [`Camera`] looking at a world of [`Hittable`] objects, most
+//! often gathered in a [`HittableList`] and wrapped in a [`FlatBvh`] before
+//! rendering. Objects get their look from a [`Material`], and materials take
+//! their colors from a [`Texture`]. Rendering returns an [`Image`] of linear
+//! radiance, which the encoders in [`ppm`] and friends write to disk, after
+//! a [`tonemap::ToneMap`] for 8 and 16 bit formats.
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use raytracer::image::BitDepth;
+//! use raytracer::{ppm, Camera, Color, HittableList, Lambertian, Point3, Sphere};
+//!
+//! let mut world = HittableList::new();
+//! let ground = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+//! world.add(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, ground)));
+//!
+//! let mut camera = Camera::default();
+//! camera.image_width = 400;
+//! camera.samples_per_pixel = 64;
+//! camera.lookat = Point3::new(0.0, 0.0, -1.0);
+//! let image = camera.render(&world);
+//! ppm::save_ppm(&image, "image.ppm", BitDepth::Eight, ppm::Encoding::Binary)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! Scenes can also be described in TOML files and read with
+//! [`scene::load_scene`], and meshes loaded from OBJ, PLY and STL files.
+
+#![warn(missing_docs)]
+
+pub mod aabb;
+pub mod background;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod color_space;
+mod commons;
+pub mod constant_medium;
+pub mod density_grid;
+mod distribution;
+pub mod environment;
+pub mod exr;
+pub mod flat_bvh;
+pub mod grid_medium;
+pub mod hdr;
+pub mod hittable;
+pub mod hittable_list;
+pub mod image;
+pub mod instance;
+pub mod interval;
+pub mod material;
+mod mtl;
+pub mod obj;
+pub mod parse_error;
+mod perlin;
+pub mod pfm;
+pub mod ply;
+pub mod png_io;
+pub mod ppm;
+pub mod quad;
+pub mod ray;
+pub mod scene;
+pub mod sphere;
+pub mod stl;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
+pub mod triangle;
+mod triangle_mesh;
+pub mod vec3;
+
+// The types most programs need, so they can be imported from the crate root
+pub use background::Background;
+pub use bvh::BvhNode;
+pub use camera::Camera;
+pub use color::Color;
+pub use flat_bvh::{FlatBvh, SplitStrategy};
+pub use hittable::{HitRecord, Hittable};
+pub use hittable_list::HittableList;
+pub use image::Image;
+pub use instance::{AnimatedInstance, Instance, Keyframe};
+pub use interval::Interval;
+pub use material::{Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal};
+pub use parse_error::ParseError;
+pub use quad::Quad;
+pub use ray::Ray;
+pub use scene::Scene;
+pub use sphere::Sphere;
+pub use texture::Texture;
+pub use transform::Transform;
+pub use triangle_mesh::TriangleMesh;
+pub use vec3::{Point3, Vec3};
@@ -1,86 +1,41 @@
+use std::error::Error;
 use std::io;
+use std::path::Path;
+use std::process::ExitCode;
 
-mod vec3;
-mod ray;
-mod sphere;
-mod color;
-mod hittable_list;
-mod hittable;
-mod commons;
-mod camera;
-mod interval;
-mod material;
-
-use commons::INFINITY;
-use hittable::{HitRecord, Hittable};
-use hittable_list::HittableList;
-use material::{Dielectric, Metal,Lambertian};
-use ray::Ray;
-use sphere::Sphere;
-use vec3::{Point3, Vec3};
-use camera::Camera;
-use color::{write_color,Color};
-use dotenv::dotenv;
-use std::sync::Arc;
-
-fn toy_env() -> io::Result<()>{
-    let mut world = HittableList::new();
+mod cli;
 
-    // let R = (PI/4.0).cos();
-
-
-    let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    let material_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    let material_left = Arc::new(Dielectric::new(1.50));
-    let matrerial_bubble = Arc::new(Dielectric::new(1.0/1.50));
-    let material_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2),0.7));
-
-    let spheres: Vec<Box<dyn Hittable>> = vec![
-        // Center sphere
-        Box::new(Sphere::new(Vec3::new(0.0, 0.0, -1.2), 0.5, material_center)),
-        // Left sphere
-        Box::new(Sphere::new(Vec3::new(-1.0, 0.0, -1.0), 0.5, material_left)),
-        // Bubble Sphere
-        Box::new(Sphere::new(Vec3::new(-1.0,0.0,-1.0),0.4,matrerial_bubble)),
-        // Right sphere
-        Box::new(Sphere::new(Vec3::new(1.0, 0.0, -1.0), 0.5, material_right)),
-        // Ground sphere
-        Box::new(Sphere::new(Vec3::new(0.0, -100.5, -1.0), 100.0, material_ground))
-    ];
-    world.add_objects(spheres);
-
-    let aspect_ratio = 16.0/9.0;
-    let image_width = 400;
-    let samples_per_pixel = 200;
-    let max_depth = 100;
-
-    let vfov = 45.0;
-    let lookfrom = Point3::new(0.0,0.0,1.0);
-    let lookat = Point3::new(0.0, 0.0, -1.0);
-    let vup = Vec3::new(0.0, 1.0, 0.0);
+use std::sync::Arc;
 
-    let defocus_angle = 0.0;
-    let focus_dist = 1.0;
-    
-    let mut cam = Camera::new(
-                                        aspect_ratio,
-                                        image_width,
-                                        samples_per_pixel,
-                                        max_depth,
-                                        vfov,
-                                        lookfrom,
-                                        lookat,
-                                        vup,
-                                        defocus_angle,
-                                        focus_dist
-                                    );
-
-    cam.render(&world)?;
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use raytracer::image::Image;
+use raytracer::scene::{self, Scene};
+use raytracer::exr::SampleType;
+use raytracer::ppm::Encoding;
+use raytracer::tonemap::ToneMap;
+use raytracer::{exr, hdr, pfm, png_io, ppm};
+use raytracer::{Camera, Color, Dielectric, FlatBvh, HittableList, Lambertian, Metal, Point3, Sphere, SplitStrategy, Vec3};
+
+use cli::{Cli, OutputFormat, SceneSource};
+
+fn build_bvh(world: HittableList, strategy: SplitStrategy) -> FlatBvh {
+    let start = std::time::Instant::now();
+    let bvh = FlatBvh::new(world, strategy);
+    log::info!("Built {:?} BVH in {:?} ({})", strategy, start.elapsed(), bvh.stats());
+
+    bvh
+}
 
-    Ok(())
+fn random_color(rng: &mut StdRng, min: f64, max: f64) -> Color {
+    Color::new(rng.random_range(min..max), rng.random_range(min..max), rng.random_range(min..max))
 }
 
-fn book_env() -> io::Result<()> {
+// The random spheres come from their own generator, so the same seed always
+// builds the same scene
+fn book_env(seed: u64) -> Scene {
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut world = HittableList::new();
 
     // Ground material and sphere
@@ -94,23 +49,23 @@ fn book_env() -> io::Result<()> {
     // Random small spheres
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat = commons::random_double();
+            let choose_mat: f64 = rng.random();
             let center = Point3::new(
-                a as f64 + 0.9 * commons::random_double(),
+                a as f64 + 0.9 * rng.random::<f64>(),
                 0.2,
-                b as f64 + 0.9 * commons::random_double(),
+                b as f64 + 0.9 * rng.random::<f64>(),
             );
 
             if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 if choose_mat < 0.8 {
                     // Diffuse
-                    let albedo = Color::random() * Color::random();
+                    let albedo = random_color(&mut rng, 0.0, 1.0) * random_color(&mut rng, 0.0, 1.0);
                     let sphere_material = Arc::new(Lambertian::new(albedo));
                     world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
                 } else if choose_mat < 0.95 {
                     // Metal
-                    let albedo = Color::random_range(0.5, 1.0);
-                    let fuzz = commons::random_double() * 0.5;
+                    let albedo = random_color(&mut rng, 0.5, 1.0);
+                    let fuzz = rng.random_range(0.0..0.5);
                     let sphere_material = Arc::new(Metal::new(albedo, fuzz));
                     world.add(Box::new(Sphere::new(center, 0.2, sphere_material)));
                 } else {
@@ -158,7 +113,7 @@ fn book_env() -> io::Result<()> {
     let defocus_angle = 0.6;
     let focus_dist = 10.0;
 
-    let mut cam = Camera::new(
+    let camera = Camera::new(
         aspect_ratio,
         image_width,
         samples_per_pixel,
@@ -171,17 +126,101 @@ fn book_env() -> io::Result<()> {
         focus_dist,
     );
 
-    cam.render(&world)?;
+    Scene { world, camera }
+}
 
-    Ok(())
+fn load_scene(cli: &Cli, seed: u64) -> Result<Scene, Box<dyn Error>> {
+    let scene = match cli.scene_source()? {
+        SceneSource::Book => book_env(seed),
+        SceneSource::Bundled { name, text } => {
+            // Bundled scenes reference no other files, so there is no base
+            scene::parse_scene(text, Path::new("")).map_err(|err| format!("{}: {}", name, err))?
+        }
+        SceneSource::File(path) => scene::load_scene(&path).map_err(|err| format!("{}: {}", path.display(), err))?,
+    };
+    Ok(scene)
 }
 
+fn save_image(image: &Image, cli: &Cli, format: OutputFormat) -> io::Result<()> {
+    let path = &cli.output;
+    let depth = cli.bit_depth.unwrap_or_default();
+    let encoding = if cli.plain { Encoding::Plain } else { Encoding::Binary };
+    match format {
+        OutputFormat::Ppm => ppm::save_ppm(image, path, depth, encoding),
+        OutputFormat::Pgm => ppm::save_pgm(image, path, depth, encoding),
+        OutputFormat::Png => png_io::save_png(image, path, depth),
+        OutputFormat::Exr => {
+            let sample_type = if cli.float { SampleType::Float } else { SampleType::Half };
+            exr::save_exr(image, path, sample_type)
+        }
+        OutputFormat::Hdr => hdr::save_hdr(image, path),
+        OutputFormat::Pfm => pfm::save_pfm(image, path),
+    }
+}
 
+fn run(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    // Check everything that can go wrong before spending time rendering
+    let format = cli.output_format()?;
+    let float_format = matches!(format, OutputFormat::Exr | OutputFormat::Hdr | OutputFormat::Pfm);
+    if float_format && cli.bit_depth.is_some() {
+        return Err("--bit-depth only applies to PPM, PGM and PNG output, float formats are never quantized".into());
+    }
+    if float_format && (cli.tonemap.is_some() || cli.exposure.is_some() || cli.white_point.is_some()) {
+        return Err("tone mapping only applies to PPM, PGM and PNG output, float formats keep the linear radiance".into());
+    }
+    if cli.white_point.is_some_and(|white| !(white > 0.0 && white.is_finite())) {
+        return Err("--white-point must be a positive number".into());
+    }
+    if cli.plain && !matches!(format, OutputFormat::Ppm | OutputFormat::Pgm) {
+        return Err("--plain only applies to PPM and PGM output".into());
+    }
+    if cli.float && format != OutputFormat::Exr {
+        return Err("--float only applies to EXR output".into());
+    }
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()?;
+    }
+
+    let seed = cli.seed.unwrap_or(0);
+    let Scene { world, mut camera } = load_scene(cli, seed)?;
 
-fn main() -> io::Result<()>{
-    //Load enviroment variables from .env
-    dotenv().ok();
+    if let Some(width) = cli.width {
+        camera.image_width = width;
+    }
+    if let Some(samples) = cli.samples {
+        camera.samples_per_pixel = samples;
+    }
+    if let Some(max_depth) = cli.max_depth {
+        camera.max_depth = max_depth;
+    }
+    camera.seed = seed;
+
+    let world = build_bvh(world, cli.bvh_split);
+    let mut image = camera.render(&world);
+    if !float_format {
+        let tonemap = ToneMap {
+            operator: cli.tonemap.unwrap_or_default(),
+            exposure: cli.exposure.unwrap_or(0.0),
+            white_point: cli.white_point,
+        };
+        image = tonemap.apply_image(&image);
+    }
+    let image = image.convert_to(cli.color_space);
+    save_image(&image, cli, format).map_err(|err| format!("{}: {}", cli.output.display(), err))?;
 
-    // book_env()
-    toy_env()
-}
\ No newline at end of file
+    log::info!("Wrote {}", cli.output.display());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,9 +1,15 @@
+//! How surfaces and volumes scatter light.
+
+use std::sync::Arc;
+
 use crate::{
-    color::Color, commons::random_double, hittable::HitRecord, ray::Ray, vec3::{Point3, Vec3}
+    color::Color, commons::{random_double, PI}, hittable::HitRecord, ray::Ray, texture::{SolidColor, Texture}, vec3::{Point3, Vec3}
 };
 
+/// How a surface or volume scatters and emits light.
 pub trait Material: Send + Sync {
-
+    /// Returns false when the ray is absorbed, otherwise fills in the
+    /// scattered ray and how much it is attenuated.
     fn scatter(&self,
                 ray:&Ray, 
                 rec :&HitRecord,
@@ -12,17 +18,35 @@ pub trait Material: Send + Sync {
     {
         false
     }
+
+    /// Pdf of scattering into the direction of `scattered`, used to weight
+    /// light samples. Zero for materials that only scatter into one direction.
+    fn scattering_pdf(&self, _ray: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        0.0
+    }
+
+    /// Light given off by the surface itself, black for everything but lights.
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
 }
 
 
+/// Ideal diffuse surface.
 pub struct Lambertian {
-    albedo : Color
+    tex : Arc<dyn Texture>
 }
 
 impl Lambertian {
 
+    /// Diffuse surface of a single color.
     pub fn new(albedo:Color) -> Self {
-        Self { albedo }
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// Diffuse surface colored by a texture.
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
     }
     
 }
@@ -40,23 +64,36 @@ impl Material for Lambertian {
             scatter_direction = rec.normal()
         }
         
-        *scattered = Ray::new(rec.p, scatter_direction);
-        *attenuation = self.albedo;
+        *scattered = Ray::with_time(rec.p, scatter_direction, ray.time());
+        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
 
         true
     }
+
+    // Matches the cosine weighted directions picked by scatter
+    fn scattering_pdf(&self, _ray: &Ray, rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = rec.normal().dot(scattered.direction().unit_vector());
+        if cos_theta < 0.0 { 0.0 } else { cos_theta / PI }
+    }
 }
 
 
+/// Mirror surface, blurred by `fuzz`.
 pub struct Metal {
-    albedo : Color,
+    tex : Arc<dyn Texture>,
     fuzz: f64
 }
 
 impl Metal {
+    /// Metal of a single color. `fuzz` from 0 to 1 blurs the reflections.
     pub fn new(albedo:Color,fuzz:f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
+
+    /// Metal colored by a texture.
+    pub fn from_texture(tex: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self {
-            albedo,
+            tex,
             fuzz : if fuzz < 1.0 {fuzz} else {1.0}
         }
     }
@@ -70,8 +107,8 @@ impl Material for Metal {
                     scattered:&mut Ray) -> bool {
         let mut reflected = Vec3::reflect(&ray.direction().unit_vector(), &rec.normal());
         reflected = reflected.unit_vector() + (self.fuzz * Vec3::random_unit_vector());
-        *scattered = Ray::new(rec.p(), reflected);
-        *attenuation = self.albedo;
+        *scattered = Ray::with_time(rec.p(), reflected, ray.time());
+        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
         
         scattered.direction().dot(rec.normal) > 0.0
     }
@@ -79,11 +116,14 @@ impl Material for Metal {
 
 
 //Dielectric
+/// Glass and other clear materials, reflecting or refracting by Schlick's
+/// approximation.
 pub struct Dielectric{
     refraction_index : f64
 }
 
 impl Dielectric {
+    /// Dielectric with the given refractive index, relative to the medium around it.
     pub fn new(refraction_index:f64) -> Self {
         Self {
             refraction_index
@@ -125,10 +165,137 @@ impl Material for Dielectric {
             Vec3::refract(&unit_direction, &rec.normal, ri)
         };
 
-        *scattered = Ray::new(rec.p(), direction);
+        *scattered = Ray::with_time(rec.p(), direction, ray.time());
+
+        true
+    }
+}
+
+//DiffuseLight
+/// Surface emitting light evenly in every direction.
+pub struct DiffuseLight {
+    tex: Arc<dyn Texture>
+}
+
+impl DiffuseLight {
+    /// Light of a single color. Values above one make it brighter.
+    pub fn new(emit: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(emit)))
+    }
+
+    /// Light colored by a texture.
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for DiffuseLight {
+    // Lights absorb every incoming ray and only emit
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.tex.value(u, v, p)
+    }
+}
+
+//Isotropic
+/// Phase function of a participating medium: scatters the same amount into
+/// every direction of the sphere.
+pub struct Isotropic {
+    tex: Arc<dyn Texture>
+}
+
+impl Isotropic {
+    /// Isotropic medium of a single color.
+    pub fn new(albedo: Color) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
+    }
+
+    /// Isotropic medium colored by a texture.
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self,
+                    ray:&Ray,
+                    rec :&HitRecord,
+                    attenuation: &mut Color,
+                    scattered:&mut Ray) -> bool {
+        *scattered = Ray::with_time(rec.p(), Vec3::random_unit_vector(), ray.time());
+        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
+
+        true
+    }
+
+    fn scattering_pdf(&self, _ray: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+//HenyeyGreenstein
+/// Anisotropic phase function. g in (-1, 1) is the mean cosine of the
+/// scattering angle: positive values keep light going forward like clouds do,
+/// negative values send it back, zero is isotropic.
+pub struct HenyeyGreenstein {
+    tex: Arc<dyn Texture>,
+    g: f64
+}
+
+impl HenyeyGreenstein {
+    /// Medium of a single color with mean cosine `g`.
+    pub fn new(albedo: Color, g: f64) -> Self {
+        Self::from_texture(Arc::new(SolidColor::new(albedo)), g)
+    }
+
+    /// Medium colored by a texture with mean cosine `g`.
+    pub fn from_texture(tex: Arc<dyn Texture>, g: f64) -> Self {
+        Self { tex, g: g.clamp(-0.999, 0.999) }
+    }
+
+    // Density over the sphere for turning by an angle with cosine cos_theta
+    fn phase(&self, cos_theta: f64) -> f64 {
+        let g = self.g;
+        let denom = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denom * denom.sqrt())
+    }
+
+    // Inverts the cdf of the phase function
+    fn sample_cos_theta(&self, xi: f64) -> f64 {
+        let g = self.g;
+        if g.abs() < 1e-3 {
+            return 1.0 - 2.0 * xi;
+        }
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self,
+                    ray:&Ray,
+                    rec :&HitRecord,
+                    attenuation: &mut Color,
+                    scattered:&mut Ray) -> bool {
+        let w = ray.direction().unit_vector();
+        let a = if w.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let u = w.cross(a).unit_vector();
+        let v = w.cross(u);
+
+        let cos_theta = self.sample_cos_theta(random_double());
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        let direction = sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w;
+
+        *scattered = Ray::with_time(rec.p(), direction, ray.time());
+        *attenuation = self.tex.value(rec.u, rec.v, &rec.p);
 
         true
     }
+
+    fn scattering_pdf(&self, ray: &Ray, _rec: &HitRecord, scattered: &Ray) -> f64 {
+        let cos_theta = ray.direction().unit_vector().dot(scattered.direction().unit_vector());
+        self.phase(cos_theta)
+    }
 }
 
 #[cfg(test)]
@@ -156,4 +323,135 @@ mod tests {
         // Verify that scattered direction is in the same hemisphere as the normal
         assert!(scattered.direction().dot(hit_record.normal()) > 0.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_only_lights_emit() {
+        let p = Point3::new(1.0, 2.0, 3.0);
+        let lambertian = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        assert_eq!(lambertian.emitted(0.0, 0.0, &p), Color::new(0.0, 0.0, 0.0));
+
+        let light = DiffuseLight::new(Color::new(4.0, 4.0, 4.0));
+        assert_eq!(light.emitted(0.5, 0.5, &p), Color::new(4.0, 4.0, 4.0));
+
+        let mut scattered = Ray::default();
+        let mut attenuation = Color::default();
+        assert!(!light.scatter(&Ray::default(), &HitRecord::default(), &mut attenuation, &mut scattered));
+    }
+
+    #[test]
+    fn test_scattering_pdf() {
+        let lambertian = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let mut rec = HitRecord::default();
+        rec.set_normal(Vec3::new(0.0, 1.0, 0.0));
+
+        let up = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0));
+        let down = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        assert_eq!(lambertian.scattering_pdf(&Ray::default(), &rec, &up), 1.0 / PI);
+        assert_eq!(lambertian.scattering_pdf(&Ray::default(), &rec, &down), 0.0);
+
+        // Mirrors scatter into a single direction, so have no pdf
+        let metal = Metal::new(Color::new(0.5, 0.5, 0.5), 0.0);
+        assert_eq!(metal.scattering_pdf(&Ray::default(), &rec, &up), 0.0);
+    }
+
+    #[test]
+    fn test_albedo_comes_from_texture() {
+        use crate::texture::CheckerTexture;
+
+        let checker = Arc::new(CheckerTexture::from_colors(1.0, Color::new(1.0, 1.0, 1.0), Color::new(0.2, 0.3, 0.4)));
+        let lambertian = Lambertian::from_texture(checker.clone());
+        let metal = Metal::from_texture(checker, 0.0);
+
+        let mut rec = HitRecord::default();
+        rec.set_normal(Vec3::new(0.0, 1.0, 0.0));
+        rec.set_p(Point3::new(1.5, 0.0, 0.5));
+        let ray = Ray::new(Point3::new(1.0, 1.0, 0.0), Vec3::new(0.5, -1.0, 0.5));
+
+        let mut scattered = Ray::default();
+        let mut attenuation = Color::default();
+        lambertian.scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(0.2, 0.3, 0.4));
+
+        metal.scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(0.2, 0.3, 0.4));
+    }
+
+    #[test]
+    fn test_scattered_rays_keep_time() {
+        let mut rec = HitRecord::default();
+        rec.set_normal(Vec3::new(0.0, 1.0, 0.0));
+        let ray = Ray::with_time(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.5, -1.0, 0.0), 0.7);
+
+        let materials: [Box<dyn Material>; 3] = [
+            Box::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))),
+            Box::new(Metal::new(Color::new(0.5, 0.5, 0.5), 0.0)),
+            Box::new(Dielectric::new(1.5)),
+        ];
+        for material in &materials {
+            let mut scattered = Ray::default();
+            let mut attenuation = Color::default();
+            material.scatter(&ray, &rec, &mut attenuation, &mut scattered);
+            assert_eq!(scattered.time(), 0.7);
+        }
+    }
+
+    #[test]
+    fn test_isotropic_scatters_everywhere() {
+        let isotropic = Isotropic::new(Color::new(0.8, 0.8, 0.8));
+        let mut rec = HitRecord::default();
+        rec.set_normal(Vec3::new(0.0, 1.0, 0.0));
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        // Both hemispheres get picked, whatever the normal says
+        let mut below = 0;
+        for _ in 0..200 {
+            let mut scattered = Ray::default();
+            let mut attenuation = Color::default();
+            assert!(isotropic.scatter(&ray, &rec, &mut attenuation, &mut scattered));
+            assert_eq!(attenuation, Color::new(0.8, 0.8, 0.8));
+            assert!((scattered.direction().length() - 1.0).abs() < 1e-9);
+            if scattered.direction().dot(rec.normal()) < 0.0 {
+                below += 1;
+            }
+        }
+        assert!(below > 50 && below < 150);
+
+        let up = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(isotropic.scattering_pdf(&ray, &rec, &up), 1.0 / (4.0 * PI));
+    }
+
+    #[test]
+    fn test_henyey_greenstein() {
+        // The phase function integrates to one over the sphere
+        for g in [-0.7, 0.0, 0.3, 0.9] {
+            let hg = HenyeyGreenstein::new(Color::new(1.0, 1.0, 1.0), g);
+            let steps = 20000;
+            let integral: f64 = (0..steps)
+                .map(|i| {
+                    let cos_theta = -1.0 + 2.0 * (i as f64 + 0.5) / steps as f64;
+                    2.0 * PI * hg.phase(cos_theta) * 2.0 / steps as f64
+                })
+                .sum();
+            assert!((integral - 1.0).abs() < 1e-3, "g = {}: {}", g, integral);
+        }
+
+        // Sampled directions have g as their mean cosine
+        let hg = HenyeyGreenstein::new(Color::new(1.0, 1.0, 1.0), 0.6);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 3.0));
+        let samples = 20000;
+        let mut mean_cos = 0.0;
+        for _ in 0..samples {
+            let mut scattered = Ray::default();
+            let mut attenuation = Color::default();
+            hg.scatter(&ray, &HitRecord::default(), &mut attenuation, &mut scattered);
+            assert!((scattered.direction().length() - 1.0).abs() < 1e-9);
+            mean_cos += scattered.direction().z / samples as f64;
+        }
+        assert!((mean_cos - 0.6).abs() < 0.02, "mean cosine {}", mean_cos);
+
+        let forward = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let backward = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = HitRecord::default();
+        assert!(hg.scattering_pdf(&ray, &rec, &forward) > hg.scattering_pdf(&ray, &rec, &backward));
+    }
+}
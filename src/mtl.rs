@@ -0,0 +1,324 @@
+//! Wavefront MTL material libraries, as referenced by OBJ files.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::parse_error::ParseError;
+use crate::texture::{ImageTexture, SolidColor, Texture};
+
+// Directives we understand but have no use for in this renderer
+const IGNORED_DIRECTIVES: [&str; 12] = [
+    "Ka", "Ke", "Tf", "sharpness", "map_Ka", "map_Ks", "map_Ns", "map_d", "map_bump", "bump", "disp", "refl",
+];
+
+/// One `newmtl` block of a Wavefront material library.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MtlMaterial {
+    /// Name given by `newmtl`.
+    pub(crate) name: String,
+    /// Diffuse color, `Kd`.
+    pub(crate) diffuse: Color,
+    /// Specular color, `Ks`.
+    pub(crate) specular: Color,
+    /// Phong exponent, `Ns`.
+    pub(crate) shininess: f64,
+    /// Index of refraction, `Ni`.
+    pub(crate) refraction_index: f64,
+    /// Opacity, `d`, or one minus `Tr`.
+    pub(crate) dissolve: f64,
+    /// Illumination model, `illum`.
+    pub(crate) illum: u32,
+    /// Image replacing the diffuse color, `map_Kd`.
+    pub(crate) diffuse_map: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    /// Light grey diffuse material, what a block holds before its statements.
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refraction_index: 1.0,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+
+    fn is_transparent(&self) -> bool {
+        self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9)
+    }
+
+    fn is_mirror(&self) -> bool {
+        matches!(self.illum, 3 | 5 | 8)
+    }
+
+    /// Map shininess (Phong exponent, 0..1000) to a fuzz in [0, 1].
+    pub(crate) fn fuzz(&self) -> f64 {
+        (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt()
+    }
+
+    // Kd, replaced by the map_Kd image when it can be loaded
+    fn diffuse_texture(&self) -> Arc<dyn Texture> {
+        if let Some(path) = &self.diffuse_map {
+            match ImageTexture::load(path) {
+                Ok(texture) => return Arc::new(texture),
+                Err(err) => log::warn!("Material '{}': can't load {}: {}, using Kd", self.name, path.display(), err),
+            }
+        }
+        Arc::new(SolidColor::new(self.diffuse))
+    }
+
+    /// Translate onto the closest of our material types: transparent
+    /// illumination models become glass, ray traced reflection becomes metal and
+    /// everything else is diffuse.
+    pub(crate) fn to_material(&self) -> Arc<dyn Material> {
+        if self.is_transparent() {
+            // Ni defaults to 1.0, which would make the glass invisible
+            let ri = if self.refraction_index > 1.0 { self.refraction_index } else { 1.5 };
+            Arc::new(Dielectric::new(ri))
+        } else if self.is_mirror() {
+            if self.specular.near_zero() {
+                Arc::new(Metal::from_texture(self.diffuse_texture(), self.fuzz()))
+            } else {
+                Arc::new(Metal::new(self.specular, self.fuzz()))
+            }
+        } else {
+            Arc::new(Lambertian::from_texture(self.diffuse_texture()))
+        }
+    }
+}
+
+/// Reads a material library, resolving texture paths against its directory.
+pub(crate) fn load_mtl<P: AsRef<Path>>(path: P) -> Result<Vec<MtlMaterial>, ParseError> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let mut materials = parse_mtl(BufReader::new(file))?;
+
+    // Texture paths are relative to the library itself
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    for material in &mut materials {
+        if let Some(map) = material.diffuse_map.take() {
+            material.diffuse_map = Some(base.join(map));
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Reads the `newmtl` blocks of a material library. Statements without a use
+/// here are skipped, unknown ones logged.
+pub(crate) fn parse_mtl<R: BufRead>(reader: R) -> Result<Vec<MtlMaterial>, ParseError> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+
+        let content = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(ParseError::syntax(line_number, "newmtl needs a name"));
+            }
+            materials.push(MtlMaterial::new(&args.join(" ")));
+            continue;
+        }
+
+        let Some(current) = materials.last_mut() else {
+            return Err(ParseError::syntax(
+                line_number,
+                format!("'{}' appears before any newmtl", keyword),
+            ));
+        };
+
+        match keyword {
+            "Kd" => current.diffuse = parse_color(&args, line_number)?,
+            "Ks" => current.specular = parse_color(&args, line_number)?,
+            "Ns" => current.shininess = parse_scalar(&args, line_number)?,
+            "Ni" => current.refraction_index = parse_scalar(&args, line_number)?,
+            "d" => current.dissolve = parse_scalar(&args, line_number)?,
+            "Tr" => current.dissolve = 1.0 - parse_scalar(&args, line_number)?,
+            "illum" => {
+                let value = args.first().ok_or_else(|| ParseError::syntax(line_number, "illum needs a value"))?;
+                current.illum = value
+                    .parse()
+                    .map_err(|_| ParseError::syntax(line_number, format!("invalid illum model '{}'", value)))?;
+            }
+            "map_Kd" => {
+                // Options such as -s or -o come first, the file name is last
+                let file = args.last().ok_or_else(|| ParseError::syntax(line_number, "map_Kd needs a file name"))?;
+                current.diffuse_map = Some(PathBuf::from(file));
+            }
+            other if IGNORED_DIRECTIVES.contains(&other) => {
+                log::debug!("MTL line {}: ignoring '{}'", line_number, other);
+            }
+            other => log::warn!("MTL line {}: ignoring unknown directive '{}'", line_number, other),
+        }
+    }
+
+    Ok(materials)
+}
+
+fn parse_scalar(args: &[&str], line: usize) -> Result<f64, ParseError> {
+    let token = args.first().ok_or_else(|| ParseError::syntax(line, "missing value"))?;
+    token
+        .parse()
+        .map_err(|_| ParseError::syntax(line, format!("invalid number '{}'", token)))
+}
+
+fn parse_color(args: &[&str], line: usize) -> Result<Color, ParseError> {
+    // Spectral and XYZ color statements are not supported
+    if matches!(args.first(), Some(&"spectral") | Some(&"xyz")) {
+        return Err(ParseError::syntax(line, "only RGB colors are supported"));
+    }
+
+    let r = parse_scalar(args, line)?;
+    // A single value means a grey color
+    if args.len() == 1 {
+        return Ok(Color::new(r, r, r));
+    }
+    if args.len() < 3 {
+        return Err(ParseError::syntax(line, format!("color needs 1 or 3 components, found {}", args.len())));
+    }
+
+    let g = parse_scalar(&args[1..], line)?;
+    let b = parse_scalar(&args[2..], line)?;
+    Ok(Color::new(r, g, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::HitRecord;
+    use crate::ray::Ray;
+
+    const LIBRARY: &str = "\
+# A plastic, a mirror and a glass
+newmtl red_plastic
+Kd 0.8 0.1 0.1
+Ks 0.5 0.5 0.5
+Ns 96
+illum 2
+map_Kd -s 1 1 1 textures/red.png
+
+newmtl chrome
+Kd 0.2 0.2 0.2
+Ks 0.9 0.9 0.9
+Ns 1000
+illum 3
+
+newmtl glass
+Ni 1.45
+d 0.1
+illum 6
+Ka 0 0 0
+";
+
+    #[test]
+    fn test_parse_mtl_library() {
+        let materials = parse_mtl(LIBRARY.as_bytes()).unwrap();
+        assert_eq!(materials.len(), 3);
+
+        let plastic = &materials[0];
+        assert_eq!(plastic.name, "red_plastic");
+        assert_eq!(plastic.diffuse, Color::new(0.8, 0.1, 0.1));
+        assert_eq!(plastic.shininess, 96.0);
+        assert_eq!(plastic.diffuse_map, Some(PathBuf::from("textures/red.png")));
+
+        assert_eq!(materials[1].illum, 3);
+        assert_eq!(materials[2].refraction_index, 1.45);
+        assert_eq!(materials[2].dissolve, 0.1);
+    }
+
+    #[test]
+    fn test_fuzz_decreases_with_shininess() {
+        let mut material = MtlMaterial::new("m");
+        material.shininess = 0.0;
+        assert_eq!(material.fuzz(), 1.0);
+
+        material.shininess = 1000.0;
+        assert!(material.fuzz() < 0.05);
+    }
+
+    #[test]
+    fn test_material_mapping() {
+        let mut material = MtlMaterial::new("m");
+
+        // Diffuse, mirror and glass all need the matching scatter behaviour
+        let rec = HitRecord::default();
+        let ray = Ray::default();
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+
+        material.diffuse = Color::new(0.1, 0.2, 0.3);
+        material.to_material().scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(0.1, 0.2, 0.3));
+
+        material.illum = 3;
+        material.specular = Color::new(0.9, 0.8, 0.7);
+        material.to_material().scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(0.9, 0.8, 0.7));
+
+        material.illum = 2;
+        material.dissolve = 0.5;
+        material.to_material().scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_grey_color_and_transmission() {
+        let materials = parse_mtl("newmtl a\nKd 0.5\nTr 0.25\n".as_bytes()).unwrap();
+        assert_eq!(materials[0].diffuse, Color::new(0.5, 0.5, 0.5));
+        assert_eq!(materials[0].dissolve, 0.75);
+    }
+
+    #[test]
+    fn test_unknown_directive_is_not_fatal() {
+        let materials = parse_mtl("newmtl a\nPr 0.5\nKd 1 0 0\n".as_bytes()).unwrap();
+        assert_eq!(materials[0].diffuse, Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_errors_report_line_numbers() {
+        let err = parse_mtl("newmtl a\nKd 1 0\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+
+        let err = parse_mtl("Kd 1 0 0\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = parse_mtl("newmtl a\n\nillum shiny\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 3, .. }));
+    }
+
+    #[test]
+    fn test_diffuse_map_is_used_as_texture() {
+        let path = std::env::temp_dir().join(format!("mtl_diffuse_map_{}.ppm", std::process::id()));
+        std::fs::write(&path, "P3\n1 1\n255\n0 255 0\n").unwrap();
+
+        let mut material = MtlMaterial::new("textured");
+        material.diffuse_map = Some(path.clone());
+
+        let rec = HitRecord::default();
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+        material.to_material().scatter(&Ray::default(), &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(0.0, 1.0, 0.0));
+
+        // A missing image falls back to Kd
+        std::fs::remove_file(&path).unwrap();
+        material.to_material().scatter(&Ray::default(), &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(0.8, 0.8, 0.8));
+    }
+}
@@ -0,0 +1,604 @@
+//! Wavefront OBJ meshes.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::mtl::{load_mtl, MtlMaterial};
+use crate::parse_error::ParseError;
+use crate::triangle_mesh::{MeshData, MeshFace, TriangleMesh};
+use crate::vec3::{Point3, Vec3};
+
+/// A triangulated face, with indices already resolved to 0-based offsets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjFace {
+    /// Indices into [`ObjData::positions`].
+    pub positions: [usize; 3],
+    /// Indices into [`ObjData::normals`], if the face has normals.
+    pub normals: Option<[usize; 3]>,
+    /// Indices into [`ObjData::uvs`], if the face has texture coordinates.
+    pub uvs: Option<[usize; 3]>,
+    /// Index into ObjData::groups.
+    pub group: usize,
+    /// Index into ObjData::material_names, set by the last usemtl statement.
+    pub material: Option<usize>,
+}
+
+/// Raw contents of a Wavefront OBJ file.
+#[derive(Debug, Default)]
+pub struct ObjData {
+    /// Vertex positions, `v` statements.
+    pub positions: Vec<Point3>,
+    /// Vertex normals, `vn` statements.
+    pub normals: Vec<Vec3>,
+    /// Texture coordinates, `vt` statements.
+    pub uvs: Vec<(f64, f64)>,
+    /// Faces, already triangulated.
+    pub faces: Vec<ObjFace>,
+    /// Group names, starting with `default`.
+    pub groups: Vec<String>,
+    /// Files named by `mtllib` statements.
+    pub material_libraries: Vec<String>,
+    /// Materials named by `usemtl` statements.
+    pub material_names: Vec<String>,
+}
+
+impl ObjData {
+    /// Build a mesh with a single material for every face.
+    pub fn into_mesh(self, material: Arc<dyn Material>) -> TriangleMesh {
+        self.into_mesh_with_library(&[], material)
+    }
+
+    // Build a mesh whose faces use the library material named by their usemtl
+    // statement, falling back to `default` when there is none
+    pub(crate) fn into_mesh_with_library(self, library: &[MtlMaterial], default: Arc<dyn Material>) -> TriangleMesh {
+        // Slot 0 holds the default, every used library material follows
+        let mut materials = vec![default];
+        let mut slots: Vec<Option<usize>> = vec![None; self.material_names.len()];
+
+        for (name, slot) in self.material_names.iter().zip(slots.iter_mut()) {
+            match library.iter().find(|m| m.name == *name) {
+                Some(found) => {
+                    materials.push(found.to_material());
+                    *slot = Some(materials.len() - 1);
+                }
+                None => log::warn!("OBJ material '{}' is not defined in any library, using the default", name),
+            }
+        }
+
+        let faces = self
+            .faces
+            .iter()
+            .map(|face| MeshFace {
+                positions: face.positions,
+                normals: face.normals,
+                uvs: face.uvs,
+                material: face.material.and_then(|m| slots[m]).unwrap_or(0),
+            })
+            .collect();
+
+        TriangleMesh::new(MeshData {
+            positions: self.positions,
+            normals: self.normals,
+            uvs: self.uvs,
+            faces,
+            materials,
+        })
+    }
+}
+
+/// Load an OBJ file along with the material libraries it references, which are
+/// looked up relative to the OBJ file itself.
+pub fn load_obj<P: AsRef<Path>>(path: P, default_material: Arc<dyn Material>) -> Result<TriangleMesh, ParseError> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    let data = parse_obj(BufReader::new(file))?;
+
+    let base = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut library = Vec::new();
+    for name in &data.material_libraries {
+        match load_mtl(base.join(name)) {
+            Ok(materials) => library.extend(materials),
+            // A missing library only costs us the materials, not the geometry
+            Err(ParseError::Io(err)) => log::warn!("Could not read material library '{}': {}", name, err),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(data.into_mesh_with_library(&library, default_material))
+}
+
+/// Reads the vertices, faces, groups and materials of an OBJ file. Line and
+/// point elements are skipped, unknown statements are logged and skipped.
+pub fn parse_obj<R: BufRead>(reader: R) -> Result<ObjData, ParseError> {
+    let mut data = ObjData {
+        groups: vec![String::from("default")],
+        ..ObjData::default()
+    };
+    let mut current_group = 0;
+    let mut current_material = None;
+
+    for (index, line) in reader.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line?;
+
+        // Strip comments and skip blank lines
+        let content = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = content.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&args, line_number, "vertex")?;
+                data.positions.push(Point3::new(x, y, z));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&args, line_number, "normal")?;
+                data.normals.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                // The optional third (w) coordinate is ignored
+                let args = if args.len() > 2 { &args[..2] } else { &args[..] };
+                let [u, v] = match args.len() {
+                    1 => [parse_float(args[0], line_number)?, 0.0],
+                    _ => parse_floats::<2>(args, line_number, "texture coordinate")?,
+                };
+                data.uvs.push((u, v));
+            }
+            "f" => {
+                let face = parse_face(&args, &data, (current_group, current_material), line_number)?;
+                data.faces.extend(face);
+            }
+            "g" | "o" => {
+                let name = if args.is_empty() { String::from("default") } else { args.join(" ") };
+                current_group = match data.groups.iter().position(|g| *g == name) {
+                    Some(existing) => existing,
+                    None => {
+                        data.groups.push(name);
+                        data.groups.len() - 1
+                    }
+                };
+            }
+            "mtllib" => data.material_libraries.extend(args.iter().map(|lib| lib.to_string())),
+            "usemtl" => {
+                let name = args.join(" ");
+                if name.is_empty() {
+                    return Err(ParseError::syntax(line_number, "usemtl needs a material name"));
+                }
+                current_material = match data.material_names.iter().position(|m| *m == name) {
+                    Some(existing) => Some(existing),
+                    None => {
+                        data.material_names.push(name);
+                        Some(data.material_names.len() - 1)
+                    }
+                };
+            }
+            // Smoothing groups, lines and points have no effect on the geometry we build
+            "s" | "l" | "p" => {}
+            other => log::warn!("OBJ line {}: ignoring unknown directive '{}'", line_number, other),
+        }
+    }
+
+    Ok(data)
+}
+
+fn parse_float(token: &str, line: usize) -> Result<f64, ParseError> {
+    token
+        .parse::<f64>()
+        .map_err(|_| ParseError::syntax(line, format!("invalid number '{}'", token)))
+}
+
+fn parse_floats<const N: usize>(args: &[&str], line: usize, what: &str) -> Result<[f64; N], ParseError> {
+    if args.len() < N {
+        return Err(ParseError::syntax(
+            line,
+            format!("{} needs {} coordinates, found {}", what, N, args.len()),
+        ));
+    }
+
+    let mut values = [0.0; N];
+    for (value, token) in values.iter_mut().zip(args) {
+        *value = parse_float(token, line)?;
+    }
+    Ok(values)
+}
+
+// Resolve a 1-based (or negative, relative) OBJ index against `count` elements
+fn resolve_index(token: &str, count: usize, what: &str, line: usize) -> Result<usize, ParseError> {
+    let raw: i64 = token
+        .parse()
+        .map_err(|_| ParseError::syntax(line, format!("invalid {} index '{}'", what, token)))?;
+
+    let resolved = match raw {
+        0 => None,
+        i if i > 0 => Some(i as usize - 1),
+        i => count.checked_sub(i.unsigned_abs() as usize),
+    };
+
+    match resolved {
+        Some(i) if i < count => Ok(i),
+        _ => Err(ParseError::syntax(
+            line,
+            format!("{} index {} out of range ({} defined)", what, raw, count),
+        )),
+    }
+}
+
+fn parse_face(
+    args: &[&str],
+    data: &ObjData,
+    (group, material): (usize, Option<usize>),
+    line: usize,
+) -> Result<Vec<ObjFace>, ParseError> {
+    if args.len() < 3 {
+        return Err(ParseError::syntax(
+            line,
+            format!("face needs at least 3 vertices, found {}", args.len()),
+        ));
+    }
+
+    let mut positions = Vec::with_capacity(args.len());
+    let mut uvs = Vec::with_capacity(args.len());
+    let mut normals = Vec::with_capacity(args.len());
+
+    // Each vertex is one of v, v/vt, v//vn or v/vt/vn
+    for vertex in args {
+        let mut parts = vertex.split('/');
+        let position = parts.next().unwrap_or("");
+        positions.push(resolve_index(position, data.positions.len(), "vertex", line)?);
+
+        match parts.next() {
+            Some("") | None => uvs.push(None),
+            Some(uv) => uvs.push(Some(resolve_index(uv, data.uvs.len(), "texture coordinate", line)?)),
+        }
+
+        match parts.next() {
+            Some("") | None => normals.push(None),
+            Some(normal) => normals.push(Some(resolve_index(normal, data.normals.len(), "normal", line)?)),
+        }
+    }
+
+    let points: Vec<Point3> = positions.iter().map(|&i| data.positions[i]).collect();
+
+    // Attributes only count if every corner of the polygon has them
+    let all_uvs: Option<Vec<usize>> = uvs.into_iter().collect();
+    let all_normals: Option<Vec<usize>> = normals.into_iter().collect();
+
+    let faces = triangulate(&points)
+        .into_iter()
+        .map(|[a, b, c]| ObjFace {
+            positions: [positions[a], positions[b], positions[c]],
+            normals: all_normals.as_ref().map(|n| [n[a], n[b], n[c]]),
+            uvs: all_uvs.as_ref().map(|uv| [uv[a], uv[b], uv[c]]),
+            group,
+            material,
+        })
+        .collect();
+
+    Ok(faces)
+}
+
+// Split a planar polygon into triangles by ear clipping, which also handles
+// concave polygons. Returns triples of indices into `points`.
+pub(crate) fn triangulate(points: &[Point3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives a robust normal even for concave polygons
+    let mut normal = Vec3::default();
+    for i in 0..n {
+        let current = points[i];
+        let next = points[(i + 1) % n];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    if normal.near_zero() {
+        return fan(&(0..n).collect::<Vec<_>>());
+    }
+
+    // Project onto the plane most perpendicular to the normal; the sign of the
+    // dropped axis tells us which winding counts as convex
+    let (ax, ay) = (normal.x.abs(), normal.y.abs());
+    let (project, sign): (Projection, f64) = if ax >= ay && ax >= normal.z.abs() {
+        (|p| (p.y, p.z), normal.x.signum())
+    } else if ay >= normal.z.abs() {
+        (|p| (p.z, p.x), normal.y.signum())
+    } else {
+        (|p| (p.x, p.y), normal.z.signum())
+    };
+
+    let projected: Vec<(f64, f64)> = points.iter().map(project).collect();
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let prev = remaining[(i + m - 1) % m];
+            let curr = remaining[i];
+            let next = remaining[(i + 1) % m];
+            is_ear(&projected, prev, curr, next, &remaining, sign)
+        });
+
+        match ear {
+            Some(i) => {
+                let prev = remaining[(i + m - 1) % m];
+                let next = remaining[(i + 1) % m];
+                triangles.push([prev, remaining[i], next]);
+                remaining.remove(i);
+            }
+            // Self-intersecting or degenerate input: give up and fan the rest
+            None => {
+                triangles.extend(fan(&remaining));
+                return triangles;
+            }
+        }
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+type Projection = fn(&Point3) -> (f64, f64);
+
+fn fan(indices: &[usize]) -> Vec<[usize; 3]> {
+    (1..indices.len() - 1)
+        .map(|i| [indices[0], indices[i], indices[i + 1]])
+        .collect()
+}
+
+fn cross_2d(o: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+fn is_ear(points: &[(f64, f64)], prev: usize, curr: usize, next: usize, remaining: &[usize], sign: f64) -> bool {
+    let (a, b, c) = (points[prev], points[curr], points[next]);
+
+    // Reflex (or flat) corners can never be ears
+    if cross_2d(a, b, c) * sign <= 0.0 {
+        return false;
+    }
+
+    // No other polygon vertex may lie inside the candidate triangle
+    remaining
+        .iter()
+        .filter(|&&i| i != prev && i != curr && i != next)
+        .all(|&i| {
+            let p = points[i];
+            let d1 = cross_2d(a, b, p) * sign;
+            let d2 = cross_2d(b, c, p) * sign;
+            let d3 = cross_2d(c, a, p) * sign;
+            d1 < 0.0 || d2 < 0.0 || d3 < 0.0
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+
+    fn parse(source: &str) -> Result<ObjData, ParseError> {
+        parse_obj(source.as_bytes())
+    }
+
+    fn syntax_line(err: ParseError) -> usize {
+        match err {
+            ParseError::Syntax { line, .. } => line,
+            other => panic!("expected a syntax error, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_single_triangle() {
+        let data = parse("# a triangle\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+
+        assert_eq!(data.positions.len(), 3);
+        assert_eq!(data.faces.len(), 1);
+        assert_eq!(data.faces[0].positions, [0, 1, 2]);
+        assert_eq!(data.faces[0].normals, None);
+        assert_eq!(data.faces[0].uvs, None);
+    }
+
+    #[test]
+    fn test_parse_vertex_attribute_formats() {
+        let source = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1 0
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1
+f 1//1 2//1 3//1
+f 1/1 2/2 3/3
+";
+        let data = parse(source).unwrap();
+
+        assert_eq!(data.uvs.len(), 3);
+        assert_eq!(data.faces[0].uvs, Some([0, 1, 2]));
+        assert_eq!(data.faces[0].normals, Some([0, 0, 0]));
+        assert_eq!(data.faces[1].uvs, None);
+        assert_eq!(data.faces[1].normals, Some([0, 0, 0]));
+        assert_eq!(data.faces[2].uvs, Some([0, 1, 2]));
+        assert_eq!(data.faces[2].normals, None);
+    }
+
+    #[test]
+    fn test_parse_negative_indices() {
+        let data = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(data.faces[0].positions, [0, 1, 2]);
+    }
+
+    #[test]
+    fn test_quad_is_split_into_two_triangles() {
+        let data = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n").unwrap();
+        assert_eq!(data.faces.len(), 2);
+    }
+
+    #[test]
+    fn test_groups_are_recorded() {
+        let source = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+g lid
+f 1 2 3
+o body
+f 1 2 3
+g lid
+f 1 2 3
+";
+        let data = parse(source).unwrap();
+
+        assert_eq!(data.groups, vec!["default", "lid", "body"]);
+        let groups: Vec<usize> = data.faces.iter().map(|f| f.group).collect();
+        assert_eq!(groups, vec![0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn test_errors_report_line_numbers() {
+        let err = parse("v 0 0 0\nv 1 0 0\nv 0 1 zero\n").unwrap_err();
+        assert_eq!(syntax_line(err), 3);
+
+        let err = parse("v 0 0 0\nv 1 0 0\n\nf 1 2 3\n").unwrap_err();
+        assert_eq!(syntax_line(err), 4);
+
+        let err = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2\n").unwrap_err();
+        assert_eq!(syntax_line(err), 4);
+
+        let err = parse("v 0 0\n").unwrap_err();
+        assert_eq!(syntax_line(err), 1);
+
+        let err = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n").unwrap_err();
+        assert_eq!(syntax_line(err), 4);
+    }
+
+    #[test]
+    fn test_unknown_directives_are_skipped() {
+        let data = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\ncstype bspline\ns off\nf 1 2 3\n").unwrap();
+        assert_eq!(data.faces.len(), 1);
+    }
+
+    #[test]
+    fn test_triangulate_concave_polygon() {
+        // L-shaped hexagon, whose fan triangulation from vertex 0 would leave the polygon
+        let points = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+            Point3::new(2.0, 1.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(1.0, 2.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+        ];
+
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+
+        // Areas must add up to the polygon's area of 3
+        let area: f64 = triangles
+            .iter()
+            .map(|[a, b, c]| 0.5 * (points[*b] - points[*a]).cross(points[*c] - points[*a]).length())
+            .sum();
+        assert!((area - 3.0).abs() < 1e-9);
+
+        // Every triangle must keep the polygon's orientation (no inverted ears)
+        for [a, b, c] in &triangles {
+            let n = (points[*b] - points[*a]).cross(points[*c] - points[*a]);
+            assert!(n.z > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_mesh_from_obj_is_hittable() {
+        let source = "\
+v -1 -1 0
+v 1 -1 0
+v 1 1 0
+v -1 1 0
+f 1 2 3 4
+";
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mesh = parse(source).unwrap().into_mesh(material);
+        let ray = Ray::new(Point3::new(0.5, -0.5, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert_eq!(mesh.triangle_count(), 2);
+        assert!(mesh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 2.0);
+    }
+
+    #[test]
+    fn test_usemtl_assigns_materials_per_face() {
+        let source = "\
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+g shiny
+usemtl chrome
+f 1 2 3
+usemtl red
+f 1 2 3
+usemtl chrome
+f 1 2 3
+";
+        let data = parse(source).unwrap();
+
+        assert_eq!(data.material_libraries, vec!["scene.mtl"]);
+        assert_eq!(data.material_names, vec!["chrome", "red"]);
+        let materials: Vec<Option<usize>> = data.faces.iter().map(|f| f.material).collect();
+        assert_eq!(materials, vec![None, Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_load_obj_with_material_library() {
+        let dir = std::env::temp_dir().join(format!("obj_mtl_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("mirror.mtl"), "newmtl mirror\nKs 0.9 0.9 0.9\nillum 3\n").unwrap();
+        std::fs::write(
+            dir.join("mirror.obj"),
+            "mtllib mirror.mtl missing.mtl\nv -1 -1 0\nv 1 -1 0\nv 0 1 0\nusemtl mirror\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let default = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mesh = load_obj(dir.join("mirror.obj"), default).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+        assert!(mesh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+
+        // The hit must carry the library's metal rather than the grey default
+        let mut attenuation = Vec3::default();
+        let mut scattered = Ray::default();
+        rec.mat.scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Vec3::new(0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn test_load_obj_missing_file() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let result = load_obj("does/not/exist.obj", material);
+        assert!(matches!(result, Err(ParseError::Io(_))));
+    }
+}
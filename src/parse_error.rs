@@ -0,0 +1,86 @@
+//! The error type shared by every file reader.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// Error raised by the text/binary asset readers. Syntax errors carry the
+/// 1-based line number they occurred at, binary data errors the 0-based index
+/// of the offending record (vertex, face, triangle...).
+#[derive(Debug)]
+pub enum ParseError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// Malformed text.
+    Syntax {
+        /// 1-based line number.
+        line: usize,
+        /// What is wrong with the line.
+        message: String,
+    },
+    /// Malformed binary data.
+    Record {
+        /// 0-based index of the record.
+        index: usize,
+        /// What is wrong with the record.
+        message: String,
+    },
+}
+
+impl ParseError {
+    /// Syntax error at `line`.
+    pub fn syntax(line: usize, message: impl Into<String>) -> Self {
+        ParseError::Syntax { line, message: message.into() }
+    }
+
+    /// Error in the record at `index`.
+    pub fn record(index: usize, message: impl Into<String>) -> Self {
+        ParseError::Record { index, message: message.into() }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(err) => write!(f, "{}", err),
+            ParseError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ParseError::Record { index, message } => write!(f, "record {}: {}", index, message),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(err) => Some(err),
+            ParseError::Syntax { .. } | ParseError::Record { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_error_display() {
+        let err = ParseError::syntax(12, "expected 3 coordinates");
+        assert_eq!(err.to_string(), "line 12: expected 3 coordinates");
+
+        let err = ParseError::record(7, "vertex index out of range");
+        assert_eq!(err.to_string(), "record 7: vertex index out of range");
+    }
+
+    #[test]
+    fn test_io_error_conversion() {
+        let err: ParseError = io::Error::new(io::ErrorKind::NotFound, "missing.obj").into();
+        assert!(matches!(err, ParseError::Io(_)));
+        assert!(err.source().is_some());
+    }
+}
@@ -0,0 +1,169 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::vec3::{Point3, Vec3};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient noise on the integer lattice. The tables come from a seeded RNG so
+// the same seed always gives the same pattern.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    randvec: [Vec3; POINT_COUNT],
+    perm_x: [usize; POINT_COUNT],
+    perm_y: [usize; POINT_COUNT],
+    perm_z: [usize; POINT_COUNT],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let randvec = std::array::from_fn(|_| {
+            let v = Vec3::new(
+                rng.random_range(-1.0..1.0),
+                rng.random_range(-1.0..1.0),
+                rng.random_range(-1.0..1.0),
+            );
+            if v.near_zero() { Vec3::new(1.0, 0.0, 0.0) } else { v.unit_vector() }
+        });
+
+        Self {
+            randvec,
+            perm_x: Perlin::generate_perm(&mut rng),
+            perm_y: Perlin::generate_perm(&mut rng),
+            perm_z: Perlin::generate_perm(&mut rng),
+        }
+    }
+
+    fn generate_perm(rng: &mut StdRng) -> [usize; POINT_COUNT] {
+        let mut perm = std::array::from_fn(|i| i);
+        perm.shuffle(rng);
+        perm
+    }
+
+    // Noise in [-1, 1], zero on every lattice point
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let u = p.x - p.x.floor();
+        let v = p.y - p.y.floor();
+        let w = p.z - p.z.floor();
+
+        let i = p.x.floor() as i64;
+        let j = p.y.floor() as i64;
+        let k = p.z.floor() as i64;
+
+        let mut c = [[[Vec3::default(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.perm_x[Perlin::wrap(i + di as i64)]
+                        ^ self.perm_y[Perlin::wrap(j + dj as i64)]
+                        ^ self.perm_z[Perlin::wrap(k + dk as i64)];
+                    *corner = self.randvec[index];
+                }
+            }
+        }
+
+        Perlin::perlin_interp(&c, u, v, w)
+    }
+
+    // Sum of `depth` octaves of noise, each at twice the frequency and half
+    // the weight of the previous one
+    pub fn turb(&self, p: &Point3, depth: u32) -> f64 {
+        let mut accum = 0.0;
+        let mut temp_p = *p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(&temp_p);
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+        }
+
+        accum.abs()
+    }
+
+    fn wrap(i: i64) -> usize {
+        (i & (POINT_COUNT as i64 - 1)) as usize
+    }
+
+    // Trilinear blend of the corner gradients, with Hermite smoothing of the
+    // weights to hide the lattice
+    fn perlin_interp(c: &[[[Vec3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accum = 0.0;
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, gradient) in row.iter().enumerate() {
+                    let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                    let weight_v = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * gradient.dot(weight_v);
+                }
+            }
+        }
+
+        accum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_points() -> Vec<Point3> {
+        (0..50)
+            .map(|i| {
+                let t = i as f64 * 0.731;
+                Point3::new(t.sin() * 7.3, t * 0.37 - 4.0, (t * 1.7).cos() * 3.1)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_same_noise() {
+        let a = Perlin::new(42);
+        let b = Perlin::new(42);
+        let c = Perlin::new(7);
+
+        let points = sample_points();
+        assert!(points.iter().all(|p| a.noise(p) == b.noise(p)));
+        assert!(points.iter().any(|p| a.noise(p) != c.noise(p)));
+    }
+
+    #[test]
+    fn test_noise_range_and_lattice() {
+        let perlin = Perlin::new(1);
+        for p in sample_points() {
+            let n = perlin.noise(&p);
+            assert!((-1.0..=1.0).contains(&n));
+        }
+        assert_eq!(perlin.noise(&Point3::new(3.0, -2.0, 5.0)), 0.0);
+    }
+
+    #[test]
+    fn test_noise_is_continuous() {
+        let perlin = Perlin::new(3);
+        let p = Point3::new(1.999999, 0.5, 0.5);
+        let q = Point3::new(2.000001, 0.5, 0.5);
+        assert!((perlin.noise(&p) - perlin.noise(&q)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_turbulence() {
+        let perlin = Perlin::new(5);
+        for p in sample_points() {
+            let turb = perlin.turb(&p, 7);
+            assert!(turb >= 0.0);
+            // Octave weights add up to less than 2
+            assert!(turb < 2.0);
+        }
+        assert_eq!(perlin.turb(&Point3::new(0.5, 0.5, 0.5), 0), 0.0);
+    }
+}
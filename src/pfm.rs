@@ -0,0 +1,190 @@
+//! Portable float map (.pfm) input and output.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::parse_error::ParseError;
+
+/// Reads a `.pfm` file, see [`parse_pfm`].
+pub fn load_pfm<P: AsRef<Path>>(path: P) -> Result<Image, ParseError> {
+    let bytes = fs::read(path)?;
+    parse_pfm(&bytes)
+}
+
+/// Portable float map, either RGB (PF) or greyscale (Pf). The sign of the
+/// scale gives the byte order and rows are stored bottom to top.
+pub fn parse_pfm(bytes: &[u8]) -> Result<Image, ParseError> {
+    let mut pos = 0;
+    let mut line = 1;
+    let mut next_token = || -> Result<(usize, String), ParseError> {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            if bytes[pos] == b'\n' {
+                line += 1;
+            }
+            pos += 1;
+        }
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(ParseError::syntax(line, "file ends inside the header"));
+        }
+        Ok((line, String::from_utf8_lossy(&bytes[start..pos]).into_owned()))
+    };
+
+    let (line, magic) = next_token()?;
+    let channels = match magic.as_str() {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(ParseError::syntax(line, format!("expected PF or Pf, found '{}'", magic))),
+    };
+
+    let mut parse_size = || -> Result<(usize, usize), ParseError> {
+        let (line, token) = next_token()?;
+        let size = token
+            .parse()
+            .map_err(|_| ParseError::syntax(line, format!("invalid image size '{}'", token)))?;
+        Ok((line, size))
+    };
+    let (size_line, width) = parse_size()?;
+    let (_, height) = parse_size()?;
+
+    let (line, token) = next_token()?;
+    let scale: f64 = token
+        .parse()
+        .map_err(|_| ParseError::syntax(line, format!("invalid scale '{}'", token)))?;
+    let little_endian = scale < 0.0;
+
+    // A single whitespace character separates the header from the data
+    let data = &bytes[(pos + 1).min(bytes.len())..];
+    let Some(row_size) = width.checked_mul(channels * 4) else {
+        return Err(ParseError::syntax(size_line, format!("image width {} is too large", width)));
+    };
+    // Check the header against the file before allocating the image, the
+    // first missing row is the one reported
+    if row_size == 0 || height == 0 {
+        return Ok(Image::new(width, height));
+    }
+    if data.len() / row_size < height {
+        return Err(ParseError::record(data.len() / row_size, "file ends inside the row"));
+    }
+
+    let mut image = Image::new(width, height);
+    for row in 0..height {
+        let Some(raw) = data.get(row * row_size..(row + 1) * row_size) else {
+            return Err(ParseError::record(row, "file ends inside the row"));
+        };
+
+        let values: Vec<f64> = raw
+            .chunks_exact(4)
+            .map(|c| {
+                let b = [c[0], c[1], c[2], c[3]];
+                let value = if little_endian { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) };
+                value as f64
+            })
+            .collect();
+
+        let y = height - 1 - row;
+        for x in 0..width {
+            let color = if channels == 3 {
+                Color::new(values[3 * x], values[3 * x + 1], values[3 * x + 2])
+            } else {
+                Color::from_scalar(values[x])
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+
+    Ok(image)
+}
+
+/// Writes `image` to a `.pfm` file, see [`write_pfm`].
+pub fn save_pfm<P: AsRef<Path>>(image: &Image, path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_pfm(image, &mut writer)?;
+    writer.flush()
+}
+
+/// Little endian RGB float map, bottom row first.
+pub fn write_pfm<W: Write>(image: &Image, writer: &mut W) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", image.width(), image.height())?;
+    for y in (0..image.height()).rev() {
+        for x in 0..image.width() {
+            let pixel = image.pixel(x, y);
+            for value in [pixel.x, pixel.y, pixel.z] {
+                writer.write_all(&(value as f32).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color_little_endian() {
+        let mut bytes = b"PF\n2 2\n-1.0\n".to_vec();
+        // Bottom row first
+        let values: [f32; 12] = [0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0, 0.5, 2.0, 4.0];
+        bytes.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+
+        let image = parse_pfm(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 1), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(image.pixel(1, 1), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(image.pixel(1, 0), Color::new(0.5, 2.0, 4.0));
+    }
+
+    #[test]
+    fn test_parse_grey_big_endian() {
+        let mut bytes = b"Pf\n3 1\n1.0\n".to_vec();
+        bytes.extend([0.25f32, 8.0, 0.0].iter().flat_map(|v| v.to_be_bytes()));
+
+        let image = parse_pfm(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(0.25, 0.25, 0.25));
+        assert_eq!(image.pixel(1, 0), Color::new(8.0, 8.0, 8.0));
+    }
+
+    #[test]
+    fn test_write_pfm_round_trip() {
+        let image = Image::from_pixels(2, 2, vec![
+            Color::new(1.0, 2.0, 3.0),
+            Color::new(1e4, 0.5, 0.0),
+            Color::new(-1.0, 0.0, 0.25),
+            Color::new(0.125, 7.0, 8.0),
+        ]);
+        let mut bytes = Vec::new();
+        write_pfm(&image, &mut bytes).unwrap();
+
+        assert!(bytes.starts_with(b"PF\n2 2\n-1.0\n"));
+        assert_eq!(parse_pfm(&bytes).unwrap(), image);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse_pfm(b"P6\n1 1\n255\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = parse_pfm(b"PF\n1 x\n-1.0\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+
+        let mut bytes = b"Pf\n1 2\n-1.0\n".to_vec();
+        bytes.extend(1.0f32.to_le_bytes());
+        let err = parse_pfm(&bytes).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 1, .. }));
+
+        // Huge sizes fail cleanly instead of allocating
+        let err = parse_pfm(b"PF\n4000000000 4000000000\n-1.0\n").unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 0, .. }));
+        let err = parse_pfm(b"PF\n18446744073709551615 1\n-1.0\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+        let image = parse_pfm(b"PF\n0 18446744073709551615\n-1.0\n").unwrap();
+        assert_eq!(image.pixels().len(), 0);
+    }
+}
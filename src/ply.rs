@@ -0,0 +1,576 @@
+//! Stanford PLY meshes.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::material::{Lambertian, Material};
+use crate::obj::triangulate;
+use crate::parse_error::ParseError;
+use crate::triangle_mesh::{MeshData, MeshFace, TriangleMesh};
+use crate::vec3::{Point3, Vec3};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(ScalarType::I8),
+            "uchar" | "uint8" => Some(ScalarType::U8),
+            "short" | "int16" => Some(ScalarType::I16),
+            "ushort" | "uint16" => Some(ScalarType::U16),
+            "int" | "int32" => Some(ScalarType::I32),
+            "uint" | "uint32" => Some(ScalarType::U32),
+            "float" | "float32" => Some(ScalarType::F32),
+            "double" | "float64" => Some(ScalarType::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Geometry read from a PLY file. Polygons are already triangulated.
+#[derive(Debug, Default)]
+pub struct PlyData {
+    /// Vertex positions.
+    pub positions: Vec<Point3>,
+    /// Vertex normals, empty when the file has none.
+    pub normals: Vec<Vec3>,
+    /// Vertex colors scaled to [0, 1], empty when the file has none.
+    pub colors: Vec<Color>,
+    /// Vertex texture coordinates, empty when the file has none.
+    pub uvs: Vec<(f64, f64)>,
+    /// Vertex indices of each triangle.
+    pub faces: Vec<[usize; 3]>,
+}
+
+impl PlyData {
+    /// Build a mesh using `material` for every face. If the file carries vertex
+    /// colors, each face instead gets a diffuse material of its averaged color.
+    pub fn into_mesh(self, material: Arc<dyn Material>) -> TriangleMesh {
+        let has_normals = self.normals.len() == self.positions.len() && !self.normals.is_empty();
+        let has_uvs = self.uvs.len() == self.positions.len() && !self.uvs.is_empty();
+        let has_colors = self.colors.len() == self.positions.len() && !self.colors.is_empty();
+
+        let mut materials = vec![material];
+        // Faces sharing the same 8-bit color share a material
+        let mut palette: HashMap<[u8; 3], usize> = HashMap::new();
+
+        let faces = self
+            .faces
+            .iter()
+            .map(|&positions| {
+                let material = if has_colors {
+                    let average = (self.colors[positions[0]] + self.colors[positions[1]] + self.colors[positions[2]]) / 3.0;
+                    let key = [average.x, average.y, average.z].map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                    *palette.entry(key).or_insert_with(|| {
+                        let albedo = Color::new(key[0] as f64, key[1] as f64, key[2] as f64) / 255.0;
+                        materials.push(Arc::new(Lambertian::new(albedo)));
+                        materials.len() - 1
+                    })
+                } else {
+                    0
+                };
+
+                MeshFace {
+                    positions,
+                    normals: if has_normals { Some(positions) } else { None },
+                    uvs: if has_uvs { Some(positions) } else { None },
+                    material,
+                }
+            })
+            .collect();
+
+        TriangleMesh::new(MeshData {
+            positions: self.positions,
+            normals: if has_normals { self.normals } else { Vec::new() },
+            uvs: if has_uvs { self.uvs } else { Vec::new() },
+            faces,
+            materials,
+        })
+    }
+}
+
+/// Reads a PLY file into a mesh, see [`PlyData::into_mesh`].
+pub fn load_ply<P: AsRef<Path>>(path: P, material: Arc<dyn Material>) -> Result<TriangleMesh, ParseError> {
+    let bytes = fs::read(path)?;
+    let data = parse_ply(&bytes)?;
+
+    Ok(data.into_mesh(material))
+}
+
+/// Reads the vertices and faces of an ASCII or binary PLY file.
+pub fn parse_ply(bytes: &[u8]) -> Result<PlyData, ParseError> {
+    let (format, elements, body_start, header_lines) = parse_header(bytes)?;
+    let body = &bytes[body_start..];
+
+    let mut reader = match format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body)
+                .map_err(|_| ParseError::syntax(header_lines + 1, "ASCII body is not valid UTF-8"))?;
+            BodyReader::Ascii { lines: text.lines().collect(), line: 0, first_line: header_lines + 1, tokens: Vec::new() }
+        }
+        Format::BinaryLittleEndian => BodyReader::Binary { bytes: body, offset: 0, big_endian: false, record: 0 },
+        Format::BinaryBigEndian => BodyReader::Binary { bytes: body, offset: 0, big_endian: true, record: 0 },
+    };
+
+    let mut data = PlyData::default();
+
+    for element in &elements {
+        reader.begin_element();
+        match element.name.as_str() {
+            "vertex" => read_vertices(&mut reader, element, &mut data)?,
+            "face" => read_faces(&mut reader, element, &mut data)?,
+            _ => {
+                for _ in 0..element.count {
+                    reader.begin_record()?;
+                    for property in &element.properties {
+                        reader.read_property(&property.kind)?;
+                    }
+                }
+            }
+        }
+    }
+
+    for (index, face) in data.faces.iter().enumerate() {
+        if let Some(&bad) = face.iter().find(|&&i| i >= data.positions.len()) {
+            return Err(ParseError::record(
+                index,
+                format!("face refers to vertex {} but only {} exist", bad, data.positions.len()),
+            ));
+        }
+    }
+
+    Ok(data)
+}
+
+// Returns the format, elements, byte offset of the body and number of header lines
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize, usize), ParseError> {
+    let mut offset = 0;
+    let mut line_number = 0;
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+
+    loop {
+        let Some(end) = bytes[offset..].iter().position(|&b| b == b'\n') else {
+            return Err(ParseError::syntax(line_number + 1, "header is missing end_header"));
+        };
+        let line = String::from_utf8_lossy(&bytes[offset..offset + end]).trim().to_string();
+        offset += end + 1;
+        line_number += 1;
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        if line_number == 1 {
+            if tokens != ["ply"] {
+                return Err(ParseError::syntax(1, "not a PLY file (missing 'ply' magic)"));
+            }
+            continue;
+        }
+
+        match tokens.as_slice() {
+            [] => {}
+            ["comment", ..] | ["obj_info", ..] => {}
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    other => return Err(ParseError::syntax(line_number, format!("unknown format '{}'", other))),
+                });
+            }
+            ["element", name, count] => {
+                let count = count
+                    .parse()
+                    .map_err(|_| ParseError::syntax(line_number, format!("invalid element count '{}'", count)))?;
+                elements.push(Element { name: name.to_string(), count, properties: Vec::new() });
+            }
+            ["property", "list", count, item, name] => {
+                let kind = PropertyKind::List {
+                    count: scalar_type(count, line_number)?,
+                    item: scalar_type(item, line_number)?,
+                };
+                push_property(&mut elements, name, kind, line_number)?;
+            }
+            ["property", ty, name] => {
+                let kind = PropertyKind::Scalar(scalar_type(ty, line_number)?);
+                push_property(&mut elements, name, kind, line_number)?;
+            }
+            ["end_header"] => break,
+            _ => return Err(ParseError::syntax(line_number, format!("unexpected header line '{}'", line))),
+        }
+    }
+
+    let format = format.ok_or_else(|| ParseError::syntax(line_number, "header has no format line"))?;
+    Ok((format, elements, offset, line_number))
+}
+
+fn scalar_type(name: &str, line: usize) -> Result<ScalarType, ParseError> {
+    ScalarType::parse(name).ok_or_else(|| ParseError::syntax(line, format!("unknown property type '{}'", name)))
+}
+
+fn push_property(elements: &mut [Element], name: &str, kind: PropertyKind, line: usize) -> Result<(), ParseError> {
+    let element = elements
+        .last_mut()
+        .ok_or_else(|| ParseError::syntax(line, "property declared before any element"))?;
+    element.properties.push(Property { name: name.to_string(), kind });
+    Ok(())
+}
+
+fn find_property(element: &Element, names: &[&str]) -> Option<usize> {
+    element.properties.iter().position(|p| names.contains(&p.name.as_str()))
+}
+
+fn read_vertices(reader: &mut BodyReader, element: &Element, data: &mut PlyData) -> Result<(), ParseError> {
+    let position = [
+        find_property(element, &["x"]),
+        find_property(element, &["y"]),
+        find_property(element, &["z"]),
+    ];
+    let [Some(x), Some(y), Some(z)] = position else {
+        return Err(ParseError::record(0, "vertex element needs x, y and z properties"));
+    };
+
+    let normal = [
+        find_property(element, &["nx"]),
+        find_property(element, &["ny"]),
+        find_property(element, &["nz"]),
+    ];
+    let color = [
+        find_property(element, &["red", "diffuse_red", "r"]),
+        find_property(element, &["green", "diffuse_green", "g"]),
+        find_property(element, &["blue", "diffuse_blue", "b"]),
+    ];
+    let uv = [
+        find_property(element, &["u", "s", "texture_u", "texture_s"]),
+        find_property(element, &["v", "t", "texture_v", "texture_t"]),
+    ];
+
+    let mut values = vec![0.0; element.properties.len()];
+
+    for _ in 0..element.count {
+        reader.begin_record()?;
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            // Lists in a vertex element are unexpected, skip their contents
+            *value = reader.read_property(&property.kind)?.first().copied().unwrap_or(0.0);
+        }
+
+        data.positions.push(Point3::new(values[x], values[y], values[z]));
+
+        if let [Some(nx), Some(ny), Some(nz)] = normal {
+            data.normals.push(Vec3::new(values[nx], values[ny], values[nz]));
+        }
+
+        if let [Some(r), Some(g), Some(b)] = color {
+            // Integer colors are 0-255, floating point colors 0-1
+            let scale = match element.properties[r].kind {
+                PropertyKind::Scalar(ScalarType::F32) | PropertyKind::Scalar(ScalarType::F64) => 1.0,
+                _ => 1.0 / 255.0,
+            };
+            data.colors.push(Color::new(values[r], values[g], values[b]) * scale);
+        }
+
+        if let [Some(u), Some(v)] = uv {
+            data.uvs.push((values[u], values[v]));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(reader: &mut BodyReader, element: &Element, data: &mut PlyData) -> Result<(), ParseError> {
+    let indices = find_property(element, &["vertex_indices", "vertex_index"]);
+
+    for _ in 0..element.count {
+        reader.begin_record()?;
+        let mut polygon = Vec::new();
+
+        for (i, property) in element.properties.iter().enumerate() {
+            let values = reader.read_property(&property.kind)?;
+            if Some(i) == indices {
+                polygon = values;
+            }
+        }
+
+        if polygon.len() < 3 {
+            return Err(reader.error(format!("face needs at least 3 vertices, found {}", polygon.len())));
+        }
+
+        let mut corners = Vec::with_capacity(polygon.len());
+        for value in polygon {
+            if value < 0.0 || value.fract() != 0.0 {
+                return Err(reader.error(format!("invalid vertex index {}", value)));
+            }
+            corners.push(value as usize);
+        }
+
+        // Indices are checked once all elements are read, so triangulate
+        // with whatever positions are available and fall back to a fan
+        let points: Option<Vec<Point3>> = corners.iter().map(|&i| data.positions.get(i).copied()).collect();
+        let triangles = match points {
+            Some(points) => triangulate(&points),
+            None => (1..corners.len() - 1).map(|i| [0, i, i + 1]).collect(),
+        };
+
+        data.faces
+            .extend(triangles.into_iter().map(|[a, b, c]| [corners[a], corners[b], corners[c]]));
+    }
+
+    Ok(())
+}
+
+enum BodyReader<'a> {
+    // Tokens of the current record are kept reversed so they can be popped
+    Ascii { lines: Vec<&'a str>, line: usize, first_line: usize, tokens: Vec<&'a str> },
+    Binary { bytes: &'a [u8], offset: usize, big_endian: bool, record: usize },
+}
+
+impl BodyReader<'_> {
+    // Binary records are numbered from 0 within each element
+    fn begin_element(&mut self) {
+        if let BodyReader::Binary { record, .. } = self {
+            *record = 0;
+        }
+    }
+
+    // ASCII records occupy one line each, binary records are just counted
+    fn begin_record(&mut self) -> Result<(), ParseError> {
+        match self {
+            BodyReader::Ascii { lines, line, first_line, tokens } => {
+                // Skip blank lines between records
+                while *line < lines.len() && lines[*line].trim().is_empty() {
+                    *line += 1;
+                }
+                if *line >= lines.len() {
+                    return Err(ParseError::syntax(*first_line + *line, "file ends before all elements were read"));
+                }
+
+                *tokens = lines[*line].split_whitespace().rev().collect();
+                *line += 1;
+            }
+            BodyReader::Binary { record, .. } => *record += 1,
+        }
+        Ok(())
+    }
+
+    // Error for the record currently being read
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        match self {
+            BodyReader::Ascii { line, first_line, .. } => ParseError::syntax(*first_line + *line - 1, message),
+            BodyReader::Binary { record, .. } => ParseError::record(record.saturating_sub(1), message),
+        }
+    }
+
+    fn read_property(&mut self, kind: &PropertyKind) -> Result<Vec<f64>, ParseError> {
+        match kind {
+            PropertyKind::Scalar(ty) => Ok(vec![self.read_scalar(*ty)?]),
+            PropertyKind::List { count, item } => {
+                let n = self.read_scalar(*count)?;
+                if n < 0.0 || n.fract() != 0.0 {
+                    return Err(self.error(format!("invalid list length {}", n)));
+                }
+                (0..n as usize).map(|_| self.read_scalar(*item)).collect()
+            }
+        }
+    }
+
+    fn read_scalar(&mut self, ty: ScalarType) -> Result<f64, ParseError> {
+        match self {
+            BodyReader::Ascii { tokens, .. } => {
+                let Some(token) = tokens.pop() else {
+                    return Err(self.error("record has too few values"));
+                };
+                token
+                    .parse::<f64>()
+                    .map_err(|_| self.error(format!("invalid number '{}'", token)))
+            }
+            BodyReader::Binary { bytes, offset, big_endian, .. } => {
+                let size = ty.size();
+                if *offset + size > bytes.len() {
+                    return Err(self.error("file ends before all elements were read"));
+                }
+
+                let mut raw = [0u8; 8];
+                raw[..size].copy_from_slice(&bytes[*offset..*offset + size]);
+                *offset += size;
+                if *big_endian {
+                    raw[..size].reverse();
+                }
+
+                Ok(match ty {
+                    ScalarType::I8 => raw[0] as i8 as f64,
+                    ScalarType::U8 => raw[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+                    ScalarType::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    ScalarType::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    ScalarType::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+                    ScalarType::F64 => f64::from_le_bytes(raw),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+
+    const ASCII_QUAD: &str = "\
+ply
+format ascii 1.0
+comment a unit quad with colors and normals
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 1 255 0 0
+1 1 0 0 0 1 255 0 0
+0 1 0 0 0 1 255 0 0
+4 0 1 2 3
+";
+
+    // Binary version of a single triangle with an extra per-face property
+    fn binary_triangle(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut bytes = format!(
+            "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\n\
+             element face 1\nproperty list uchar uint vertex_indices\nproperty short flags\nend_header\n",
+            format
+        )
+        .into_bytes();
+
+        let vertices: [f32; 9] = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+        for v in vertices {
+            bytes.extend(if big_endian { v.to_be_bytes() } else { v.to_le_bytes() });
+        }
+        bytes.push(3);
+        for i in [0u32, 1, 2] {
+            bytes.extend(if big_endian { i.to_be_bytes() } else { i.to_le_bytes() });
+        }
+        bytes.extend(if big_endian { 7i16.to_be_bytes() } else { 7i16.to_le_bytes() });
+        bytes
+    }
+
+    #[test]
+    fn test_parse_ascii_ply() {
+        let data = parse_ply(ASCII_QUAD.as_bytes()).unwrap();
+
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.normals.len(), 4);
+        assert_eq!(data.colors[0], Color::new(1.0, 0.0, 0.0));
+        // The quad is triangulated
+        assert_eq!(data.faces.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_binary_ply_both_endians() {
+        for big_endian in [false, true] {
+            let data = parse_ply(&binary_triangle(big_endian)).unwrap();
+            assert_eq!(data.positions[1], Point3::new(1.0, 0.0, 0.0));
+            assert_eq!(data.faces, vec![[0, 1, 2]]);
+        }
+    }
+
+    #[test]
+    fn test_vertex_colors_become_materials() {
+        let mesh = parse_ply(ASCII_QUAD.as_bytes())
+            .unwrap()
+            .into_mesh(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let ray = Ray::new(Point3::new(0.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(mesh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+
+        let mut attenuation = Color::default();
+        let mut scattered = Ray::default();
+        rec.mat.scatter(&ray, &rec, &mut attenuation, &mut scattered);
+        assert_eq!(attenuation, Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_truncated_binary_reports_record() {
+        let mut bytes = binary_triangle(false);
+        bytes.truncate(bytes.len() - 4);
+
+        let err = parse_ply(&bytes).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 0, .. }));
+    }
+
+    #[test]
+    fn test_ascii_errors_report_line_numbers() {
+        let bad_value = ASCII_QUAD.replace("1 1 0 0 0 1", "1 one 0 0 0 1");
+        let err = parse_ply(bad_value.as_bytes()).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 19, .. }));
+
+        let bad_index = ASCII_QUAD.replace("4 0 1 2 3", "3 0 1 9");
+        let err = parse_ply(bad_index.as_bytes()).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 0, .. }));
+
+        let err = parse_ply(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\nend_header\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 4, .. }));
+
+        let err = parse_ply(b"obj\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+    }
+}
@@ -0,0 +1,231 @@
+//! PNG input and output.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Seek, Write};
+use std::path::Path;
+
+use png::{ColorType, ScaledFloat, SourceChromaticities, SrgbRenderingIntent, Transformations};
+
+use crate::color::{srgb_to_linear, Color};
+use crate::color_space::ColorSpace;
+use crate::image::{BitDepth, Image};
+use crate::parse_error::ParseError;
+
+/// Reads a `.png` file, see [`parse_png`].
+pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Image, ParseError> {
+    let file = File::open(path)?;
+    decode_png(BufReader::new(file))
+}
+
+/// Decodes PNG bytes into a linear image, see [`load_png`].
+pub fn parse_png(bytes: &[u8]) -> Result<Image, ParseError> {
+    decode_png(Cursor::new(bytes))
+}
+
+// Decodes any PNG into linear RGB, taking the samples as sRGB. Palettes and
+// low bit depths are expanded by the decoder, alpha is dropped.
+fn decode_png<R: BufRead + Seek>(reader: R) -> Result<Image, ParseError> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(decoding_error)?;
+
+    let size = reader
+        .output_buffer_size()
+        .ok_or_else(|| ParseError::record(0, "image is too large to decode"))?;
+    let mut buffer = vec![0; size];
+    let info = reader.next_frame(&mut buffer).map_err(decoding_error)?;
+
+    let channels = match info.color_type {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        ColorType::Indexed => return Err(ParseError::record(0, "palette was not expanded")),
+    };
+    let (sample_size, max_value) = match info.bit_depth {
+        png::BitDepth::Sixteen => (2, 65535.0),
+        _ => (1, 255.0),
+    };
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut image = Image::new(width, height);
+    for y in 0..height {
+        let row = &buffer[y * info.line_size..(y + 1) * info.line_size];
+        for x in 0..width {
+            let sample = |channel: usize| {
+                let offset = (x * channels + channel) * sample_size;
+                let value = if sample_size == 2 {
+                    u16::from_be_bytes([row[offset], row[offset + 1]]) as f64
+                } else {
+                    row[offset] as f64
+                };
+                srgb_to_linear(value / max_value)
+            };
+
+            let color = if channels < 3 {
+                Color::from_scalar(sample(0))
+            } else {
+                Color::new(sample(0), sample(1), sample(2))
+            };
+            image.set_pixel(x, y, color);
+        }
+    }
+
+    Ok(image)
+}
+
+/// Writes `image` to a `.png` file, see [`write_png`].
+pub fn save_png<P: AsRef<Path>>(image: &Image, path: P, depth: BitDepth) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_png(image, &mut writer, depth)?;
+    writer.flush()
+}
+
+/// RGB PNG encoded with the curve of the image's color space. sRGB images get
+/// an sRGB chunk; other spaces are tagged with cICP, which color managed
+/// viewers understand, plus cHRM and gAMA as an approximation for older ones.
+pub fn write_png<W: Write>(image: &Image, writer: W, depth: BitDepth) -> io::Result<()> {
+    let (width, height) = (image.width(), image.height());
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, format!("{}x{} is too large for PNG", width, height));
+    let mut encoder = png::Encoder::new(
+        writer,
+        u32::try_from(width).map_err(|_| too_large())?,
+        u32::try_from(height).map_err(|_| too_large())?,
+    );
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(match depth {
+        BitDepth::Eight => png::BitDepth::Eight,
+        BitDepth::Sixteen => png::BitDepth::Sixteen,
+    });
+    let space = image.color_space();
+    if space == ColorSpace::Srgb {
+        encoder.set_source_srgb(SrgbRenderingIntent::Perceptual);
+    } else {
+        let c = space.chromaticities();
+        let xy = |(x, y): (f64, f64)| (x as f32, y as f32);
+        encoder.set_source_chromaticities(SourceChromaticities::new(xy(c.white), xy(c.red), xy(c.green), xy(c.blue)));
+        encoder.set_source_gamma(ScaledFloat::new(1.0 / 2.2));
+    }
+
+    let mut data = Vec::with_capacity(width * height * 3 * if depth == BitDepth::Sixteen { 2 } else { 1 });
+    for pixel in image.pixels() {
+        for component in [pixel.x, pixel.y, pixel.z] {
+            let value = depth.quantize(space.encode(component));
+            match depth {
+                BitDepth::Eight => data.push(value as u8),
+                BitDepth::Sixteen => data.extend(value.to_be_bytes()),
+            }
+        }
+    }
+
+    let mut writer = encoder.write_header()?;
+    if space != ColorSpace::Srgb {
+        // Primaries, transfer function, RGB matrix and full range
+        let (primaries, transfer) = space.code_points();
+        writer.write_chunk(png::chunk::cICP, &[primaries, transfer, 0, 1])?;
+    }
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn decoding_error(err: png::DecodingError) -> ParseError {
+    match err {
+        png::DecodingError::IoError(err) => ParseError::Io(err),
+        other => ParseError::Io(io::Error::new(io::ErrorKind::InvalidData, other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(width: u32, height: u32, color_type: ColorType, bit_depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode_rgb() {
+        let bytes = encode(2, 1, ColorType::Rgb, png::BitDepth::Eight, &[255, 0, 0, 0, 0, 255]);
+        let image = parse_png(&bytes).unwrap();
+
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(image.pixel(1, 0), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_decode_grey_alpha_16_bit() {
+        // Half grey in 16 bit, alpha is ignored
+        let bytes = encode(1, 1, ColorType::GrayscaleAlpha, png::BitDepth::Sixteen, &[0x80, 0x00, 0x12, 0x34]);
+        let image = parse_png(&bytes).unwrap();
+
+        let expected = srgb_to_linear(0x8000 as f64 / 65535.0);
+        assert_eq!(image.pixel(0, 0), Color::from_scalar(expected));
+    }
+
+    fn gradient() -> Image {
+        let pixels = (0..12).map(|i| Color::new(i as f64 / 11.0, 1.0 - i as f64 / 11.0, 0.25)).collect();
+        Image::from_pixels(4, 3, pixels)
+    }
+
+    fn assert_close(a: &Image, b: &Image, tolerance: f64) {
+        assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+        for (p, q) in a.pixels().iter().zip(b.pixels()) {
+            assert!((*p - *q).length() < tolerance, "{:?} vs {:?}", p, q);
+        }
+    }
+
+    #[test]
+    fn test_write_png_round_trip() {
+        let image = gradient();
+
+        let mut eight = Vec::new();
+        write_png(&image, &mut eight, BitDepth::Eight).unwrap();
+        assert_close(&parse_png(&eight).unwrap(), &image, 0.01);
+
+        let mut sixteen = Vec::new();
+        write_png(&image, &mut sixteen, BitDepth::Sixteen).unwrap();
+        assert_close(&parse_png(&sixteen).unwrap(), &image, 1e-4);
+    }
+
+    #[test]
+    fn test_write_png_header() {
+        let mut bytes = Vec::new();
+        write_png(&gradient(), &mut bytes, BitDepth::Sixteen).unwrap();
+
+        let reader = png::Decoder::new(Cursor::new(&bytes)).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (4, 3));
+        assert_eq!((info.color_type, info.bit_depth), (ColorType::Rgb, png::BitDepth::Sixteen));
+        assert_eq!(info.srgb, Some(SrgbRenderingIntent::Perceptual));
+    }
+
+    #[test]
+    fn test_write_png_tags_wide_gamut() {
+        let image = gradient().convert_to(ColorSpace::DisplayP3);
+        let mut bytes = Vec::new();
+        write_png(&image, &mut bytes, BitDepth::Eight).unwrap();
+
+        let reader = png::Decoder::new(Cursor::new(&bytes)).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.srgb, None);
+        assert_eq!(
+            info.coding_independent_code_points.map(|c| (c.color_primaries, c.transfer_function)),
+            Some((12, 13))
+        );
+        let chromaticities = info.chrm_chunk.unwrap();
+        assert_eq!(chromaticities.red.0, ScaledFloat::new(0.68));
+    }
+
+    #[test]
+    fn test_invalid_png() {
+        let err = parse_png(b"not a png").unwrap_err();
+        assert!(matches!(err, ParseError::Io(_)));
+    }
+}
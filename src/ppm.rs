@@ -0,0 +1,355 @@
+//! Netpbm PPM input and output.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::{srgb_to_linear, Color};
+use crate::image::{BitDepth, Image};
+use crate::parse_error::ParseError;
+
+/// How netpbm samples are stored after the header.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Decimal numbers (P2, P3), large and slow but human readable.
+    Plain,
+    /// Big endian bytes (P5, P6).
+    #[default]
+    Binary,
+}
+
+/// Reads a netpbm file, see [`parse_ppm`].
+pub fn load_ppm<P: AsRef<Path>>(path: P) -> Result<Image, ParseError> {
+    let bytes = fs::read(path)?;
+    parse_ppm(&bytes)
+}
+
+/// Plain (P3) or binary (P6) pixmap with 8 or 16 bit samples, or the P2 and P5
+/// greymaps. Netpbm has no way to tag a color space, so the samples are taken
+/// as sRGB and converted back to linear.
+pub fn parse_ppm(bytes: &[u8]) -> Result<Image, ParseError> {
+    let mut header = Header { bytes, pos: 0, line: 1 };
+
+    let (line, magic) = header.token()?;
+    let (channels, binary) = match magic.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(ParseError::syntax(line, format!("expected P2, P3, P5 or P6, found '{}'", magic))),
+    };
+
+    let width = header.number()?;
+    let (size_line, height) = header.number_with_line()?;
+    let (line, max_value) = header.number_with_line()?;
+    if max_value == 0 || max_value > 65535 {
+        return Err(ParseError::syntax(line, format!("maximum value {} is out of range", max_value)));
+    }
+
+    let sample_size = if max_value < 256 { 1 } else { 2 };
+    let too_large = || ParseError::syntax(size_line, format!("image size {}x{} is too large", width, height));
+    let count = width.checked_mul(height).and_then(|count| count.checked_mul(channels)).ok_or_else(too_large)?;
+    let samples: Vec<usize> = if binary {
+        // A single whitespace character separates the header from the data
+        let data = &bytes[(header.pos + 1).min(bytes.len())..];
+        let needed = count.checked_mul(sample_size).ok_or_else(too_large)?;
+        if data.len() < needed {
+            let pixel = data.len() / (channels * sample_size);
+            return Err(ParseError::record(pixel, "file ends inside the pixel data"));
+        }
+
+        if sample_size == 1 {
+            data[..needed].iter().map(|&b| b as usize).collect()
+        } else {
+            data[..needed].chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]]) as usize).collect()
+        }
+    } else {
+        // Grown as samples are read, the header alone can't be trusted
+        let mut samples = Vec::new();
+        for _ in 0..count {
+            let (line, token) = header.token()?;
+            let value: usize = token
+                .parse()
+                .map_err(|_| ParseError::syntax(line, format!("invalid sample '{}'", token)))?;
+            if value > max_value {
+                return Err(ParseError::syntax(line, format!("sample {} exceeds maximum {}", value, max_value)));
+            }
+            samples.push(value);
+        }
+        samples
+    };
+
+    let scale = 1.0 / max_value as f64;
+    let linear = |sample: usize| srgb_to_linear(sample as f64 * scale);
+    let pixels = samples
+        .chunks_exact(channels)
+        .map(|c| {
+            if channels == 1 {
+                Color::from_scalar(linear(c[0]))
+            } else {
+                Color::new(linear(c[0]), linear(c[1]), linear(c[2]))
+            }
+        })
+        .collect();
+
+    Ok(Image::from_pixels(width, height, pixels))
+}
+
+/// Writes `image` to a netpbm pixmap, see [`write_ppm`].
+pub fn save_ppm<P: AsRef<Path>>(image: &Image, path: P, depth: BitDepth, encoding: Encoding) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_ppm(image, &mut writer, depth, encoding)?;
+    writer.flush()
+}
+
+/// Pixmap encoded with the curve of the image's color space, P6 or P3
+/// depending on `encoding`. The color space itself is not recorded.
+pub fn write_ppm<W: Write>(image: &Image, writer: &mut W, depth: BitDepth, encoding: Encoding) -> io::Result<()> {
+    let samples = image.pixels().iter().flat_map(|pixel| [pixel.x, pixel.y, pixel.z]);
+    write_netpbm(image, writer, depth, encoding, 3, samples)
+}
+
+/// Writes the luminance of `image` to a netpbm greymap, see [`write_pgm`].
+pub fn save_pgm<P: AsRef<Path>>(image: &Image, path: P, depth: BitDepth, encoding: Encoding) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_pgm(image, &mut writer, depth, encoding)?;
+    writer.flush()
+}
+
+/// Greymap of the luminance, P5 or P2 depending on `encoding`.
+pub fn write_pgm<W: Write>(image: &Image, writer: &mut W, depth: BitDepth, encoding: Encoding) -> io::Result<()> {
+    let space = image.color_space();
+    let samples = image.pixels().iter().map(|&pixel| space.luminance(pixel));
+    write_netpbm(image, writer, depth, encoding, 1, samples)
+}
+
+fn write_netpbm<W: Write>(
+    image: &Image,
+    writer: &mut W,
+    depth: BitDepth,
+    encoding: Encoding,
+    channels: usize,
+    samples: impl Iterator<Item = f64>,
+) -> io::Result<()> {
+    let magic = match (channels, encoding) {
+        (1, Encoding::Plain) => "P2",
+        (1, Encoding::Binary) => "P5",
+        (_, Encoding::Plain) => "P3",
+        (_, Encoding::Binary) => "P6",
+    };
+    let max_value = depth.max_value();
+    writeln!(writer, "{}\n{} {}\n{}", magic, image.width(), image.height(), max_value)?;
+
+    let space = image.color_space();
+    let quantize = |linear: f64| depth.quantize(space.encode(linear));
+
+    match encoding {
+        Encoding::Binary => {
+            let mut data = Vec::with_capacity(channels * image.pixels().len() * if depth == BitDepth::Sixteen { 2 } else { 1 });
+            for sample in samples {
+                let value = quantize(sample);
+                match depth {
+                    BitDepth::Eight => data.push(value as u8),
+                    BitDepth::Sixteen => data.extend(value.to_be_bytes()),
+                }
+            }
+            writer.write_all(&data)
+        }
+        Encoding::Plain => {
+            // One pixel per line
+            let mut line = Vec::with_capacity(channels);
+            for sample in samples {
+                line.push(quantize(sample).to_string());
+                if line.len() == channels {
+                    writeln!(writer, "{}", line.join(" "))?;
+                    line.clear();
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+// Whitespace separated header tokens, skipping `#` comments
+struct Header<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    line: usize,
+}
+
+impl Header<'_> {
+    fn token(&mut self) -> Result<(usize, String), ParseError> {
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'\n') => {
+                    self.line += 1;
+                    self.pos += 1;
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                Some(b'#') => {
+                    while self.bytes.get(self.pos).is_some_and(|&b| b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(_) => break,
+                None => return Err(ParseError::syntax(self.line, "unexpected end of file")),
+            }
+        }
+
+        let start = self.pos;
+        while self.bytes.get(self.pos).is_some_and(|b| !b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        Ok((self.line, String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned()))
+    }
+
+    fn number_with_line(&mut self) -> Result<(usize, usize), ParseError> {
+        let (line, token) = self.token()?;
+        let value = token
+            .parse()
+            .map_err(|_| ParseError::syntax(line, format!("invalid number '{}'", token)))?;
+        Ok((line, value))
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        Ok(self.number_with_line()?.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_ppm() {
+        let image = parse_ppm(b"P3\n# tiny\n2 1\n255\n255 0 0\n0 0 255\n").unwrap();
+        assert_eq!((image.width(), image.height()), (2, 1));
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(image.pixel(1, 0), Color::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_parse_binary_ppm_is_linearized() {
+        let mut bytes = b"P6 1 1 4\n".to_vec();
+        bytes.extend([2, 4, 0]);
+
+        let image = parse_ppm(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(srgb_to_linear(0.5), 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_parse_16_bit_ppm() {
+        let mut bytes = b"P6\n1 1\n65535\n".to_vec();
+        bytes.extend([0xff, 0xff, 0, 0, 0xff, 0xff]);
+
+        let image = parse_ppm(&bytes).unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 0.0, 1.0));
+    }
+
+    fn gradient() -> Image {
+        let pixels = (0..12).map(|i| Color::new(i as f64 / 11.0, 1.0 - i as f64 / 11.0, 0.25)).collect();
+        Image::from_pixels(4, 3, pixels)
+    }
+
+    fn assert_close(a: &Image, b: &Image, tolerance: f64) {
+        assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+        for (p, q) in a.pixels().iter().zip(b.pixels()) {
+            assert!((*p - *q).length() < tolerance, "{:?} vs {:?}", p, q);
+        }
+    }
+
+    #[test]
+    fn test_write_plain_ppm() {
+        let image = Image::from_pixels(2, 1, vec![Color::new(1.0, 0.25, 0.0), Color::new(0.0, 0.0, 0.5)]);
+        let mut bytes = Vec::new();
+        write_ppm(&image, &mut bytes, BitDepth::Eight, Encoding::Plain).unwrap();
+
+        assert_eq!(bytes, b"P3\n2 1\n255\n255 137 0\n0 0 188\n");
+    }
+
+    #[test]
+    fn test_samples_match_png() {
+        let image = gradient();
+        for depth in [BitDepth::Eight, BitDepth::Sixteen] {
+            let mut ppm = Vec::new();
+            write_ppm(&image, &mut ppm, depth, Encoding::Binary).unwrap();
+            let mut png = Vec::new();
+            crate::png_io::write_png(&image, &mut png, depth).unwrap();
+
+            let mut reader = png::Decoder::new(std::io::Cursor::new(&png)).read_info().unwrap();
+            let mut samples = vec![0; reader.output_buffer_size().unwrap()];
+            reader.next_frame(&mut samples).unwrap();
+            assert!(ppm.ends_with(&samples), "{:?}", depth);
+        }
+    }
+
+    #[test]
+    fn test_write_ppm_round_trip() {
+        let image = gradient();
+        for (depth, tolerance) in [(BitDepth::Eight, 0.01), (BitDepth::Sixteen, 1e-4)] {
+            for encoding in [Encoding::Plain, Encoding::Binary] {
+                let mut bytes = Vec::new();
+                write_ppm(&image, &mut bytes, depth, encoding).unwrap();
+                assert_close(&parse_ppm(&bytes).unwrap(), &image, tolerance);
+            }
+        }
+
+        let mut bytes = Vec::new();
+        write_ppm(&image, &mut bytes, BitDepth::Sixteen, Encoding::Binary).unwrap();
+        let header = b"P6\n4 3\n65535\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes.len(), header.len() + 12 * 3 * 2);
+    }
+
+    #[test]
+    fn test_write_pgm_round_trip() {
+        let image = Image::from_pixels(2, 1, vec![Color::new(0.5, 0.5, 0.5), Color::new(1.0, 0.0, 0.0)]);
+        for encoding in [Encoding::Plain, Encoding::Binary] {
+            let mut bytes = Vec::new();
+            write_pgm(&image, &mut bytes, BitDepth::Sixteen, encoding).unwrap();
+            assert!(bytes.starts_with(if encoding == Encoding::Plain { b"P2" } else { b"P5" }));
+
+            let read = parse_ppm(&bytes).unwrap();
+            assert!((read.pixel(0, 0) - Color::from_scalar(0.5)).length() < 1e-4);
+            assert!((read.pixel(1, 0) - Color::from_scalar(0.2126)).length() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_parse_pgm() {
+        let image = parse_ppm(b"P2\n2 1\n4\n4 2\n").unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(image.pixel(1, 0), Color::from_scalar(srgb_to_linear(0.5)));
+
+        let image = parse_ppm(b"P5 1 1 255\n\xff").unwrap();
+        assert_eq!(image.pixel(0, 0), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = parse_ppm(b"P4\n1 1\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 1, .. }));
+
+        let err = parse_ppm(b"P3\n1 1\n255\n0 300 0\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 4, .. }));
+
+        let err = parse_ppm(b"P6\n2 1\n255\n\x01\x02\x03").unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_huge_sizes_fail_cleanly() {
+        // Overflowing sizes are reported on the size line
+        let err = parse_ppm(b"P6\n18446744073709551615 2\n255\n\x01").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 2, .. }));
+        let err = parse_ppm(b"P5\n4294967296\n4294967295 65535\n\x01").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 3, .. }));
+
+        // Sizes that fit are checked against the data, without allocating
+        // for the header's claim
+        let err = parse_ppm(b"P6\n100000 100000\n255\n\x01\x02\x03").unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 1, .. }));
+        let err = parse_ppm(b"P3\n100000 100000\n255\n1 2 3\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { .. }));
+    }
+}
@@ -0,0 +1,221 @@
+//! Parallelograms and boxes made of them.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+#[derive(Clone)]
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    mat: Arc<dyn Material>,
+    bbox: Aabb,
+    normal: Vec3,
+    d: f64
+}
+
+impl Quad {
+    /// Quad with corners `q`, `q + u`, `q + v` and `q + u + v`. The front
+    /// face is the side `u × v` points to.
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: Arc<dyn Material>) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+
+        // Bounding box of all four vertices
+        let bbox_diagonal1 = Aabb::from_points(q, q + u + v);
+        let bbox_diagonal2 = Aabb::from_points(q + u, q + v);
+
+        Self {
+            q,
+            u,
+            v,
+            w: n / n.dot(n),
+            mat: material,
+            bbox: Aabb::surrounding(&bbox_diagonal1, &bbox_diagonal2),
+            normal,
+            d: normal.dot(q),
+        }
+    }
+
+    // Given the hit point in plane coordinates, is it inside the quad?
+    fn is_interior(a: f64, b: f64) -> bool {
+        let unit_interval = Interval::new(0.0, 1.0);
+        unit_interval.contains(a) && unit_interval.contains(b)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let root = (self.d - self.normal.dot(r.origin())) / denom;
+        if !t.surrounds(root) {
+            return false;
+        }
+
+        // Express the hit point in the quad's (u, v) plane coordinates
+        let intersection = r.at(root);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = self.w.dot(planar_hitpt_vector.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hitpt_vector));
+
+        if !Quad::is_interior(alpha, beta) {
+            return false;
+        }
+
+        rec.set_t(root);
+        rec.set_p(intersection);
+        rec.set_face_normal(r, self.normal);
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = self.mat.clone();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+/// Returns the 3D box (six sides) that contains the two opposite vertices `a`
+/// and `b`.
+pub fn make_box(a: Point3, b: Point3, material: Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::new();
+
+    let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+    let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+    // front, right, back, left, top, bottom
+    sides.add(Box::new(Quad::new(Point3::new(min.x, min.y, max.z), dx, dy, material.clone())));
+    sides.add(Box::new(Quad::new(Point3::new(max.x, min.y, max.z), -dz, dy, material.clone())));
+    sides.add(Box::new(Quad::new(Point3::new(max.x, min.y, min.z), -dx, dy, material.clone())));
+    sides.add(Box::new(Quad::new(Point3::new(min.x, min.y, min.z), dz, dy, material.clone())));
+    sides.add(Box::new(Quad::new(Point3::new(min.x, max.y, max.z), dx, -dz, material.clone())));
+    sides.add(Box::new(Quad::new(Point3::new(min.x, min.y, min.z), dx, dz, material)));
+
+    sides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::material::Lambertian;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)))
+    }
+
+    fn unit_quad() -> Quad {
+        Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material(),
+        )
+    }
+
+    #[test]
+    fn test_ray_hits_quad() {
+        let quad = unit_quad();
+        let ray = Ray::new(Point3::new(0.5, 0.25, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(quad.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 3.0);
+        assert_eq!(rec.p(), Point3::new(0.5, 0.25, 0.0));
+        assert_eq!(rec.normal(), Vec3::new(0.0, 0.0, 1.0));
+        assert!(rec.front_face());
+        assert!((rec.u - 0.25).abs() < EPSILON);
+        assert!((rec.v - 0.25).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_ray_misses_quad() {
+        let quad = unit_quad();
+        let beside = Ray::new(Point3::new(2.5, 0.5, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let parallel = Ray::new(Point3::new(0.5, 0.5, 3.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(!quad.hit(&beside, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!(!quad.hit(&parallel, &Interval::new(0.0, INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_arbitrary_quad() {
+        // Tilted parallelogram, hit from below
+        let quad = Quad::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            material(),
+        );
+        let ray = Ray::new(Point3::new(0.5, 0.5, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(quad.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!((rec.t() - 2.5).abs() < EPSILON);
+        assert!((rec.u - 0.5).abs() < EPSILON);
+        assert!((rec.v - 0.5).abs() < EPSILON);
+        assert!(!rec.front_face());
+    }
+
+    #[test]
+    fn test_quad_bounding_box_is_padded() {
+        let bbox = unit_quad().bounding_box();
+        assert_eq!(bbox.x(), Interval::new(0.0, 2.0));
+        assert_eq!(bbox.y(), Interval::new(0.0, 1.0));
+        assert!(bbox.z().size() > 0.0);
+    }
+
+    #[test]
+    fn test_make_box() {
+        let sides = make_box(Point3::new(1.0, 1.0, 1.0), Point3::new(-1.0, -1.0, -1.0), material());
+        let bbox = sides.bounding_box();
+        // Flat sides are padded slightly, so allow for that
+        for axis in [bbox.x(), bbox.y(), bbox.z()] {
+            assert!((axis.min() + 1.0).abs() < 1e-3);
+            assert!((axis.max() - 1.0).abs() < 1e-3);
+        }
+
+        // Every face points outwards, so rays from outside always hit a front face
+        let directions = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        for direction in directions {
+            let ray = Ray::new(-5.0 * direction + Vec3::new(0.1, 0.2, 0.3) * 0.5, direction);
+            let mut rec = HitRecord::default();
+
+            assert!(sides.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+            assert!((rec.t() - 4.0).abs() < 0.5);
+            assert!(rec.front_face());
+            assert!((rec.normal() + direction).near_zero());
+        }
+    }
+}
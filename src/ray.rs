@@ -1,24 +1,44 @@
+//! Rays: a half line with the time it was fired at.
+
 use crate::vec3::Vec3;
 
+/// Half line `origin + t * direction`, fired at `time` inside the camera
+/// shutter interval.
 #[derive(Debug,Copy,Clone)]
 pub struct Ray{
     origin:Vec3,
-    direction:Vec3
+    direction:Vec3,
+    time:f64
 }
 
 impl Ray {
+    /// Ray fired at time 0.
     pub fn new(origin:Vec3,direction:Vec3) -> Self{
-        Self{origin,direction}
+        Self::with_time(origin, direction, 0.0)
+    }
+
+    /// Rays carry the instant they were fired at inside the camera shutter, so
+    /// moving objects can be intersected where they were at that time.
+    pub fn with_time(origin:Vec3,direction:Vec3,time:f64) -> Self{
+        Self{origin,direction,time}
     }
 
+    /// Starting point.
     pub fn origin(&self) -> Vec3{
         self.origin
     }
 
+    /// Direction, not necessarily of unit length.
     pub fn direction(&self) -> Vec3{
         self.direction
     }
 
+    /// Instant the ray was fired at.
+    pub fn time(&self) -> f64{
+        self.time
+    }
+
+    /// Point at parameter `t` along the ray.
     pub fn at(self,t:f64) -> Vec3{
         self.origin + t*self.direction
     }
@@ -28,7 +48,8 @@ impl Default for Ray{
     fn default() -> Self {
         Self {
             origin: Vec3::default(),
-            direction: Vec3::default()
+            direction: Vec3::default(),
+            time: 0.0
         }
     }
 }
@@ -45,7 +66,16 @@ mod tests {
         let ray = Ray::new(origin,direction);
 
         assert_eq!(ray.origin(),origin);
-        assert_eq!(ray.direction(),direction)
+        assert_eq!(ray.direction(),direction);
+        assert_eq!(ray.time(),0.0)
+    }
+
+    #[test]
+    fn test_ray_with_time(){
+        let ray = Ray::with_time(Vec3::new(1.0,2.0,3.0),Vec3::new(0.0,1.0,0.0),0.25);
+
+        assert_eq!(ray.time(),0.25);
+        assert_eq!(ray.at(1.0).y,3.0);
     }
 
     #[test]
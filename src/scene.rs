@@ -0,0 +1,928 @@
+//! Scenes described in TOML files.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::background::{Background, BackgroundImage};
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::constant_medium::ConstantMedium;
+use crate::density_grid::DensityGrid;
+use crate::environment::EnvironmentMap;
+use crate::grid_medium::GridMedium;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::image::load_image;
+use crate::instance::{AnimatedInstance, Instance, Keyframe};
+use crate::material::{Dielectric, DiffuseLight, HenyeyGreenstein, Isotropic, Lambertian, Material, Metal};
+use crate::obj::load_obj;
+use crate::parse_error::ParseError;
+use crate::ply::load_ply;
+use crate::quad::{make_box, Quad};
+use crate::sphere::Sphere;
+use crate::stl::load_stl;
+use crate::texture::{CheckerTexture, Filter, ImageTexture, MarbleTexture, NoiseTexture, SolidColor, Texture, WoodTexture, WrapMode};
+use crate::transform::Transform;
+use crate::vec3::Vec3;
+
+/// Everything needed to render a scene file.
+pub struct Scene {
+    /// Every object of the file, ready to be wrapped in a BVH.
+    pub world: HittableList,
+    /// Camera from the `[camera]` table, with the background set.
+    pub camera: Camera,
+}
+
+/// Reads a TOML scene description. Paths inside it (meshes, images, voxel
+/// grids) are relative to the scene file.
+pub fn load_scene<P: AsRef<Path>>(path: P) -> Result<Scene, ParseError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    parse_scene(&text, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// Like [`load_scene`] for text already in memory, resolving paths against
+/// `base`.
+pub fn parse_scene(text: &str, base: &Path) -> Result<Scene, ParseError> {
+    let desc: SceneDesc = toml::from_str(text).map_err(|err| {
+        let line = err.span().map_or(1, |span| line_at(text, span.start));
+        ParseError::syntax(line, err.message())
+    })?;
+
+    let mut builder = SceneBuilder { text, base, textures: BTreeMap::new(), materials: BTreeMap::new() };
+
+    for (name, texture) in &desc.textures {
+        let built = builder.texture(name, texture)?;
+        builder.textures.insert(name.clone(), built);
+    }
+    for (name, material) in &desc.materials {
+        let built = builder.material(name, material)?;
+        builder.materials.insert(name.clone(), built);
+    }
+
+    let mut world = HittableList::new();
+    for (index, object) in desc.objects.iter().enumerate() {
+        world.add(builder.object(index, object)?);
+    }
+
+    let mut camera = desc.camera.build(&builder)?;
+    if let Some(background) = &desc.background {
+        camera.background = builder.background(background)?;
+    }
+
+    Ok(Scene { world, camera })
+}
+
+// 1-based line holding the byte at `offset`
+fn line_at(text: &str, offset: usize) -> usize {
+    text[..offset.min(text.len())].matches('\n').count() + 1
+}
+
+fn to_vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    camera: CameraDesc,
+    background: Option<BackgroundDesc>,
+    #[serde(default)]
+    textures: BTreeMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: BTreeMap<String, MaterialDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+}
+
+// Same parameters as Camera::new, plus the shutter interval
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: Spanned<f64>,
+    image_width: Spanned<i32>,
+    samples_per_pixel: Spanned<i32>,
+    max_depth: Spanned<i32>,
+    vfov: Spanned<f64>,
+    lookfrom: Spanned<[f64; 3]>,
+    lookat: Spanned<[f64; 3]>,
+    vup: Spanned<[f64; 3]>,
+    defocus_angle: f64,
+    focus_dist: Spanned<f64>,
+    shutter: Spanned<[f64; 2]>,
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        // Defaults are never reported, so they need no real span
+        Self {
+            aspect_ratio: Spanned::new(0..0, 16.0 / 9.0),
+            image_width: Spanned::new(0..0, 400),
+            samples_per_pixel: Spanned::new(0..0, 100),
+            max_depth: Spanned::new(0..0, 50),
+            vfov: Spanned::new(0..0, 90.0),
+            lookfrom: Spanned::new(0..0, [0.0, 0.0, 0.0]),
+            lookat: Spanned::new(0..0, [0.0, 0.0, -1.0]),
+            vup: Spanned::new(0..0, [0.0, 1.0, 0.0]),
+            defocus_angle: 0.0,
+            focus_dist: Spanned::new(0..0, 10.0),
+            shutter: Spanned::new(0..0, [0.0, 1.0]),
+        }
+    }
+}
+
+impl CameraDesc {
+    fn build(&self, builder: &SceneBuilder) -> Result<Camera, ParseError> {
+        let error = |span: std::ops::Range<usize>, message: &str| {
+            Err(ParseError::syntax(builder.line(span), format!("camera: {}", message)))
+        };
+        if *self.image_width.get_ref() <= 0 {
+            return error(self.image_width.span(), "`image_width` must be positive");
+        }
+        if *self.samples_per_pixel.get_ref() <= 0 {
+            return error(self.samples_per_pixel.span(), "`samples_per_pixel` must be positive");
+        }
+        if *self.max_depth.get_ref() <= 0 {
+            return error(self.max_depth.span(), "`max_depth` must be positive");
+        }
+        if *self.aspect_ratio.get_ref() <= 0.0 {
+            return error(self.aspect_ratio.span(), "`aspect_ratio` must be positive");
+        }
+        if *self.focus_dist.get_ref() <= 0.0 {
+            return error(self.focus_dist.span(), "`focus_dist` must be positive");
+        }
+        if self.lookfrom.get_ref() == self.lookat.get_ref() {
+            // Point at whichever of the two was written last
+            let span = if self.lookat.span().start >= self.lookfrom.span().start { self.lookat.span() } else { self.lookfrom.span() };
+            return error(span, "`lookfrom` and `lookat` are the same point");
+        }
+        let vfov = *self.vfov.get_ref();
+        if !(vfov > 0.0 && vfov < 180.0) {
+            return error(self.vfov.span(), "`vfov` must be between 0 and 180 degrees");
+        }
+        let vup = to_vec3(*self.vup.get_ref());
+        if vup.near_zero() {
+            return error(self.vup.span(), "`vup` must not be zero");
+        }
+        let view = to_vec3(*self.lookfrom.get_ref()) - to_vec3(*self.lookat.get_ref());
+        if vup.unit_vector().cross(view.unit_vector()).near_zero() {
+            return error(self.vup.span(), "`vup` is parallel to the view direction");
+        }
+        // Sphere motion runs from time 0 to 1, see Sphere::moving
+        let [open, close] = *self.shutter.get_ref();
+        if !(0.0..=1.0).contains(&open) || !(0.0..=1.0).contains(&close) {
+            return error(self.shutter.span(), "`shutter` must lie within [0, 1]");
+        }
+        if open > close {
+            return error(self.shutter.span(), "`shutter` opens after it closes");
+        }
+
+        let mut camera = Camera::new(
+            *self.aspect_ratio.get_ref(),
+            *self.image_width.get_ref(),
+            *self.samples_per_pixel.get_ref(),
+            *self.max_depth.get_ref(),
+            vfov,
+            to_vec3(*self.lookfrom.get_ref()),
+            to_vec3(*self.lookat.get_ref()),
+            vup,
+            self.defocus_angle,
+            *self.focus_dist.get_ref(),
+        );
+        camera.shutter_open = open;
+        camera.shutter_close = close;
+        Ok(camera)
+    }
+}
+
+// The `type` key picks what a table describes. Every kind takes its own
+// subset of the optional keys; anything else is reported rather than ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackgroundDesc {
+    #[serde(rename = "type")]
+    kind: Spanned<String>,
+    color: Option<[f64; 3]>,
+    bottom: Option<[f64; 3]>,
+    top: Option<[f64; 3]>,
+    path: Option<String>,
+    rotation: Option<f64>,
+    intensity: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDesc {
+    #[serde(rename = "type")]
+    kind: Spanned<String>,
+    color: Option<[f64; 3]>,
+    scale: Option<f64>,
+    even: Option<[f64; 3]>,
+    odd: Option<[f64; 3]>,
+    path: Option<String>,
+    filter: Option<String>,
+    wrap: Option<String>,
+    seed: Option<u64>,
+    turbulence: Option<u32>,
+    light: Option<[f64; 3]>,
+    dark: Option<[f64; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDesc {
+    #[serde(rename = "type")]
+    kind: Spanned<String>,
+    albedo: Option<[f64; 3]>,
+    texture: Option<Spanned<String>>,
+    fuzz: Option<f64>,
+    ior: Option<f64>,
+    emit: Option<[f64; 3]>,
+    g: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ObjectDesc {
+    #[serde(rename = "type")]
+    kind: Spanned<String>,
+    material: Option<Spanned<String>>,
+    center: Option<[f64; 3]>,
+    center1: Option<[f64; 3]>,
+    radius: Option<f64>,
+    q: Option<[f64; 3]>,
+    u: Option<[f64; 3]>,
+    v: Option<[f64; 3]>,
+    min: Option<[f64; 3]>,
+    max: Option<[f64; 3]>,
+    path: Option<Spanned<String>>,
+    density: Option<f64>,
+    seed: Option<u64>,
+    resolution: Option<[usize; 3]>,
+    translate: Option<[f64; 3]>,
+    rotate: Option<[f64; 3]>,
+    scale: Option<Spanned<[f64; 3]>>,
+    keyframes: Option<Vec<KeyframeDesc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyframeDesc {
+    time: f64,
+    translate: Option<[f64; 3]>,
+    rotate: Option<[f64; 3]>,
+    scale: Option<Spanned<[f64; 3]>>,
+}
+
+impl KeyframeDesc {
+    fn build(&self, builder: &SceneBuilder, fields: &Fields) -> Result<Keyframe, ParseError> {
+        builder.pose(fields, Keyframe::new(self.time), self.translate, self.rotate, &self.scale)
+    }
+}
+
+// Checks the keys given for one table against what its kind accepts
+struct Fields {
+    what: String,
+    line: usize,
+    present: Vec<(&'static str, bool)>,
+}
+
+impl Fields {
+    fn allow(&self, allowed: &[&str]) -> Result<(), ParseError> {
+        match self.present.iter().find(|(name, set)| *set && !allowed.contains(name)) {
+            Some((name, _)) => Err(ParseError::syntax(self.line, format!("{} does not take `{}`", self.what, name))),
+            None => Ok(()),
+        }
+    }
+
+    fn required<T: Clone>(&self, name: &str, value: &Option<T>) -> Result<T, ParseError> {
+        value.clone().ok_or_else(|| ParseError::syntax(self.line, format!("{} is missing `{}`", self.what, name)))
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> ParseError {
+        ParseError::syntax(self.line, format!("{}: {}", self.what, message))
+    }
+}
+
+struct SceneBuilder<'a> {
+    text: &'a str,
+    base: &'a Path,
+    textures: BTreeMap<String, Arc<dyn Texture>>,
+    materials: BTreeMap<String, Arc<dyn Material>>,
+}
+
+impl SceneBuilder<'_> {
+    fn line(&self, span: std::ops::Range<usize>) -> usize {
+        line_at(self.text, span.start)
+    }
+
+    fn fields(&self, what: String, kind: &Spanned<String>, present: Vec<(&'static str, bool)>) -> Fields {
+        Fields { what, line: self.line(kind.span()), present }
+    }
+
+    fn pose(
+        &self,
+        fields: &Fields,
+        keyframe: Keyframe,
+        translate: Option<[f64; 3]>,
+        rotate: Option<[f64; 3]>,
+        scale: &Option<Spanned<[f64; 3]>>,
+    ) -> Result<Keyframe, ParseError> {
+        let mut keyframe = keyframe;
+        if let Some(scale) = scale {
+            let factors = to_vec3(*scale.get_ref());
+            if Transform::scale(factors).is_none() {
+                let message = format!("{}: `scale` components must be non-zero and finite", fields.what);
+                return Err(ParseError::syntax(self.line(scale.span()), message));
+            }
+            keyframe = keyframe.with_scale(factors);
+        }
+        if let Some(rotate) = rotate {
+            keyframe = keyframe.with_rotation(to_vec3(rotate));
+        }
+        if let Some(translate) = translate {
+            keyframe = keyframe.with_translation(to_vec3(translate));
+        }
+        Ok(keyframe)
+    }
+
+    fn path(&self, path: &str) -> PathBuf {
+        self.base.join(path)
+    }
+
+    fn background(&self, desc: &BackgroundDesc) -> Result<Background, ParseError> {
+        let kind = desc.kind.get_ref().as_str();
+        let fields = self.fields(
+            format!("{} background", kind),
+            &desc.kind,
+            vec![
+                ("color", desc.color.is_some()),
+                ("bottom", desc.bottom.is_some()),
+                ("top", desc.top.is_some()),
+                ("path", desc.path.is_some()),
+                ("rotation", desc.rotation.is_some()),
+                ("intensity", desc.intensity.is_some()),
+            ],
+        );
+
+        match kind {
+            "sky" => {
+                fields.allow(&[])?;
+                Ok(Background::sky())
+            }
+            "black" => {
+                fields.allow(&[])?;
+                Ok(Background::Black)
+            }
+            "solid" => {
+                fields.allow(&["color"])?;
+                Ok(Background::Solid(to_vec3(fields.required("color", &desc.color)?)))
+            }
+            "gradient" => {
+                fields.allow(&["bottom", "top"])?;
+                Ok(Background::Gradient {
+                    bottom: to_vec3(fields.required("bottom", &desc.bottom)?),
+                    top: to_vec3(fields.required("top", &desc.top)?),
+                })
+            }
+            "image" => {
+                fields.allow(&["path"])?;
+                let path = fields.required("path", &desc.path)?;
+                let image = load_image(self.path(&path)).map_err(|err| fields.error(format!("{}: {}", path, err)))?;
+                Ok(Background::Image(Arc::new(BackgroundImage::new(image.width(), image.height(), image.pixels().to_vec()))))
+            }
+            "environment" => {
+                fields.allow(&["path", "rotation", "intensity"])?;
+                let path = fields.required("path", &desc.path)?;
+                let map = EnvironmentMap::load(self.path(&path)).map_err(|err| fields.error(format!("{}: {}", path, err)))?;
+                Ok(Background::Environment(Arc::new(
+                    map.with_rotation(desc.rotation.unwrap_or(0.0)).with_intensity(desc.intensity.unwrap_or(1.0)),
+                )))
+            }
+            _ => Err(fields.error("unknown background type, expected sky, black, solid, gradient, image or environment")),
+        }
+    }
+
+    fn texture(&self, name: &str, desc: &TextureDesc) -> Result<Arc<dyn Texture>, ParseError> {
+        let kind = desc.kind.get_ref().as_str();
+        let fields = self.fields(
+            format!("texture '{}'", name),
+            &desc.kind,
+            vec![
+                ("color", desc.color.is_some()),
+                ("scale", desc.scale.is_some()),
+                ("even", desc.even.is_some()),
+                ("odd", desc.odd.is_some()),
+                ("path", desc.path.is_some()),
+                ("filter", desc.filter.is_some()),
+                ("wrap", desc.wrap.is_some()),
+                ("seed", desc.seed.is_some()),
+                ("turbulence", desc.turbulence.is_some()),
+                ("light", desc.light.is_some()),
+                ("dark", desc.dark.is_some()),
+            ],
+        );
+
+        let texture: Arc<dyn Texture> = match kind {
+            "solid" => {
+                fields.allow(&["color"])?;
+                Arc::new(SolidColor::new(to_vec3(fields.required("color", &desc.color)?)))
+            }
+            "checker" => {
+                fields.allow(&["scale", "even", "odd"])?;
+                Arc::new(CheckerTexture::from_colors(
+                    fields.required("scale", &desc.scale)?,
+                    to_vec3(fields.required("even", &desc.even)?),
+                    to_vec3(fields.required("odd", &desc.odd)?),
+                ))
+            }
+            "image" => {
+                fields.allow(&["path", "filter", "wrap"])?;
+                let path = fields.required("path", &desc.path)?;
+                let mut texture = ImageTexture::load(self.path(&path)).map_err(|err| fields.error(format!("{}: {}", path, err)))?;
+                if let Some(filter) = &desc.filter {
+                    texture = texture.with_filter(match filter.as_str() {
+                        "nearest" => Filter::Nearest,
+                        "bilinear" => Filter::Bilinear,
+                        _ => return Err(fields.error(format!("unknown filter '{}', expected nearest or bilinear", filter))),
+                    });
+                }
+                if let Some(wrap) = &desc.wrap {
+                    texture = texture.with_wrap(match wrap.as_str() {
+                        "repeat" => WrapMode::Repeat,
+                        "clamp" => WrapMode::Clamp,
+                        "mirror" => WrapMode::Mirror,
+                        _ => return Err(fields.error(format!("unknown wrap mode '{}', expected repeat, clamp or mirror", wrap))),
+                    });
+                }
+                Arc::new(texture)
+            }
+            "noise" => {
+                fields.allow(&["seed", "scale", "turbulence"])?;
+                let seed = desc.seed.unwrap_or(0);
+                let scale = fields.required("scale", &desc.scale)?;
+                match desc.turbulence {
+                    Some(depth) => Arc::new(NoiseTexture::turbulent(seed, scale, depth)),
+                    None => Arc::new(NoiseTexture::new(seed, scale)),
+                }
+            }
+            "marble" => {
+                fields.allow(&["seed", "scale", "color"])?;
+                Arc::new(MarbleTexture::new(
+                    desc.seed.unwrap_or(0),
+                    fields.required("scale", &desc.scale)?,
+                    to_vec3(desc.color.unwrap_or([1.0, 1.0, 1.0])),
+                ))
+            }
+            "wood" => {
+                fields.allow(&["seed", "scale", "light", "dark"])?;
+                Arc::new(WoodTexture::new(
+                    desc.seed.unwrap_or(0),
+                    fields.required("scale", &desc.scale)?,
+                    to_vec3(fields.required("light", &desc.light)?),
+                    to_vec3(fields.required("dark", &desc.dark)?),
+                ))
+            }
+            _ => return Err(fields.error("unknown texture type, expected solid, checker, image, noise, marble or wood")),
+        };
+        Ok(texture)
+    }
+
+    // Albedo given either as a color or as the name of a texture
+    fn albedo(&self, fields: &Fields, desc: &MaterialDesc) -> Result<Arc<dyn Texture>, ParseError> {
+        match (&desc.albedo, &desc.texture) {
+            (Some(_), Some(_)) => Err(fields.error("give either `albedo` or `texture`, not both")),
+            (Some(color), None) => Ok(Arc::new(SolidColor::new(to_vec3(*color)))),
+            (None, Some(name)) => self.textures.get(name.get_ref()).cloned().ok_or_else(|| {
+                ParseError::syntax(self.line(name.span()), format!("{}: unknown texture '{}'", fields.what, name.get_ref()))
+            }),
+            (None, None) => Err(fields.error("needs an `albedo` or a `texture`")),
+        }
+    }
+
+    fn material(&self, name: &str, desc: &MaterialDesc) -> Result<Arc<dyn Material>, ParseError> {
+        let kind = desc.kind.get_ref().as_str();
+        let fields = self.fields(
+            format!("material '{}'", name),
+            &desc.kind,
+            vec![
+                ("albedo", desc.albedo.is_some()),
+                ("texture", desc.texture.is_some()),
+                ("fuzz", desc.fuzz.is_some()),
+                ("ior", desc.ior.is_some()),
+                ("emit", desc.emit.is_some()),
+                ("g", desc.g.is_some()),
+            ],
+        );
+
+        let material: Arc<dyn Material> = match kind {
+            "lambertian" => {
+                fields.allow(&["albedo", "texture"])?;
+                Arc::new(Lambertian::from_texture(self.albedo(&fields, desc)?))
+            }
+            "metal" => {
+                fields.allow(&["albedo", "texture", "fuzz"])?;
+                Arc::new(Metal::from_texture(self.albedo(&fields, desc)?, desc.fuzz.unwrap_or(0.0)))
+            }
+            "dielectric" => {
+                fields.allow(&["ior"])?;
+                Arc::new(Dielectric::new(fields.required("ior", &desc.ior)?))
+            }
+            "light" => {
+                fields.allow(&["emit", "texture"])?;
+                match (&desc.emit, &desc.texture) {
+                    (Some(emit), None) => Arc::new(DiffuseLight::new(to_vec3(*emit))),
+                    (None, Some(_)) => Arc::new(DiffuseLight::from_texture(self.albedo(&fields, desc)?)),
+                    _ => return Err(fields.error("needs either `emit` or `texture`")),
+                }
+            }
+            "isotropic" => {
+                fields.allow(&["albedo", "texture"])?;
+                Arc::new(Isotropic::from_texture(self.albedo(&fields, desc)?))
+            }
+            "henyey_greenstein" => {
+                fields.allow(&["albedo", "texture", "g"])?;
+                Arc::new(HenyeyGreenstein::from_texture(self.albedo(&fields, desc)?, fields.required("g", &desc.g)?))
+            }
+            _ => {
+                return Err(fields.error(
+                    "unknown material type, expected lambertian, metal, dielectric, light, isotropic or henyey_greenstein",
+                ))
+            }
+        };
+        Ok(material)
+    }
+
+    fn object(&self, index: usize, desc: &ObjectDesc) -> Result<Box<dyn Hittable>, ParseError> {
+        let kind = desc.kind.get_ref().as_str();
+        let fields = self.fields(
+            format!("objects[{}] ({})", index, kind),
+            &desc.kind,
+            vec![
+                ("material", desc.material.is_some()),
+                ("center", desc.center.is_some()),
+                ("center1", desc.center1.is_some()),
+                ("radius", desc.radius.is_some()),
+                ("q", desc.q.is_some()),
+                ("u", desc.u.is_some()),
+                ("v", desc.v.is_some()),
+                ("min", desc.min.is_some()),
+                ("max", desc.max.is_some()),
+                ("path", desc.path.is_some()),
+                ("density", desc.density.is_some()),
+                ("seed", desc.seed.is_some()),
+                ("resolution", desc.resolution.is_some()),
+                ("translate", desc.translate.is_some()),
+                ("rotate", desc.rotate.is_some()),
+                ("scale", desc.scale.is_some()),
+                ("keyframes", desc.keyframes.is_some()),
+            ],
+        );
+        const PLACEMENT: [&str; 4] = ["translate", "rotate", "scale", "keyframes"];
+        let allow = |own: &[&str]| fields.allow(&[own, &PLACEMENT[..]].concat());
+
+        let material = match &desc.material {
+            Some(name) => Some(self.materials.get(name.get_ref()).cloned().ok_or_else(|| {
+                ParseError::syntax(self.line(name.span()), format!("{}: unknown material '{}'", fields.what, name.get_ref()))
+            })?),
+            None => None,
+        };
+        let required_material = || material.clone().ok_or_else(|| fields.error("is missing `material`"));
+
+        let object: Box<dyn Hittable> = match kind {
+            "sphere" => {
+                allow(&["material", "center", "center1", "radius", "density"])?;
+                let center = to_vec3(fields.required("center", &desc.center)?);
+                let radius = fields.required("radius", &desc.radius)?;
+                if radius <= 0.0 {
+                    return Err(fields.error("`radius` must be positive"));
+                }
+                let material = required_material()?;
+                let sphere = match desc.center1 {
+                    Some(center1) => Sphere::moving(center, to_vec3(center1), radius, material.clone()),
+                    None => Sphere::new(center, radius, material.clone()),
+                };
+                self.fill(&fields, desc.density, Box::new(sphere), material)?
+            }
+            "quad" => {
+                allow(&["material", "q", "u", "v"])?;
+                Box::new(Quad::new(
+                    to_vec3(fields.required("q", &desc.q)?),
+                    to_vec3(fields.required("u", &desc.u)?),
+                    to_vec3(fields.required("v", &desc.v)?),
+                    required_material()?,
+                ))
+            }
+            "box" => {
+                allow(&["material", "min", "max", "density"])?;
+                let material = required_material()?;
+                let cube = make_box(
+                    to_vec3(fields.required("min", &desc.min)?),
+                    to_vec3(fields.required("max", &desc.max)?),
+                    material.clone(),
+                );
+                self.fill(&fields, desc.density, Box::new(cube), material)?
+            }
+            "mesh" => {
+                allow(&["material", "path"])?;
+                let path = fields.required("path", &desc.path)?;
+                let full_path = self.path(path.get_ref());
+                let extension = full_path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+                let mesh_error = |err: ParseError| {
+                    ParseError::syntax(self.line(path.span()), format!("{}: {}: {}", fields.what, path.get_ref(), err))
+                };
+
+                let mesh = match extension.as_deref() {
+                    // OBJ files bring their own materials, ours only fills in the gaps
+                    Some("obj") => {
+                        let default = material.clone().unwrap_or_else(|| Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+                        load_obj(&full_path, default).map_err(mesh_error)?
+                    }
+                    Some("ply") => load_ply(&full_path, required_material()?).map_err(mesh_error)?,
+                    Some("stl") => load_stl(&full_path, required_material()?).map_err(mesh_error)?,
+                    _ => return Err(fields.error(format!("'{}' is not an .obj, .ply or .stl file", path.get_ref()))),
+                };
+                Box::new(mesh)
+            }
+            "grid_volume" => {
+                allow(&["material", "min", "max", "density", "path", "seed", "resolution"])?;
+                let grid = match (&desc.path, desc.seed) {
+                    (Some(path), None) => {
+                        let [nx, ny, nz] = fields.required("resolution", &desc.resolution)?;
+                        DensityGrid::load_raw(self.path(path.get_ref()), nx, ny, nz).map_err(|err| {
+                            ParseError::syntax(self.line(path.span()), format!("{}: {}: {}", fields.what, path.get_ref(), err))
+                        })?
+                    }
+                    (None, Some(seed)) => {
+                        let [n, ny, nz] = desc.resolution.unwrap_or([64, 64, 64]);
+                        if n != ny || n != nz {
+                            return Err(fields.error("procedural clouds need a cubic `resolution`"));
+                        }
+                        DensityGrid::perlin_cloud(seed, n).map_err(|err| fields.error(err.to_string()))?
+                    }
+                    _ => return Err(fields.error("needs either a raw grid `path` or a cloud `seed`")),
+                };
+                Box::new(GridMedium::new(
+                    Arc::new(grid),
+                    to_vec3(fields.required("min", &desc.min)?),
+                    to_vec3(fields.required("max", &desc.max)?),
+                    fields.required("density", &desc.density)?,
+                    required_material()?,
+                ))
+            }
+            _ => return Err(fields.error("unknown object type, expected sphere, quad, box, mesh or grid_volume")),
+        };
+
+        self.place(&fields, desc, object)
+    }
+
+    // With a density the shape only bounds a fog, and its material becomes
+    // the phase function
+    fn fill(
+        &self,
+        fields: &Fields,
+        density: Option<f64>,
+        shape: Box<dyn Hittable>,
+        material: Arc<dyn Material>,
+    ) -> Result<Box<dyn Hittable>, ParseError> {
+        match density {
+            Some(density) if density <= 0.0 => Err(fields.error("`density` must be positive")),
+            Some(density) => Ok(Box::new(ConstantMedium::with_phase_function(Arc::from(shape), density, material))),
+            None => Ok(shape),
+        }
+    }
+
+    fn place(&self, fields: &Fields, desc: &ObjectDesc, object: Box<dyn Hittable>) -> Result<Box<dyn Hittable>, ParseError> {
+        let fixed = desc.translate.is_some() || desc.rotate.is_some() || desc.scale.is_some();
+        match &desc.keyframes {
+            Some(_) if fixed => Err(fields.error("use either `keyframes` or translate/rotate/scale")),
+            Some(keyframes) if keyframes.is_empty() => Err(fields.error("`keyframes` is empty")),
+            Some(keyframes) => {
+                let keyframes = keyframes.iter().map(|k| k.build(self, fields)).collect::<Result<_, _>>()?;
+                Ok(Box::new(AnimatedInstance::new(Arc::from(object), keyframes)))
+            }
+            None if fixed => {
+                let keyframe = self.pose(fields, Keyframe::new(0.0), desc.translate, desc.rotate, &desc.scale)?;
+                let transform = keyframe.transform().ok_or_else(|| fields.error("the pose has no inverse"))?;
+                Ok(Box::new(Instance::new(Arc::from(object), transform)))
+            }
+            None => Ok(object),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::hittable::HitRecord;
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+    use crate::vec3::Point3;
+
+    fn parse(text: &str) -> Result<Scene, ParseError> {
+        parse_scene(text, Path::new(""))
+    }
+
+    fn syntax_line(result: Result<Scene, ParseError>) -> (usize, String) {
+        match result {
+            Err(ParseError::Syntax { line, message }) => (line, message),
+            Err(err) => panic!("expected a syntax error, got {}", err),
+            Ok(_) => panic!("expected a syntax error"),
+        }
+    }
+
+    const SCENE: &str = r#"
+[camera]
+image_width = 200
+vfov = 20
+lookfrom = [13, 2, 3]
+lookat = [0, 0, 0]
+
+[background]
+type = "black"
+
+[textures.checker]
+type = "checker"
+scale = 0.5
+even = [0.2, 0.3, 0.1]
+odd = [0.9, 0.9, 0.9]
+
+[materials.ground]
+type = "lambertian"
+texture = "checker"
+
+[materials.lamp]
+type = "light"
+emit = [4, 4, 4]
+
+[[objects]]
+type = "sphere"
+center = [0, -1000, 0]
+radius = 1000
+material = "ground"
+
+[[objects]]
+type = "box"
+min = [0, 0, 0]
+max = [1, 1, 1]
+material = "lamp"
+rotate = [0, 45, 0]
+translate = [0, 2, 0]
+"#;
+
+    #[test]
+    fn test_parse_scene() {
+        let scene = parse(SCENE).unwrap();
+        assert_eq!(scene.camera.image_width, 200);
+        assert_eq!(scene.camera.vfov, 20.0);
+        assert_eq!(scene.camera.lookfrom, Point3::new(13.0, 2.0, 3.0));
+        assert_eq!(scene.camera.samples_per_pixel, 100);
+        assert!(matches!(scene.camera.background, Background::Black));
+
+        // The lamp box is turned so one of its edges faces +x
+        let ray = Ray::new(Point3::new(5.0, 2.5, 0.2), Vec3::new(-1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+        assert!(scene.world.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert!((rec.p().x - (2f64.sqrt() - 0.2)).abs() < 1e-6);
+        assert_eq!(rec.mat.emitted(0.0, 0.0, &rec.p()), Color::new(4.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn test_toml_errors_have_lines() {
+        let (line, message) = syntax_line(parse("[camera]\nimage_width = 200\nvfov = \"wide\"\n"));
+        assert_eq!(line, 3);
+        assert!(message.contains("invalid type"), "{}", message);
+
+        let (line, message) = syntax_line(parse("[camera]\nimage_widht = 200\n"));
+        assert_eq!(line, 2);
+        assert!(message.contains("image_widht"), "{}", message);
+    }
+
+    #[test]
+    fn test_errors_point_at_the_field() {
+        let scene = SCENE.replace("material = \"lamp\"", "material = \"lmap\"");
+        let (line, message) = syntax_line(parse(&scene));
+        assert_eq!(scene.lines().nth(line - 1).unwrap(), "material = \"lmap\"");
+        assert!(message.contains("objects[1] (box): unknown material 'lmap'"), "{}", message);
+
+        let scene = SCENE.replace("texture = \"checker\"", "texture = \"chekcer\"");
+        let (line, message) = syntax_line(parse(&scene));
+        assert_eq!(scene.lines().nth(line - 1).unwrap(), "texture = \"chekcer\"");
+        assert!(message.contains("unknown texture 'chekcer'"), "{}", message);
+
+        let scene = SCENE.replace("radius = 1000\n", "");
+        let (line, message) = syntax_line(parse(&scene));
+        assert_eq!(scene.lines().nth(line - 1).unwrap(), "type = \"sphere\"");
+        assert!(message.contains("objects[0] (sphere) is missing `radius`"), "{}", message);
+
+        let scene = SCENE.replace("radius = 1000\n", "radius = 1000\nq = [0, 0, 0]\n");
+        let (_, message) = syntax_line(parse(&scene));
+        assert!(message.contains("does not take `q`"), "{}", message);
+
+        let scene = SCENE.replace("type = \"black\"", "type = \"night\"");
+        let (line, message) = syntax_line(parse(&scene));
+        assert_eq!(scene.lines().nth(line - 1).unwrap(), "type = \"night\"");
+        assert!(message.contains("unknown background type"), "{}", message);
+    }
+
+    #[test]
+    fn test_camera_is_validated() {
+        for (from, to) in [
+            ("image_width = 200", "image_width = -5"),
+            ("image_width = 200", "image_width = 200\nsamples_per_pixel = 0"),
+            ("image_width = 200", "image_width = 200\nmax_depth = 0"),
+            ("image_width = 200", "image_width = 200\naspect_ratio = -1.5"),
+            ("image_width = 200", "image_width = 200\nfocus_dist = 0"),
+            ("lookat = [0, 0, 0]", "lookat = [13, 2, 3]"),
+            ("vfov = 20", "vfov = 0"),
+            ("vfov = 20", "vfov = 180"),
+            ("image_width = 200", "image_width = 200\nvup = [0, 0, 0]"),
+            ("image_width = 200", "image_width = 200\nvup = [-6.5, -1, -1.5]"),
+            ("image_width = 200", "image_width = 200\nshutter = [1, 0]"),
+            ("image_width = 200", "image_width = 200\nshutter = [0, 2]"),
+            ("image_width = 200", "image_width = 200\nshutter = [-0.5, 0.5]"),
+        ] {
+            let scene = SCENE.replace(from, to);
+            let (line, message) = syntax_line(parse(&scene));
+            let field = to.lines().last().unwrap();
+            assert_eq!(scene.lines().nth(line - 1).unwrap(), field, "{}", message);
+            assert!(message.starts_with("camera: "), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_scale_is_validated() {
+        let keyframes = "keyframes = [\n    { time = 0 },\n    { time = 1, scale = [2, 2, inf] },\n]";
+        for to in ["scale = [1, 0, 1]", "scale = [1, nan, 1]", keyframes] {
+            let scene = SCENE.replace("rotate = [0, 45, 0]\ntranslate = [0, 2, 0]", to);
+            let (line, message) = syntax_line(parse(&scene));
+            assert!(scene.lines().nth(line - 1).unwrap().contains("scale = ["), "{}", message);
+            assert!(message.contains("objects[1] (box): `scale`"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_volumes_and_animation() {
+        let scene = parse(
+            r#"
+[materials.fog]
+type = "isotropic"
+albedo = [1, 1, 1]
+
+[materials.white]
+type = "lambertian"
+albedo = [0.7, 0.7, 0.7]
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 1
+material = "fog"
+density = 0.5
+
+[[objects]]
+type = "sphere"
+center = [0, 0, 0]
+radius = 0.5
+material = "white"
+keyframes = [
+    { time = 0, translate = [5, 0, 0] },
+    { time = 1, translate = [5, 4, 0] },
+]
+"#,
+        )
+        .unwrap();
+        assert_eq!(scene.world.into_objects().len(), 2);
+
+        let (_, message) = syntax_line(parse(
+            "[materials.fog]\ntype = \"isotropic\"\nalbedo = [1, 1, 1]\n\n[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"fog\"\ndensity = -1\n",
+        ));
+        assert!(message.contains("`density` must be positive"), "{}", message);
+
+        let (_, message) = syntax_line(parse(
+            "[materials.fog]\ntype = \"isotropic\"\nalbedo = [1, 1, 1]\n\n[[objects]]\ntype = \"grid_volume\"\nseed = 1\nresolution = [4000000, 4000000, 4000000]\nmin = [0, 0, 0]\nmax = [1, 1, 1]\ndensity = 1\nmaterial = \"fog\"\n",
+        ));
+        assert!(message.contains("is too large"), "{}", message);
+    }
+
+    #[test]
+    fn test_bundled_scenes_parse() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        let mut count = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "toml") {
+                load_scene(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+}
@@ -1,32 +1,74 @@
+//! Spheres, still or moving.
+
 use std::sync::Arc;
 
+use crate::aabb::Aabb;
+use crate::commons::PI;
 use crate::interval::Interval;
 use crate::material::Material;
-use crate::vec3::Point3;
+use crate::vec3::{Point3, Vec3};
 use crate::hittable::{Hittable,HitRecord};
 use crate::ray::Ray;
 
 
+/// Sphere, optionally moving in a straight line while the shutter is open.
 #[derive(Clone)]
 pub struct Sphere {
     center:Point3,
+    // Distance travelled between time 0 and time 1
+    motion:Vec3,
     radius:f64,
-    mat: Arc<dyn Material>
+    mat: Arc<dyn Material>,
+    bbox: Aabb
 }
 
 impl Sphere {
+    /// Still sphere. A negative radius is treated as zero.
     pub fn new(center:Point3,radius:f64,material: Arc<dyn Material>) -> Self{
+        let radius = f64::max(0.0,radius);
+        let rvec = Vec3::from_scalar(radius);
+
         Self {
             center,
-            radius : f64::max(0.0,radius),
+            motion: Vec3::default(),
+            radius,
             mat:material,
+            bbox: Aabb::from_points(center - rvec, center + rvec),
         }
     }
+
+    /// Sphere moving in a straight line from `center0` at time 0 to `center1`
+    /// at time 1, blurred by the camera shutter. Outside [0, 1] it holds
+    /// still at the nearer end.
+    pub fn moving(center0:Point3,center1:Point3,radius:f64,material: Arc<dyn Material>) -> Self{
+        let mut sphere = Sphere::new(center0, radius, material);
+        let rvec = Vec3::from_scalar(sphere.radius);
+        let end_box = Aabb::from_points(center1 - rvec, center1 + rvec);
+
+        sphere.motion = center1 - center0;
+        sphere.bbox = Aabb::surrounding(&sphere.bbox, &end_box);
+        sphere
+    }
+
+    fn center_at(&self, time:f64) -> Point3 {
+        // Clamped so the box around the two ends covers every time
+        self.center + time.clamp(0.0, 1.0) * self.motion
+    }
+
+    // p is a point on the unit sphere. u goes around the y axis starting at
+    // -x, v from the bottom (-y) to the top (+y).
+    fn get_sphere_uv(p: Point3) -> (f64, f64) {
+        let theta = (-p.y).clamp(-1.0, 1.0).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
 }
 
 impl Hittable for Sphere {
     fn hit(&self,r:&Ray,t: &Interval,rec:&mut HitRecord) -> bool {
-        let oc = self.center - r.origin();
+        let center = self.center_at(r.time());
+        let oc = center - r.origin();
         let a = r.direction().length_squared();
         let h: f64 = r.direction().dot(oc);
         let c = oc.length_squared() - self.radius * self.radius;
@@ -50,21 +92,24 @@ impl Hittable for Sphere {
 
         rec.set_t(root);
         rec.set_p(r.at(rec.t()));
-        let outward_normal = ((rec.p() - self.center) / self.radius).unit_vector();
+        let outward_normal = ((rec.p() - center) / self.radius).unit_vector();
         rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = Sphere::get_sphere_uv(outward_normal);
         rec.mat = self.mat.clone();
 
         true
     }
 
-    
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{commons::INFINITY,vec3::Vec3};
+    use crate::commons::INFINITY;
     use crate::material::Metal;
 
     #[test]
@@ -175,4 +220,63 @@ mod tests {
         assert_eq!(rec.normal(), Vec3::new(0.0, 0.0, -1.0));
     }
 
+    #[test]
+    fn test_sphere_bounding_box() {
+        let material = Arc::new(Metal::new(Vec3::new(0.8, 0.8, 0.8),1.0));
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, material);
+        let bbox = sphere.bounding_box();
+
+        assert_eq!(bbox.x(), Interval::new(0.5, 1.5));
+        assert_eq!(bbox.y(), Interval::new(1.5, 2.5));
+        assert_eq!(bbox.z(), Interval::new(2.5, 3.5));
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let material = Arc::new(Metal::new(Vec3::new(0.8, 0.8, 0.8),1.0));
+        let sphere = Sphere::moving(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 2.0, 0.0), 0.5, material);
+
+        // Fired straight at y = 1, only the middle of the shutter finds it
+        let early = Ray::with_time(Point3::new(0.0, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+        let middle = Ray::with_time(Point3::new(0.0, 1.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.5);
+        let mut rec = HitRecord::default();
+
+        assert!(!sphere.hit(&early, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!(sphere.hit(&middle, &Interval::new(0.0, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 4.5);
+        assert_eq!(rec.normal(), Vec3::new(0.0, 0.0, -1.0));
+
+        // The box covers the whole path
+        assert_eq!(sphere.bounding_box().y(), Interval::new(-0.5, 2.5));
+
+        // Past the end of its path the sphere stays inside the box
+        let late = Ray::with_time(Point3::new(0.0, 2.0, -5.0), Vec3::new(0.0, 0.0, 1.0), 3.0);
+        assert!(sphere.hit(&late, &Interval::new(0.0, INFINITY), &mut rec));
+        assert_eq!(rec.p(), Point3::new(0.0, 2.0, -0.5));
+    }
+
+    #[test]
+    fn test_sphere_uv() {
+        let cases = [
+            (Point3::new(1.0, 0.0, 0.0), (0.5, 0.5)),
+            (Point3::new(0.0, 1.0, 0.0), (0.5, 1.0)),
+            (Point3::new(0.0, 0.0, 1.0), (0.25, 0.5)),
+            (Point3::new(-1.0, 0.0, 0.0), (0.0, 0.5)),
+            (Point3::new(0.0, -1.0, 0.0), (0.5, 0.0)),
+            (Point3::new(0.0, 0.0, -1.0), (0.75, 0.5)),
+        ];
+        for (p, (u, v)) in cases {
+            let (su, sv) = Sphere::get_sphere_uv(p);
+            assert!((su - u).abs() < 1e-9 && (sv - v).abs() < 1e-9, "{:?} -> ({}, {})", p, su, sv);
+        }
+
+        let material = Arc::new(Metal::new(Vec3::new(0.8, 0.8, 0.8),1.0));
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, material);
+        let ray = Ray::new(Point3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(sphere.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!((rec.v - 1.0).abs() < 1e-9);
+    }
+
 }
\ No newline at end of file
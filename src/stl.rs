@@ -0,0 +1,285 @@
+//! STL meshes, binary or ASCII.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::material::Material;
+use crate::parse_error::ParseError;
+use crate::triangle_mesh::{MeshData, MeshFace, TriangleMesh};
+use crate::vec3::Point3;
+
+const BINARY_HEADER_SIZE: usize = 80;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+/// Triangles read from an STL file. STL stores every triangle with its own
+/// copy of the corners, so identical positions are welded into shared vertices.
+#[derive(Debug, Default)]
+pub struct StlData {
+    /// Welded vertex positions.
+    pub positions: Vec<Point3>,
+    /// Vertex indices of each triangle.
+    pub faces: Vec<[usize; 3]>,
+    welded: HashMap<[u64; 3], usize>,
+}
+
+impl StlData {
+    fn add_triangle(&mut self, corners: [Point3; 3]) {
+        let face = corners.map(|p| self.vertex_index(p));
+        self.faces.push(face);
+    }
+
+    fn vertex_index(&mut self, p: Point3) -> usize {
+        // Adding 0.0 turns -0.0 into 0.0 so both weld together
+        let key = [p.x + 0.0, p.y + 0.0, p.z + 0.0].map(f64::to_bits);
+        let next = self.positions.len();
+        let index = *self.welded.entry(key).or_insert(next);
+        if index == next {
+            self.positions.push(p);
+        }
+        index
+    }
+
+    /// Builds a mesh using `material` for every face. STL normals are not kept,
+    /// so the mesh is flat shaded.
+    pub fn into_mesh(self, material: Arc<dyn Material>) -> TriangleMesh {
+        let faces = self
+            .faces
+            .iter()
+            .map(|&positions| MeshFace { positions, normals: None, uvs: None, material: 0 })
+            .collect();
+
+        TriangleMesh::new(MeshData {
+            positions: self.positions,
+            faces,
+            materials: vec![material],
+            ..MeshData::default()
+        })
+    }
+}
+
+/// Reads an STL file into a mesh.
+pub fn load_stl<P: AsRef<Path>>(path: P, material: Arc<dyn Material>) -> Result<TriangleMesh, ParseError> {
+    let bytes = fs::read(path)?;
+    let data = parse_stl(&bytes)?;
+
+    Ok(data.into_mesh(material))
+}
+
+/// Reads a binary or ASCII STL file.
+pub fn parse_stl(bytes: &[u8]) -> Result<StlData, ParseError> {
+    // Binary files may also start with "solid", so trust the size check first
+    if is_binary(bytes) {
+        return parse_binary(bytes);
+    }
+
+    let starts_with_solid = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .is_some_and(|start| bytes[start..].starts_with(b"solid"));
+
+    if starts_with_solid {
+        let text = std::str::from_utf8(bytes).map_err(|_| ParseError::syntax(1, "ASCII STL is not valid UTF-8"))?;
+        parse_ascii(text)
+    } else {
+        parse_binary(bytes)
+    }
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() < BINARY_HEADER_SIZE + 4 {
+        return false;
+    }
+
+    let count = triangle_count(bytes);
+    bytes.len() == BINARY_HEADER_SIZE + 4 + count * BINARY_TRIANGLE_SIZE
+}
+
+fn triangle_count(bytes: &[u8]) -> usize {
+    let raw = &bytes[BINARY_HEADER_SIZE..BINARY_HEADER_SIZE + 4];
+    u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as usize
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<StlData, ParseError> {
+    if bytes.len() < BINARY_HEADER_SIZE + 4 {
+        return Err(ParseError::record(0, "file is too short for a binary STL header"));
+    }
+
+    let count = triangle_count(bytes);
+    let mut data = StlData::default();
+
+    for index in 0..count {
+        let start = BINARY_HEADER_SIZE + 4 + index * BINARY_TRIANGLE_SIZE;
+        let Some(record) = bytes.get(start..start + BINARY_TRIANGLE_SIZE) else {
+            return Err(ParseError::record(
+                index,
+                format!("file ends early, header announced {} triangles", count),
+            ));
+        };
+
+        // Skip the 12 byte facet normal, then read three corners
+        let float = |offset: usize| {
+            let raw = &record[offset..offset + 4];
+            f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64
+        };
+        let corner = |i: usize| {
+            let base = 12 + 12 * i;
+            Point3::new(float(base), float(base + 4), float(base + 8))
+        };
+
+        data.add_triangle([corner(0), corner(1), corner(2)]);
+    }
+
+    Ok(data)
+}
+
+fn parse_ascii(text: &str) -> Result<StlData, ParseError> {
+    let mut data = StlData::default();
+    let mut corners: Vec<Point3> = Vec::with_capacity(3);
+    let mut in_facet = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.as_slice() {
+            [] => {}
+            ["solid", ..] | ["endsolid", ..] | ["outer", "loop"] | ["endloop"] => {}
+            ["facet", "normal", ..] => {
+                if in_facet {
+                    return Err(ParseError::syntax(line_number, "facet started before the previous endfacet"));
+                }
+                in_facet = true;
+                corners.clear();
+            }
+            ["vertex", x, y, z] => {
+                if !in_facet || corners.len() == 3 {
+                    return Err(ParseError::syntax(line_number, "unexpected vertex outside a triangle facet"));
+                }
+                let parse = |token: &str| {
+                    token
+                        .parse::<f64>()
+                        .map_err(|_| ParseError::syntax(line_number, format!("invalid number '{}'", token)))
+                };
+                corners.push(Point3::new(parse(x)?, parse(y)?, parse(z)?));
+            }
+            ["endfacet"] => {
+                if corners.len() != 3 {
+                    return Err(ParseError::syntax(
+                        line_number,
+                        format!("facet has {} vertices, expected 3", corners.len()),
+                    ));
+                }
+                data.add_triangle([corners[0], corners[1], corners[2]]);
+                in_facet = false;
+            }
+            _ => return Err(ParseError::syntax(line_number, format!("unexpected '{}'", line.trim()))),
+        }
+    }
+
+    if in_facet {
+        return Err(ParseError::syntax(text.lines().count(), "file ends inside a facet"));
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::interval::Interval;
+    use crate::material::Lambertian;
+    use crate::ray::Ray;
+    use crate::vec3::Vec3;
+
+    const ASCII_SQUARE: &str = "\
+solid square
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    fn binary_square(header: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0u8; BINARY_HEADER_SIZE];
+        bytes[..header.len()].copy_from_slice(header);
+        bytes.extend(2u32.to_le_bytes());
+
+        let triangles: [[f32; 9]; 2] = [
+            [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+        ];
+        for triangle in triangles {
+            bytes.extend([0.0f32, 0.0, 1.0].iter().flat_map(|v| v.to_le_bytes()));
+            bytes.extend(triangle.iter().flat_map(|v| v.to_le_bytes()));
+            bytes.extend([0u8, 0]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse_ascii_stl_welds_vertices() {
+        let data = parse_stl(ASCII_SQUARE.as_bytes()).unwrap();
+        assert_eq!(data.faces.len(), 2);
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.faces[1], [0, 2, 3]);
+    }
+
+    #[test]
+    fn test_parse_binary_stl() {
+        let data = parse_stl(&binary_square(b"binary square")).unwrap();
+        assert_eq!(data.faces.len(), 2);
+        assert_eq!(data.positions.len(), 4);
+    }
+
+    #[test]
+    fn test_binary_stl_starting_with_solid() {
+        // Many exporters write "solid" into the binary header too
+        let data = parse_stl(&binary_square(b"solid exported by a CAD tool")).unwrap();
+        assert_eq!(data.faces.len(), 2);
+    }
+
+    #[test]
+    fn test_truncated_binary_stl() {
+        let mut bytes = binary_square(b"binary");
+        bytes.truncate(bytes.len() - 10);
+
+        let err = parse_stl(&bytes).unwrap_err();
+        assert!(matches!(err, ParseError::Record { index: 1, .. }));
+    }
+
+    #[test]
+    fn test_ascii_errors_report_line_numbers() {
+        let bad = ASCII_SQUARE.replace("vertex 1 1 0\n    endloop\n  endfacet\n  facet", "vertex 1 x 0\n    endloop\n  endfacet\n  facet");
+        let err = parse_stl(bad.as_bytes()).unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 6, .. }));
+
+        let err = parse_stl(b"solid a\nfacet normal 0 0 1\nouter loop\nvertex 0 0 0\nendloop\nendfacet\n").unwrap_err();
+        assert!(matches!(err, ParseError::Syntax { line: 6, .. }));
+    }
+
+    #[test]
+    fn test_stl_mesh_is_hittable() {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        let mesh = parse_stl(ASCII_SQUARE.as_bytes()).unwrap().into_mesh(material);
+        let ray = Ray::new(Point3::new(0.2, 0.8, 3.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(mesh.hit(&ray, &Interval::new(0.001, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 3.0);
+    }
+}
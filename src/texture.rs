@@ -0,0 +1,348 @@
+//! Colors that vary over a surface.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::image::{load_image, Image};
+use crate::parse_error::ParseError;
+use crate::perlin::Perlin;
+use crate::vec3::Point3;
+
+/// Color as a function of the surface coordinates `u`, `v` and the hit point.
+pub trait Texture: Send + Sync {
+    /// Color at texture coordinates `u`, `v` and point `p`.
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+/// The same color everywhere.
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    /// Texture of a single color.
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+/// Checkerboard of unit cubes in space, so it works without UVs.
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    /// Checkerboard of cubes `scale` wide alternating between two textures.
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { inv_scale: 1.0 / scale, even, odd }
+    }
+
+    /// Checkerboard alternating between two colors.
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i64;
+        let y = (self.inv_scale * p.y).floor() as i64;
+        let z = (self.inv_scale * p.z).floor() as i64;
+
+        if (x + y + z).rem_euclid(2) == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+/// How an image texture is sampled between texels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    /// Color of the closest texel.
+    Nearest,
+    /// Blend of the four closest texels.
+    Bilinear,
+}
+
+/// What happens to UVs outside [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    /// Tile the image.
+    Repeat,
+    /// Repeat the edge texels.
+    Clamp,
+    /// Tile the image, flipping every other copy.
+    Mirror,
+}
+
+impl WrapMode {
+    fn apply(self, i: i64, n: usize) -> usize {
+        let n = n as i64;
+        let i = match self {
+            WrapMode::Repeat => i.rem_euclid(n),
+            WrapMode::Clamp => i.clamp(0, n - 1),
+            WrapMode::Mirror => {
+                let m = i.rem_euclid(2 * n);
+                if m < n { m } else { 2 * n - 1 - m }
+            }
+        };
+        i as usize
+    }
+}
+
+/// Image looked up by UV, with v = 0 at the bottom row.
+pub struct ImageTexture {
+    image: Arc<Image>,
+    filter: Filter,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    /// Bilinear, repeating texture over `image`.
+    pub fn new(image: Arc<Image>) -> Self {
+        Self { image, filter: Filter::Bilinear, wrap: WrapMode::Repeat }
+    }
+
+    /// Reads the texture from any image file [`load_image`] understands.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
+        Ok(Self::new(Arc::new(load_image(path)?)))
+    }
+
+    /// Sets the filter, bilinear by default.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sets the wrap mode, repeat by default.
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let x = self.wrap.apply(i, self.image.width());
+        let y = self.wrap.apply(j, self.image.height());
+        self.image.pixel(x, y)
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // Debugging aid for missing images: solid cyan
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        let x = u * self.image.width() as f64;
+        let y = (1.0 - v) * self.image.height() as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // Texel centers sit at half integer coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (i, j) = (x.floor(), y.floor());
+                let (tx, ty) = (x - i, y - j);
+                let (i, j) = (i as i64, j as i64);
+
+                let top = (1.0 - tx) * self.texel(i, j) + tx * self.texel(i + 1, j);
+                let bottom = (1.0 - tx) * self.texel(i, j + 1) + tx * self.texel(i + 1, j + 1);
+                (1.0 - ty) * top + ty * bottom
+            }
+        }
+    }
+}
+
+/// Grey Perlin noise, optionally summed over several octaves.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+    turbulence_depth: u32,
+}
+
+impl NoiseTexture {
+    /// Single octave of noise, `scale` setting its frequency.
+    pub fn new(seed: u64, scale: f64) -> Self {
+        Self { noise: Perlin::new(seed), scale, turbulence_depth: 0 }
+    }
+
+    /// Turbulence summed over `depth` octaves.
+    pub fn turbulent(seed: u64, scale: f64, depth: u32) -> Self {
+        Self { noise: Perlin::new(seed), scale, turbulence_depth: depth }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let p = self.scale * *p;
+        let value = if self.turbulence_depth > 0 {
+            self.noise.turb(&p, self.turbulence_depth)
+        } else {
+            // Map [-1, 1] to [0, 1]
+            0.5 * (1.0 + self.noise.noise(&p))
+        };
+        Color::new(1.0, 1.0, 1.0) * value
+    }
+}
+
+/// Veins along z, phase shifted by turbulence.
+pub struct MarbleTexture {
+    noise: Perlin,
+    scale: f64,
+    albedo: Color,
+}
+
+impl MarbleTexture {
+    /// Marble in shades of `albedo`, `scale` setting how close the veins are.
+    pub fn new(seed: u64, scale: f64, albedo: Color) -> Self {
+        Self { noise: Perlin::new(seed), scale, albedo }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let veins = 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin());
+        self.albedo * veins
+    }
+}
+
+/// Growth rings around the y axis, warped by turbulence.
+pub struct WoodTexture {
+    noise: Perlin,
+    scale: f64,
+    light: Color,
+    dark: Color,
+}
+
+impl WoodTexture {
+    /// Wood from `light` to `dark`, with `scale` rings per unit.
+    pub fn new(seed: u64, scale: f64, light: Color, dark: Color) -> Self {
+        Self { noise: Perlin::new(seed), scale, light, dark }
+    }
+}
+
+impl Texture for WoodTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let distance = (p.x * p.x + p.z * p.z).sqrt();
+        let rings = self.scale * distance + 2.0 * self.noise.turb(p, 4);
+        let t = rings - rings.floor();
+        (1.0 - t) * self.light + t * self.dark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn origin() -> Point3 {
+        Point3::new(0.0, 0.0, 0.0)
+    }
+
+    // 2x2 image: black and white on top, red and blue at the bottom
+    fn image_texture() -> ImageTexture {
+        let pixels = vec![
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+        ];
+        ImageTexture::new(Arc::new(Image::from_pixels(2, 2, pixels)))
+    }
+
+    #[test]
+    fn test_solid_color() {
+        let texture = SolidColor::new(Color::new(0.2, 0.4, 0.6));
+        assert_eq!(texture.value(0.3, 0.7, &origin()), Color::new(0.2, 0.4, 0.6));
+    }
+
+    #[test]
+    fn test_checker_alternates_in_space() {
+        let texture = CheckerTexture::from_colors(0.5, Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(texture.value(0.0, 0.0, &Point3::new(0.1, 0.1, 0.1)), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(texture.value(0.0, 0.0, &Point3::new(0.6, 0.1, 0.1)), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(texture.value(0.0, 0.0, &Point3::new(-0.1, 0.1, 0.1)), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(texture.value(0.0, 0.0, &Point3::new(-0.1, -0.1, 0.1)), Color::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_nearest_lookup_flips_v() {
+        let texture = image_texture().with_filter(Filter::Nearest);
+        assert_eq!(texture.value(0.25, 0.75, &origin()), Color::new(0.0, 0.0, 0.0));
+        assert_eq!(texture.value(0.75, 0.75, &origin()), Color::new(1.0, 1.0, 1.0));
+        assert_eq!(texture.value(0.25, 0.25, &origin()), Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_bilinear_blends_neighbours() {
+        let texture = image_texture().with_wrap(WrapMode::Clamp);
+
+        // On a texel center there is no blending
+        assert_eq!(texture.value(0.75, 0.25, &origin()), Color::new(0.0, 0.0, 1.0));
+
+        // Exactly between all four texels
+        let c = texture.value(0.5, 0.5, &origin());
+        assert!((c - Color::new(0.5, 0.25, 0.5)).length() < EPSILON);
+    }
+
+    #[test]
+    fn test_wrap_modes() {
+        assert_eq!(WrapMode::Repeat.apply(-1, 4), 3);
+        assert_eq!(WrapMode::Repeat.apply(5, 4), 1);
+        assert_eq!(WrapMode::Clamp.apply(-3, 4), 0);
+        assert_eq!(WrapMode::Clamp.apply(9, 4), 3);
+        assert_eq!(WrapMode::Mirror.apply(-1, 4), 0);
+        assert_eq!(WrapMode::Mirror.apply(4, 4), 3);
+        assert_eq!(WrapMode::Mirror.apply(9, 4), 1);
+
+        // Repeat wraps UVs outside [0, 1] back onto the image
+        let texture = image_texture().with_filter(Filter::Nearest);
+        assert_eq!(texture.value(1.25, 1.75, &origin()), texture.value(0.25, 0.75, &origin()));
+    }
+
+    #[test]
+    fn test_noise_textures_are_reproducible() {
+        let points = [Point3::new(0.3, 1.7, -2.2), Point3::new(5.1, -0.4, 0.9)];
+        let textures: [(Box<dyn Texture>, Box<dyn Texture>); 4] = [
+            (Box::new(NoiseTexture::new(9, 4.0)), Box::new(NoiseTexture::new(9, 4.0))),
+            (Box::new(NoiseTexture::turbulent(9, 4.0, 7)), Box::new(NoiseTexture::turbulent(9, 4.0, 7))),
+            (
+                Box::new(MarbleTexture::new(9, 4.0, Color::new(1.0, 1.0, 1.0))),
+                Box::new(MarbleTexture::new(9, 4.0, Color::new(1.0, 1.0, 1.0))),
+            ),
+            (
+                Box::new(WoodTexture::new(9, 4.0, Color::new(0.8, 0.6, 0.4), Color::new(0.4, 0.2, 0.1))),
+                Box::new(WoodTexture::new(9, 4.0, Color::new(0.8, 0.6, 0.4), Color::new(0.4, 0.2, 0.1))),
+            ),
+        ];
+
+        for (a, b) in &textures {
+            for p in &points {
+                let value = a.value(0.0, 0.0, p);
+                assert_eq!(value, b.value(0.0, 0.0, p));
+                assert!(value.x >= 0.0 && value.x <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_marble_veins_follow_z() {
+        // Without turbulence on the lattice the pattern is a pure sine of z
+        let texture = MarbleTexture::new(1, 1.0, Color::new(1.0, 1.0, 1.0));
+        let p = Point3::new(0.0, 0.0, 0.0);
+        assert_eq!(texture.value(0.0, 0.0, &p), Color::new(0.5, 0.5, 0.5));
+    }
+}
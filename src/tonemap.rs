@@ -0,0 +1,254 @@
+//! Tone mapping operators for 8 and 16 bit output.
+
+use std::str::FromStr;
+
+use crate::color::Color;
+use crate::image::Image;
+
+/// Curve squeezing scene radiance into the [0, 1] a display can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Operator {
+    /// Scale by the white point and clip.
+    #[default]
+    None,
+    /// x / (1 + x).
+    Reinhard,
+    /// Reinhard that reaches white at the white point instead of infinity.
+    ReinhardExtended,
+    /// Narkowicz's fit of the ACES filmic curve.
+    Aces,
+    /// John Hable's Uncharted 2 filmic curve.
+    Hable,
+    /// Sigmoid in log space after a slight desaturation, after Blender's AgX,
+    /// which keeps very bright colors from skewing towards pure primaries.
+    Agx,
+}
+
+impl Operator {
+    // Linear value shown as white when no white point is given, None for
+    // curves that approach white on their own
+    fn default_white(self) -> Option<f64> {
+        match self {
+            Operator::None => Some(1.0),
+            Operator::ReinhardExtended => Some(4.0),
+            Operator::Hable => Some(11.2),
+            Operator::Reinhard | Operator::Aces | Operator::Agx => None,
+        }
+    }
+
+    fn curve(self, c: Color, white: f64) -> Color {
+        let per_channel = |f: &dyn Fn(f64) -> f64| Color::new(f(c.x), f(c.y), f(c.z));
+        match self {
+            Operator::None => c,
+            Operator::Reinhard => per_channel(&|x| x / (1.0 + x)),
+            Operator::ReinhardExtended => per_channel(&|x| x * (1.0 + x / (white * white)) / (1.0 + x)),
+            Operator::Aces => per_channel(&|x| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)),
+            Operator::Hable => per_channel(&hable),
+            Operator::Agx => agx(c),
+        }
+    }
+}
+
+impl FromStr for Operator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Operator::None),
+            "reinhard" => Ok(Operator::Reinhard),
+            "reinhard-extended" => Ok(Operator::ReinhardExtended),
+            "aces" => Ok(Operator::Aces),
+            "hable" => Ok(Operator::Hable),
+            "agx" => Ok(Operator::Agx),
+            other => Err(format!(
+                "unknown tone mapping operator '{}' (expected none, reinhard, reinhard-extended, aces, hable or agx)",
+                other
+            )),
+        }
+    }
+}
+
+/// Display transform applied to the linear render before it is quantized.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ToneMap {
+    /// Curve applied after the exposure.
+    pub operator: Operator,
+    /// In stops, every +1 doubles the radiance.
+    pub exposure: f64,
+    /// Linear value, after exposure, that maps to full white. The curve is
+    /// scaled so it reaches 1 there; None keeps the operator's own default.
+    pub white_point: Option<f64>,
+}
+
+impl ToneMap {
+    /// Tone map with the given curve, no exposure change and the default white
+    /// point.
+    pub fn new(operator: Operator) -> Self {
+        Self { operator, ..Self::default() }
+    }
+
+    /// Result is still linear, in [0, 1].
+    pub fn apply(&self, color: Color) -> Color {
+        let finite = |x: f64| if x.is_finite() { x.max(0.0) } else { 0.0 };
+        let exposed = 2f64.powf(self.exposure) * Color::new(finite(color.x), finite(color.y), finite(color.z));
+
+        let white = self.white_point.or(self.operator.default_white());
+        let mapped = match white {
+            Some(white) => {
+                let scale = self.operator.curve(Color::from_scalar(white), white).x;
+                self.operator.curve(exposed, white) / scale
+            }
+            None => self.operator.curve(exposed, f64::INFINITY),
+        };
+        Color::new(mapped.x.clamp(0.0, 1.0), mapped.y.clamp(0.0, 1.0), mapped.z.clamp(0.0, 1.0))
+    }
+
+    /// Tone maps every pixel, keeping the color space.
+    pub fn apply_image(&self, image: &Image) -> Image {
+        image.map_pixels(|pixel| self.apply(pixel))
+    }
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15; // shoulder strength
+    const B: f64 = 0.50; // linear strength
+    const C: f64 = 0.10; // linear angle
+    const D: f64 = 0.20; // toe strength
+    const E: f64 = 0.02; // toe numerator
+    const F: f64 = 0.30; // toe denominator
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+// Minimal AgX: inset matrix, log2 encoding of the range [-12.47, 4.03] stops
+// around mid grey, polynomial fit of the sigmoid, outset matrix and back to
+// linear with the 2.2 power the fit was made for
+fn agx(c: Color) -> Color {
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let inset = Color::new(
+        0.842479062253094 * c.x + 0.0784335999999992 * c.y + 0.0792237451477643 * c.z,
+        0.0423282422610123 * c.x + 0.878468636469772 * c.y + 0.0791661274605434 * c.z,
+        0.0423756549057051 * c.x + 0.0784336 * c.y + 0.879142973793104 * c.z,
+    );
+
+    let sigmoid = |x: f64| {
+        let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    };
+    let (r, g, b) = (sigmoid(inset.x), sigmoid(inset.y), sigmoid(inset.z));
+
+    let outset = Color::new(
+        1.19687900512017 * r - 0.0980208811401368 * g - 0.0990297440797205 * b,
+        -0.0528968517574562 * r + 1.15190312990417 * g - 0.0989611768448433 * b,
+        -0.0529716355144438 * r - 0.0980434501171241 * g + 1.15107367264116 * b,
+    );
+    let linear = |x: f64| x.max(0.0).powf(2.2);
+    Color::new(linear(outset.x), linear(outset.y), linear(outset.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPERATORS: [Operator; 6] = [
+        Operator::None,
+        Operator::Reinhard,
+        Operator::ReinhardExtended,
+        Operator::Aces,
+        Operator::Hable,
+        Operator::Agx,
+    ];
+
+    fn grey(tonemap: &ToneMap, x: f64) -> f64 {
+        tonemap.apply(Color::from_scalar(x)).x
+    }
+
+    #[test]
+    fn test_curves_are_monotonic_and_bounded() {
+        for operator in OPERATORS {
+            let tonemap = ToneMap::new(operator);
+            assert!(grey(&tonemap, 0.0) < 1e-3, "{:?}", operator);
+
+            let mut previous = 0.0;
+            for i in 1..200 {
+                let value = grey(&tonemap, 0.01 * 1.07f64.powi(i));
+                assert!((0.0..=1.0).contains(&value), "{:?}", operator);
+                assert!(value >= previous, "{:?} is not monotonic", operator);
+                previous = value;
+            }
+            // Bright values end up near white instead of wrapping around
+            assert!(grey(&tonemap, 1e4) > 0.95, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn test_known_values() {
+        let reinhard = ToneMap::new(Operator::Reinhard);
+        assert!((grey(&reinhard, 1.0) - 0.5).abs() < 1e-12);
+        assert!((grey(&reinhard, 3.0) - 0.75).abs() < 1e-12);
+
+        // Plain clipping is the identity below 1
+        let none = ToneMap::new(Operator::None);
+        assert_eq!(none.apply(Color::new(0.25, 0.5, 2.0)), Color::new(0.25, 0.5, 1.0));
+
+        // Extended Reinhard and Hable reach white exactly at their white point
+        assert!((grey(&ToneMap::new(Operator::ReinhardExtended), 4.0) - 1.0).abs() < 1e-12);
+        assert!((grey(&ToneMap::new(Operator::Hable), 11.2) - 1.0).abs() < 1e-12);
+        assert!(grey(&ToneMap::new(Operator::Hable), 5.0) < 1.0);
+    }
+
+    #[test]
+    fn test_exposure_and_white_point() {
+        let plus_one = ToneMap { exposure: 1.0, ..ToneMap::new(Operator::Reinhard) };
+        let reinhard = ToneMap::new(Operator::Reinhard);
+        assert!((grey(&plus_one, 0.5) - grey(&reinhard, 1.0)).abs() < 1e-12);
+
+        for operator in OPERATORS {
+            let tonemap = ToneMap { white_point: Some(2.0), ..ToneMap::new(operator) };
+            assert!((grey(&tonemap, 2.0) - 1.0).abs() < 1e-9, "{:?}", operator);
+            assert!(grey(&tonemap, 1.0) < 1.0, "{:?}", operator);
+        }
+    }
+
+    #[test]
+    fn test_agx_keeps_greys_neutral() {
+        let agx = ToneMap::new(Operator::Agx);
+        let mid = agx.apply(Color::from_scalar(0.18));
+        assert!((mid.x - mid.y).abs() < 1e-3 && (mid.y - mid.z).abs() < 1e-3, "{:?}", mid);
+        assert!(mid.x > 0.1 && mid.x < 0.3, "{:?}", mid);
+
+        // A very bright red desaturates towards white instead of clipping
+        let red = agx.apply(Color::new(50.0, 1.0, 1.0));
+        assert!(red.x > red.y && red.y > 0.3, "{:?}", red);
+    }
+
+    #[test]
+    fn test_invalid_input_is_black() {
+        let tonemap = ToneMap::new(Operator::Aces);
+        assert_eq!(tonemap.apply(Color::new(f64::NAN, -1.0, f64::INFINITY)), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_apply_image_keeps_the_color_space() {
+        use crate::color_space::ColorSpace;
+
+        let image = Image::from_pixels(2, 1, vec![Color::new(3.0, 1.0, 0.0), Color::new(0.5, 0.5, 0.5)])
+            .convert_to(ColorSpace::Rec2020);
+        let tonemap = ToneMap::new(Operator::Reinhard);
+        let mapped = tonemap.apply_image(&image);
+
+        assert_eq!(mapped.color_space(), ColorSpace::Rec2020);
+        assert_eq!((mapped.width(), mapped.height()), (2, 1));
+        assert_eq!(mapped.pixel(1, 0), tonemap.apply(image.pixel(1, 0)));
+    }
+
+    #[test]
+    fn test_operator_from_str() {
+        assert_eq!("ACES".parse(), Ok(Operator::Aces));
+        assert_eq!("reinhard-extended".parse(), Ok(Operator::ReinhardExtended));
+        assert!("filmic".parse::<Operator>().is_err());
+    }
+}
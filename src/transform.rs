@@ -0,0 +1,335 @@
+//! Affine transforms.
+
+use std::ops::Mul;
+
+use crate::aabb::Aabb;
+use crate::commons::degrees_to_radians;
+use crate::vec3::{Point3, Vec3};
+
+/// Row major 4x4 matrix acting on column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    /// Rows of the matrix.
+    pub m: [[f64; 4]; 4],
+}
+
+impl Mat4 {
+    /// The identity matrix.
+    pub const IDENTITY: Mat4 = Mat4 {
+        m: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    /// Matrix from its rows.
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    /// The transposed matrix.
+    pub fn transpose(&self) -> Mat4 {
+        Mat4::new(std::array::from_fn(|i| std::array::from_fn(|j| self.m[j][i])))
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting, None if singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::IDENTITY.m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+
+        Some(Mat4::new(inv))
+    }
+
+    /// Applies the matrix to a point, dividing by w if it is not 1.
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+
+        if w == 1.0 { Point3::new(x, y, z) } else { Point3::new(x, y, z) / w }
+    }
+
+    /// Applies the linear part of the matrix to a direction.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        Mat4::new(std::array::from_fn(|i| {
+            std::array::from_fn(|j| (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum())
+        }))
+    }
+}
+
+/// Affine transform together with its inverse, so neither has to be
+/// recomputed per ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl Transform {
+    /// The transform that changes nothing.
+    pub const IDENTITY: Transform = Transform { matrix: Mat4::IDENTITY, inverse: Mat4::IDENTITY };
+
+    /// None if the matrix can't be inverted.
+    pub fn new(matrix: Mat4) -> Option<Self> {
+        Some(Self { matrix, inverse: matrix.inverse()? })
+    }
+
+    /// Moves everything by `offset`.
+    pub fn translate(offset: Vec3) -> Self {
+        let matrix = Mat4::new([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = Mat4::new([
+            [1.0, 0.0, 0.0, -offset.x],
+            [0.0, 1.0, 0.0, -offset.y],
+            [0.0, 0.0, 1.0, -offset.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Self { matrix, inverse }
+    }
+
+    /// Scales by `factors` along each axis, None if any of them is zero or
+    /// not finite.
+    pub fn scale(factors: Vec3) -> Option<Self> {
+        if ![factors.x, factors.y, factors.z].iter().all(|f| f.is_finite() && *f != 0.0) {
+            return None;
+        }
+
+        let diagonal = |x: f64, y: f64, z: f64| {
+            Mat4::new([
+                [x, 0.0, 0.0, 0.0],
+                [0.0, y, 0.0, 0.0],
+                [0.0, 0.0, z, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ])
+        };
+        Some(Self {
+            matrix: diagonal(factors.x, factors.y, factors.z),
+            inverse: diagonal(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z),
+        })
+    }
+
+    /// Scales by `factor` along every axis, None if it is zero or not finite.
+    pub fn uniform_scale(factor: f64) -> Option<Self> {
+        Self::scale(Vec3::from_scalar(factor))
+    }
+
+    /// Counter-clockwise rotation around the x axis.
+    pub fn rotate_x(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(1.0, 0.0, 0.0), degrees)
+    }
+
+    /// Counter-clockwise rotation around the y axis.
+    pub fn rotate_y(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 1.0, 0.0), degrees)
+    }
+
+    /// Counter-clockwise rotation around the z axis.
+    pub fn rotate_z(degrees: f64) -> Self {
+        Self::rotate(Vec3::new(0.0, 0.0, 1.0), degrees)
+    }
+
+    /// Counter-clockwise rotation around `axis` (Rodrigues' formula).
+    pub fn rotate(axis: Vec3, degrees: f64) -> Self {
+        let a = axis.unit_vector();
+        let (sin, cos) = degrees_to_radians(degrees).sin_cos();
+        let t = 1.0 - cos;
+
+        let matrix = Mat4::new([
+            [t * a.x * a.x + cos, t * a.x * a.y - sin * a.z, t * a.x * a.z + sin * a.y, 0.0],
+            [t * a.x * a.y + sin * a.z, t * a.y * a.y + cos, t * a.y * a.z - sin * a.x, 0.0],
+            [t * a.x * a.z - sin * a.y, t * a.y * a.z + sin * a.x, t * a.z * a.z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // Rotations are orthogonal
+        Self { matrix, inverse: matrix.transpose() }
+    }
+
+    /// Applies self first, then `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    /// The transform undoing this one.
+    pub fn inverse(&self) -> Transform {
+        Transform { matrix: self.inverse, inverse: self.matrix }
+    }
+
+    /// The forward matrix.
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    /// Transforms a point.
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    /// Transforms a direction, ignoring the translation.
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Normals go through the inverse transpose to stay perpendicular to
+    /// the surface under non-uniform scaling. The result is not normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    /// Box around the eight transformed corners of `bbox`.
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        if bbox.is_empty() {
+            return *bbox;
+        }
+
+        let mut min = Point3::from_scalar(f64::INFINITY);
+        let mut max = Point3::from_scalar(f64::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { bbox.x().min() } else { bbox.x().max() },
+                if i & 2 == 0 { bbox.y().min() } else { bbox.y().max() },
+                if i & 4 == 0 { bbox.z().min() } else { bbox.z().max() },
+            );
+            let p = self.point(corner);
+            min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+        Aabb::from_points(min, max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::Interval;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_translate_moves_points_not_vectors() {
+        let t = Transform::translate(Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(t.point(Point3::new(1.0, 1.0, 1.0)), Point3::new(2.0, 3.0, 4.0));
+        assert_eq!(t.vector(Vec3::new(1.0, 1.0, 1.0)), Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(t.inverse().point(Point3::new(2.0, 3.0, 4.0)), Point3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_rotations() {
+        assert_near(Transform::rotate_y(90.0).vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 0.0, -1.0));
+        assert_near(Transform::rotate_x(90.0).vector(Vec3::new(0.0, 1.0, 0.0)), Vec3::new(0.0, 0.0, 1.0));
+        assert_near(Transform::rotate_z(90.0).vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+
+        // Any axis: a third of a turn around the diagonal cycles the axes
+        let r = Transform::rotate(Vec3::new(1.0, 1.0, 1.0), 120.0);
+        assert_near(r.vector(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_then_applies_in_order() {
+        let t = Transform::rotate_y(90.0).then(&Transform::translate(Vec3::new(10.0, 0.0, 0.0)));
+        assert_near(t.point(Point3::new(1.0, 0.0, 0.0)), Point3::new(10.0, 0.0, -1.0));
+        assert_near(t.inverse().point(Point3::new(10.0, 0.0, -1.0)), Point3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_general_inverse() {
+        let m = Mat4::new([
+            [2.0, 0.0, 1.0, 3.0],
+            [0.0, 1.0, 0.0, -1.0],
+            [1.0, 0.0, 1.0, 0.5],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let product = m * m.inverse().unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((product.m[i][j] - Mat4::IDENTITY.m[i][j]).abs() < EPSILON);
+            }
+        }
+
+        let singular = Mat4::new([[1.0, 2.0, 0.0, 0.0], [2.0, 4.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]);
+        assert!(Transform::new(singular).is_none());
+        assert!(Transform::scale(Vec3::new(1.0, 0.0, 1.0)).is_none());
+        assert!(Transform::scale(Vec3::new(1.0, 1.0, f64::INFINITY)).is_none());
+        assert!(Transform::uniform_scale(f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_normals_stay_perpendicular_under_scaling() {
+        let t = Transform::scale(Vec3::new(4.0, 1.0, 1.0)).unwrap();
+        // Surface x + y = 0, with tangent (1, -1, 0) and normal (1, 1, 0)
+        let tangent = t.vector(Vec3::new(1.0, -1.0, 0.0));
+        let normal = t.normal(Vec3::new(1.0, 1.0, 0.0));
+        assert!(tangent.dot(normal).abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        let bbox = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+        let rotated = Transform::rotate_y(45.0).bounding_box(&bbox);
+        let diagonal = 2f64.sqrt();
+
+        assert_eq!(rotated.x().min(), 0.0);
+        assert!((rotated.x().max() - diagonal).abs() < EPSILON);
+        assert!((rotated.z().min() + diagonal / 2.0).abs() < EPSILON);
+        assert!((rotated.z().max() - diagonal / 2.0).abs() < EPSILON);
+        assert_eq!(rotated.y(), Interval::new(0.0, 1.0));
+    }
+}
@@ -0,0 +1,255 @@
+//! Single triangles.
+
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::{Point3, Vec3};
+
+// Rays closer than this to parallel with the triangle plane are treated as misses
+const PARALLEL_EPSILON: f64 = 1e-12;
+
+/// A single triangle, flat shaded unless it is given vertex normals.
+#[derive(Clone)]
+pub struct Triangle {
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+    mat: Arc<dyn Material>,
+    bbox: Aabb
+}
+
+impl Triangle {
+    /// Triangle with corners `a`, `b` and `c`, mapped onto the lower left half of
+    /// the unit UV square.
+    pub fn new(a: Point3, b: Point3, c: Point3, material: Arc<dyn Material>) -> Self {
+        Self {
+            vertices: [a, b, c],
+            normals: None,
+            // Map the triangle onto the lower-left half of the unit UV square
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            mat: material,
+            bbox: triangle_bounds(a, b, c),
+        }
+    }
+
+    /// Shading normals for each vertex, interpolated across the face.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    /// Texture coordinates for each vertex.
+    pub fn with_uvs(mut self, uvs: [(f64, f64); 3]) -> Self {
+        self.uvs = uvs;
+        self
+    }
+}
+
+pub(crate) fn triangle_bounds(a: Point3, b: Point3, c: Point3) -> Aabb {
+    let min = Point3::new(a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y), a.z.min(b.z).min(c.z));
+    let max = Point3::new(a.x.max(b.x).max(c.x), a.y.max(b.y).max(c.y), a.z.max(b.z).max(c.z));
+
+    Aabb::from_points(min, max)
+}
+
+// Intersect a ray with the triangle (a, b, c) using the Möller–Trumbore
+// algorithm, returning t and the barycentric weights of b and c
+pub(crate) fn intersect_triangle(r: &Ray, a: Point3, b: Point3, c: Point3, t: &Interval) -> Option<(f64, f64, f64)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+
+    let pvec = r.direction().cross(edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - a;
+    let u = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let qvec = tvec.cross(edge1);
+    let v = r.direction().dot(qvec) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let root = edge2.dot(qvec) * inv_det;
+    if !t.surrounds(root) {
+        return None;
+    }
+
+    Some((root, u, v))
+}
+
+// Fill in the hit record shared by every triangle-like primitive. The geometric
+// normal decides which side was hit, the shading normal (if any) is what the
+// materials see.
+pub(crate) fn set_triangle_hit(
+    rec: &mut HitRecord,
+    r: &Ray,
+    root: f64,
+    (b1, b2): (f64, f64),
+    vertices: [Point3; 3],
+    normals: Option<[Vec3; 3]>,
+    uvs: [(f64, f64); 3],
+) {
+    let b0 = 1.0 - b1 - b2;
+
+    let mut outward_normal = (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .unit_vector();
+
+    let shading_normal = normals.map(|n| (b0 * n[0] + b1 * n[1] + b2 * n[2]).unit_vector());
+
+    // Vertex normals are authoritative about which way is outside
+    if let Some(shading) = shading_normal {
+        if shading.dot(outward_normal) < 0.0 {
+            outward_normal = -outward_normal;
+        }
+    }
+
+    rec.set_t(root);
+    rec.set_p(r.at(root));
+    rec.set_face_normal(r, outward_normal);
+
+    if let Some(shading) = shading_normal {
+        rec.set_normal(if rec.front_face() { shading } else { -shading });
+    }
+
+    rec.u = b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0;
+    rec.v = b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1;
+    rec.barycentric = Vec3::new(b0, b1, b2);
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        let [a, b, c] = self.vertices;
+
+        let Some((root, b1, b2)) = intersect_triangle(r, a, b, c, t) else {
+            return false;
+        };
+
+        set_triangle_hit(rec, r, root, (b1, b2), self.vertices, self.normals, self.uvs);
+        rec.mat = self.mat.clone();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::material::Lambertian;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn unit_triangle() -> Triangle {
+        let material = Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
+        Triangle::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            material,
+        )
+    }
+
+    fn approx_eq(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < EPSILON
+    }
+
+    #[test]
+    fn test_ray_hits_triangle() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(triangle.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 1.0);
+        assert!(approx_eq(rec.p(), Point3::new(0.25, 0.25, 0.0)));
+        assert!(approx_eq(rec.normal(), Vec3::new(0.0, 0.0, 1.0)));
+        assert!(rec.front_face());
+        assert!(approx_eq(rec.barycentric, Vec3::new(0.5, 0.25, 0.25)));
+    }
+
+    #[test]
+    fn test_ray_misses_triangle() {
+        let triangle = unit_triangle();
+        let outside = Ray::new(Point3::new(0.75, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let parallel = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        let mut rec = HitRecord::default();
+
+        assert!(!triangle.hit(&outside, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!(!triangle.hit(&parallel, &Interval::new(0.0, INFINITY), &mut rec));
+    }
+
+    #[test]
+    fn test_ray_hits_triangle_outside_interval() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(!triangle.hit(&ray, &Interval::new(0.0, 0.5), &mut rec));
+    }
+
+    #[test]
+    fn test_back_face_hit() {
+        let triangle = unit_triangle();
+        let ray = Ray::new(Point3::new(0.25, 0.25, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(triangle.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!(!rec.front_face());
+        assert!(approx_eq(rec.normal(), Vec3::new(0.0, 0.0, -1.0)));
+    }
+
+    #[test]
+    fn test_interpolated_normals_and_uvs() {
+        let tilted = Vec3::new(1.0, 0.0, 1.0).unit_vector();
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let triangle = unit_triangle()
+            .with_normals([up, tilted, up])
+            .with_uvs([(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        let ray = Ray::new(Point3::new(0.5, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(triangle.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        let expected = (0.5 * up + 0.5 * tilted).unit_vector();
+        assert!(approx_eq(rec.normal(), expected));
+        assert!((rec.u - 0.5).abs() < EPSILON);
+        assert!(rec.v.abs() < EPSILON);
+    }
+
+    #[test]
+    fn test_vertex_normals_decide_outside() {
+        // Vertex normals pointing down flip which side counts as the front
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        let triangle = unit_triangle().with_normals([down, down, down]);
+        let ray = Ray::new(Point3::new(0.25, 0.25, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let mut rec = HitRecord::default();
+
+        assert!(triangle.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert!(!rec.front_face());
+        assert!(approx_eq(rec.normal(), Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_triangle_bounding_box() {
+        let bbox = unit_triangle().bounding_box();
+        assert_eq!(bbox.x(), Interval::new(0.0, 1.0));
+        assert_eq!(bbox.y(), Interval::new(0.0, 1.0));
+        assert!(bbox.z().size() > 0.0);
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::flat_bvh::{FlatBvh, SplitStrategy};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle::{intersect_triangle, set_triangle_hit, triangle_bounds};
+use crate::vec3::{Point3, Vec3};
+
+// One triangle of a mesh, as indices into the shared vertex arrays
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    // Index into MeshData::materials
+    pub material: usize,
+}
+
+// Vertex attributes shared by every triangle of the mesh
+#[derive(Default)]
+pub(crate) struct MeshData {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub materials: Vec<Arc<dyn Material>>,
+}
+
+impl MeshData {
+    fn face_vertices(&self, face: &MeshFace) -> [Point3; 3] {
+        face.positions.map(|i| self.positions[i])
+    }
+}
+
+// A triangle referring back into the mesh, so vertices are stored only once
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        let face = &self.mesh.faces[self.face];
+        let vertices = self.mesh.face_vertices(face);
+
+        let Some((root, b1, b2)) = intersect_triangle(r, vertices[0], vertices[1], vertices[2], t) else {
+            return false;
+        };
+
+        let normals = face.normals.map(|n| n.map(|i| self.mesh.normals[i]));
+        let uvs = face
+            .uvs
+            .map_or([(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], |uv| uv.map(|i| self.mesh.uvs[i]));
+
+        set_triangle_hit(rec, r, root, (b1, b2), vertices, normals, uvs);
+        rec.mat = self.mesh.materials[face.material].clone();
+
+        true
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.mesh.face_vertices(&self.mesh.faces[self.face]);
+        triangle_bounds(a, b, c)
+    }
+}
+
+/// Indexed triangle mesh with its own BVH over the faces.
+pub struct TriangleMesh {
+    mesh: Arc<MeshData>,
+    bvh: FlatBvh,
+}
+
+impl TriangleMesh {
+    pub(crate) fn new(data: MeshData) -> Self {
+        let mesh = Arc::new(data);
+
+        let triangles: Vec<Arc<dyn Hittable>> = (0..mesh.faces.len())
+            .map(|face| Arc::new(MeshTriangle { mesh: mesh.clone(), face }) as Arc<dyn Hittable>)
+            .collect();
+
+        Self {
+            bvh: FlatBvh::from_objects(triangles, SplitStrategy::Sah),
+            mesh,
+        }
+    }
+
+    /// Number of faces.
+    pub fn triangle_count(&self) -> usize {
+        self.mesh.faces.len()
+    }
+
+    /// Number of distinct vertex positions.
+    pub fn vertex_count(&self) -> usize {
+        self.mesh.positions.len()
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t: &Interval, rec: &mut HitRecord) -> bool {
+        self.bvh.hit(r, t, rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commons::INFINITY;
+    use crate::material::Lambertian;
+
+    // Unit square in the z = 0 plane made of two triangles sharing an edge
+    fn square() -> MeshData {
+        MeshData {
+            positions: vec![
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+                Point3::new(1.0, 1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+            ],
+            normals: Vec::new(),
+            uvs: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            faces: vec![
+                MeshFace { positions: [0, 1, 2], normals: None, uvs: Some([0, 1, 2]), material: 0 },
+                MeshFace { positions: [0, 2, 3], normals: None, uvs: Some([0, 2, 3]), material: 0 },
+            ],
+            materials: vec![Arc::new(Lambertian::new(Vec3::new(0.5, 0.5, 0.5)))],
+        }
+    }
+
+    #[test]
+    fn test_mesh_counts_and_bounds() {
+        let mesh = TriangleMesh::new(square());
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.bounding_box().x(), Interval::new(0.0, 1.0));
+        assert_eq!(mesh.bounding_box().y(), Interval::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn test_mesh_hit_uses_shared_uvs() {
+        let mesh = TriangleMesh::new(square());
+        let mut rec = HitRecord::default();
+
+        let ray = Ray::new(Point3::new(0.25, 0.75, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(mesh.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+        assert_eq!(rec.t(), 1.0);
+        assert!((rec.u - 0.25).abs() < 1e-9);
+        assert!((rec.v - 0.75).abs() < 1e-9);
+
+        let ray = Ray::new(Point3::new(1.5, 0.5, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!mesh.hit(&ray, &Interval::new(0.0, INFINITY), &mut rec));
+    }
+}
@@ -1,3 +1,5 @@
+//! Three component vectors and points.
+
 use std::ops::{
                 Add, 
                 Sub, 
@@ -14,13 +16,18 @@ use crate::commons::{random_double, random_double_range};
 
             
 
+/// Vector of three `f64` components, used for directions, points and colors.
 #[derive(Debug, Clone, Copy,PartialEq)]
 pub struct Vec3 {
+    /// First component.
     pub x: f64,
+    /// Second component.
     pub y: f64,
+    /// Third component.
     pub z: f64,
 }
 
+/// A position in space.
 pub type Point3 = Vec3;
 
 impl Default for Vec3 {
@@ -34,27 +41,32 @@ impl Default for Vec3 {
 }
 
 impl Vec3 {
-    //Construct a new Vec3 instance
+    /// Constructs a new Vec3 instance.
     pub fn new(x: f64, y: f64, z: f64) -> Self {
         Self { x, y, z }
     }
 
+    /// Vector with every component set to `scalar`.
     pub fn from_scalar(scalar: f64) -> Self {
         Self { x: scalar, y: scalar, z: scalar }
     }
 
+    /// Squared length, cheaper than [`Vec3::length`].
     pub fn length_squared(&self) -> f64 {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
+    /// Euclidean length.
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
 
+    /// Dot product.
     pub fn dot(&self,other:Vec3) -> f64{
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
+    /// Cross product.
     pub fn cross(&self,other:Vec3) ->  Self{
         Self{
             x: self.y*other.z - self.z * other.y,
@@ -63,11 +75,13 @@ impl Vec3 {
         }
     }
 
+    /// Vector of length one in the same direction.
     pub fn unit_vector(self) -> Self {
         self / self.length()
     }
 
 
+    /// Uniform random point inside the unit disk in the xy plane.
     pub fn random_in_unit_disk() -> Vec3 {
         loop {
             let p = Vec3::new(
@@ -82,12 +96,14 @@ impl Vec3 {
         }
     }
 
+    /// Whether every component is close to zero.
     pub fn near_zero(&self) -> bool {
         let s = 1e-8;
 
         self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
     }
 
+    /// Random vector with components in [0, 1).
     pub fn random() -> Vec3 {
         Vec3 {
             x: random_double(),
@@ -96,6 +112,7 @@ impl Vec3 {
         }
     }
 
+    /// Random vector with components in [`min`, `max`).
     pub fn random_range(min:f64,max:f64) -> Vec3 {
         Vec3 {
             x: random_double_range(min, max),
@@ -104,9 +121,10 @@ impl Vec3 {
         }
     }
 
+    /// Uniform random direction.
     pub fn random_unit_vector() -> Vec3{
         loop {
-            let p = Vec3::random();
+            let p = Vec3::random_range(-1.0, 1.0);
             let lensq = p.length_squared();
 
             if 1e-160 < lensq && lensq <= 1.0 {
@@ -115,6 +133,7 @@ impl Vec3 {
         }
     }
 
+    /// Uniform random direction on the side of `normal`.
     pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
         let on_unit_sphere = Vec3::random_unit_vector();
 
@@ -127,11 +146,14 @@ impl Vec3 {
         
     }
 
+    /// Mirror reflection of `v` about the surface with normal `n`.
     pub fn reflect(v : &Vec3,n: &Vec3) -> Vec3 {
         let n_unit = n.unit_vector();
         *v - 2.0*v.dot(n_unit) * n_unit
     }
 
+    /// Refraction of `v` through the surface with unit normal `n`, `etai_over_etat`
+    /// being the ratio of the refractive indices.
     pub fn refract(v: &Vec3, n: &Vec3, etai_over_etat: f64) -> Vec3 {
         let uv =  v.unit_vector(); 
         let cos_theta = f64::min((-uv).dot(*n), 1.0);
@@ -142,14 +164,17 @@ impl Vec3 {
     }
 
     //getters
+    /// The x component.
     pub fn x(&self) -> f64 {
         self.x
     }
 
+    /// The y component.
     pub fn y(&self) -> f64 {
         self.y
     }
 
+    /// The z component.
     pub fn z(&self) -> f64 {
         self.z
     }
@@ -452,6 +477,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_random_unit_vector_is_not_biased() {
+        // Sampling the [0, 1) cube instead of [-1, 1) only ever gives
+        // directions in the positive octant
+        crate::commons::seed_random(7);
+        let n = 10000;
+        let mut sum = Vec3::new(0.0, 0.0, 0.0);
+        for _ in 0..n {
+            let v = Vec3::random_unit_vector();
+            assert!((v.length() - 1.0).abs() < 1e-9);
+            sum += v;
+        }
+
+        assert!((sum / n as f64).length() < 0.05);
+    }
+
     #[test]
     fn test_reflect() {
         // Test reflection off a horizontal surface